@@ -0,0 +1,341 @@
+// Detect the flavor of a geometry BLOB and convert it to ISO WKB. GeoPackage uses
+// GeoPackage binary, but the files written by some tools store plain WKB, EWKB
+// (PostGIS' extended WKB), or SpatiaLite's own format instead.
+//
+// cf. https://www.gaia-gis.it/gaia-sins/BLOB-Geometry.html
+
+use std::borrow::Cow;
+
+use crate::gpkg::gpkg_geometry_to_wkb;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum BlobFlavor {
+    Gpkg,
+    Wkb,
+    Ewkb,
+    SpatiaLite,
+}
+
+// The flags of the geometry type in EWKB
+const EWKB_Z: u32 = 0x80000000;
+const EWKB_M: u32 = 0x40000000;
+const EWKB_SRID: u32 = 0x20000000;
+
+// The markers of SpatiaLite BLOB
+const SPATIALITE_START: u8 = 0x00;
+const SPATIALITE_MBR_END: u8 = 0x7C;
+const SPATIALITE_ENTITY: u8 = 0x69;
+const SPATIALITE_END: u8 = 0xFE;
+// start (1) + byte order (1) + SRID (4) + MBR (32) + MBR end (1)
+const SPATIALITE_HEADER_SIZE: usize = 39;
+
+// Guess the flavor from the first bytes. SpatiaLite BLOB is checked before WKB
+// because both can start with 0x00.
+pub(crate) fn detect_blob_flavor(b: &[u8]) -> Option<BlobFlavor> {
+    if b.starts_with(b"GP") {
+        return Some(BlobFlavor::Gpkg);
+    }
+
+    if b.len() > SPATIALITE_HEADER_SIZE + 4
+        && b[0] == SPATIALITE_START
+        && b[1] <= 1
+        && b[SPATIALITE_HEADER_SIZE - 1] == SPATIALITE_MBR_END
+        && b.last() == Some(&SPATIALITE_END)
+    {
+        return Some(BlobFlavor::SpatiaLite);
+    }
+
+    let mut reader = Reader::new(b);
+    let little_endian = match reader.u8().ok()? {
+        0 => false,
+        1 => true,
+        _ => return None,
+    };
+    let geometry_type = reader.u32(little_endian).ok()?;
+    if geometry_type & (EWKB_Z | EWKB_M | EWKB_SRID) != 0 {
+        Some(BlobFlavor::Ewkb)
+    } else {
+        Some(BlobFlavor::Wkb)
+    }
+}
+
+pub(crate) fn blob_to_wkb(b: &[u8]) -> Result<Cow<'_, [u8]>, Error> {
+    match detect_blob_flavor(b) {
+        Some(BlobFlavor::Gpkg) => match gpkg_geometry_to_wkb(b) {
+            [] => Err("Invalid GeoPackage binary: truncated or unknown envelope".into()),
+            wkb => Ok(Cow::Borrowed(wkb)),
+        },
+        Some(BlobFlavor::Wkb) => Ok(Cow::Borrowed(b)),
+        Some(BlobFlavor::Ewkb) => {
+            let mut out = Vec::with_capacity(b.len());
+            ewkb_geometry(&mut Reader::new(b), &mut out)?;
+            Ok(Cow::Owned(out))
+        }
+        Some(BlobFlavor::SpatiaLite) => {
+            let little_endian = b[1] == 1;
+            let mut reader = Reader::new(&b[SPATIALITE_HEADER_SIZE..b.len() - 1]);
+            let geometry_type = reader.u32(little_endian)?;
+            let mut out = Vec::with_capacity(b.len());
+            spatialite_geometry(&mut reader, geometry_type, little_endian, &mut out)?;
+            Ok(Cow::Owned(out))
+        }
+        None => Err(
+            "Unknown geometry BLOB (must be GeoPackage binary, WKB, EWKB, or SpatiaLite BLOB)"
+                .into(),
+        ),
+    }
+}
+
+struct Reader<'a> {
+    buffer: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer, pos: 0 }
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        let bytes = self
+            .buffer
+            .get(self.pos..self.pos + n)
+            .ok_or("Unexpected end of geometry BLOB")?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self, little_endian: bool) -> Result<u32, Error> {
+        let bytes: [u8; 4] = self.bytes(4)?.try_into().unwrap();
+        Ok(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+}
+
+fn write_u32(out: &mut Vec<u8>, v: u32, little_endian: bool) {
+    if little_endian {
+        out.extend_from_slice(&v.to_le_bytes());
+    } else {
+        out.extend_from_slice(&v.to_be_bytes());
+    }
+}
+
+// Copy the body of a point, a linestring, or a polygon, which are the same in all
+// the flavors. Returns false if it's a multi geometry or a collection.
+fn copy_simple_body(
+    reader: &mut Reader,
+    base_type: u32,
+    n_dims: usize,
+    little_endian: bool,
+    out: &mut Vec<u8>,
+) -> Result<bool, Error> {
+    let copy_points = |reader: &mut Reader, out: &mut Vec<u8>| -> Result<(), Error> {
+        let n = reader.u32(little_endian)?;
+        write_u32(out, n, little_endian);
+        out.extend_from_slice(reader.bytes(n as usize * n_dims * 8)?);
+        Ok(())
+    };
+
+    match base_type {
+        1 => out.extend_from_slice(reader.bytes(n_dims * 8)?),
+        2 => copy_points(reader, out)?,
+        3 => {
+            let n_rings = reader.u32(little_endian)?;
+            write_u32(out, n_rings, little_endian);
+            for _ in 0..n_rings {
+                copy_points(reader, out)?;
+            }
+        }
+        4..=7 => return Ok(false),
+        _ => return Err(format!("Unsupported geometry type: {base_type}").into()),
+    }
+    Ok(true)
+}
+
+// Convert the EWKB flags to the ISO type code (e.g. 0x80000001 to 1001). The SRID
+// is dropped.
+fn ewkb_geometry(reader: &mut Reader, out: &mut Vec<u8>) -> Result<(), Error> {
+    let byte_order = reader.u8()?;
+    let little_endian = byte_order == 1;
+    let geometry_type = reader.u32(little_endian)?;
+    if geometry_type & EWKB_SRID != 0 {
+        reader.bytes(4)?;
+    }
+
+    // The type code itself might be an ISO one (e.g. 1001 for POINT Z)
+    let code = geometry_type & 0x0FFFFFFF;
+    let has_z = geometry_type & EWKB_Z != 0 || matches!(code / 1000, 1 | 3);
+    let has_m = geometry_type & EWKB_M != 0 || matches!(code / 1000, 2 | 3);
+    let base_type = code % 1000;
+    let n_dims = 2 + has_z as usize + has_m as usize;
+
+    out.push(byte_order);
+    let iso_type = base_type + if has_z { 1000 } else { 0 } + if has_m { 2000 } else { 0 };
+    write_u32(out, iso_type, little_endian);
+
+    if !copy_simple_body(reader, base_type, n_dims, little_endian, out)? {
+        let n = reader.u32(little_endian)?;
+        write_u32(out, n, little_endian);
+        for _ in 0..n {
+            ewkb_geometry(reader, out)?;
+        }
+    }
+    Ok(())
+}
+
+// SpatiaLite uses the same type codes as ISO WKB, but the byte order and the type
+// are written only once in the header. The members of a collection are prefixed
+// by the entity marker and the type instead.
+fn spatialite_geometry(
+    reader: &mut Reader,
+    geometry_type: u32,
+    little_endian: bool,
+    out: &mut Vec<u8>,
+) -> Result<(), Error> {
+    if geometry_type >= 1_000_000 {
+        return Err("Compressed SpatiaLite geometry is not supported".into());
+    }
+    let base_type = geometry_type % 1000;
+    let n_dims = match geometry_type / 1000 {
+        0 => 2,
+        1 | 2 => 3,
+        3 => 4,
+        _ => return Err(format!("Unsupported geometry type: {geometry_type}").into()),
+    };
+
+    out.push(little_endian as u8);
+    write_u32(out, geometry_type, little_endian);
+
+    if !copy_simple_body(reader, base_type, n_dims, little_endian, out)? {
+        let n = reader.u32(little_endian)?;
+        write_u32(out, n, little_endian);
+        for _ in 0..n {
+            if reader.u8()? != SPATIALITE_ENTITY {
+                return Err("Invalid SpatiaLite BLOB: missing entity marker".into());
+            }
+            let member_type = reader.u32(little_endian)?;
+            spatialite_geometry(reader, member_type, little_endian, out)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlobFlavor;
+
+    // POINT (1 2) in little-endian WKB
+    const WKB_POINT: &str = "0101000000000000000000F03F0000000000000040";
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_blob_flavors() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let wkb = hex(WKB_POINT);
+        let cases = [
+            // GeoPackage binary without envelope
+            (format!("47500001E6100000{WKB_POINT}"), BlobFlavor::Gpkg),
+            (WKB_POINT.to_string(), BlobFlavor::Wkb),
+            // EWKB with SRID 4326
+            (
+                "0101000020E6100000000000000000F03F0000000000000040".to_string(),
+                BlobFlavor::Ewkb,
+            ),
+            // SpatiaLite BLOB with MBR (1 2, 1 2)
+            (
+                concat!(
+                    "0001E6100000",
+                    "000000000000F03F0000000000000040000000000000F03F0000000000000040",
+                    "7C01000000000000000000F03F0000000000000040FE"
+                )
+                .to_string(),
+                BlobFlavor::SpatiaLite,
+            ),
+        ];
+        for (blob, flavor) in cases {
+            let blob = hex(&blob);
+            assert_eq!(super::detect_blob_flavor(&blob), Some(flavor));
+            assert_eq!(super::blob_to_wkb(&blob)?.as_ref(), wkb.as_slice());
+        }
+
+        assert_eq!(super::detect_blob_flavor(&[0x02, 0x00]), None);
+        assert!(super::blob_to_wkb(&[0x02, 0x00]).is_err());
+
+        // a truncated GeoPackage binary is an error, not a panic
+        assert_eq!(super::detect_blob_flavor(b"GP"), Some(BlobFlavor::Gpkg));
+        assert!(super::blob_to_wkb(b"GP").is_err());
+        // the header claims a 32-byte envelope that is not there
+        assert!(super::blob_to_wkb(&hex("47500003E6100000")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ewkb_z_multipoint() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // MULTIPOINT Z ((1 2 3)) in big-endian EWKB without SRID
+        let ewkb = hex(concat!(
+            "008000000400000001",
+            "0080000001",
+            "3FF000000000000040000000000000004008000000000000"
+        ));
+        assert_eq!(super::detect_blob_flavor(&ewkb), Some(BlobFlavor::Ewkb));
+        assert_eq!(
+            super::blob_to_wkb(&ewkb)?.as_ref(),
+            hex(concat!(
+                "00000003EC00000001",
+                "00000003E9",
+                "3FF000000000000040000000000000004008000000000000"
+            ))
+            .as_slice()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_spatialite_multilinestring() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // MULTILINESTRING ((0 0, 1 1))
+        let blob = hex(concat!(
+            "0001E6100000",
+            "00000000000000000000000000000000000000000000F03F000000000000F03F",
+            "7C0500000001000000",
+            "690200000002000000",
+            "00000000000000000000000000000000000000000000F03F000000000000F03F",
+            "FE"
+        ));
+        assert_eq!(
+            super::detect_blob_flavor(&blob),
+            Some(BlobFlavor::SpatiaLite)
+        );
+        assert_eq!(
+            super::blob_to_wkb(&blob)?.as_ref(),
+            hex(concat!(
+                "010500000001000000",
+                "010200000002000000",
+                "00000000000000000000000000000000000000000000F03F000000000000F03F"
+            ))
+            .as_slice()
+        );
+
+        // compressed geometries are not supported
+        let mut compressed = blob.clone();
+        compressed[39..43].copy_from_slice(&1_000_005u32.to_le_bytes());
+        assert!(super::blob_to_wkb(&compressed).is_err());
+
+        Ok(())
+    }
+}
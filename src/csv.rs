@@ -0,0 +1,338 @@
+use std::path::Path;
+
+use crate::types::{ColumnSpec, ColumnType};
+
+// The column names to look for when the latitude/longitude columns are not
+// specified. They are compared case-insensitively.
+const LATITUDE_COLUMN_CANDIDATES: &[&str] = &["latitude", "lat"];
+const LONGITUDE_COLUMN_CANDIDATES: &[&str] = &["longitude", "lon", "lng", "long"];
+
+// Where the location of a row is stored
+#[derive(Clone, Debug, PartialEq)]
+pub enum CsvLocation {
+    // The longitude and latitude columns. None means the column is looked up by the
+    // candidate names.
+    LonLat {
+        longitude_column: Option<String>,
+        latitude_column: Option<String>,
+    },
+    // A column of H3 cell indices (e.g. `8928308280fffff`)
+    H3(String),
+    // A column of geohashes (e.g. `xn76u`)
+    Geohash(String),
+}
+
+#[repr(C)]
+pub struct CsvRow {
+    // A point for the longitude and latitude, or a polygon for the H3 cell or the
+    // geohash. None if the value is missing or invalid.
+    pub geometry: Option<geo_types::Geometry<f64>>,
+    // The values of the attribute columns. An empty field is None.
+    pub values: Vec<Option<String>>,
+}
+
+#[repr(C)]
+pub struct CsvDataSource {
+    pub rows: Vec<CsvRow>,
+    pub filename: String,
+    // The attribute columns, i.e. all the columns except for the latitude and the
+    // longitude columns.
+    pub column_specs: Vec<ColumnSpec>,
+    // The modification time of the file (only when `include_mtime` is specified)
+    pub mtime: Option<i64>,
+}
+
+impl CsvDataSource {
+    pub(crate) fn new<P: AsRef<Path>>(
+        path: P,
+        location: &CsvLocation,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let filename = path.to_string_lossy().into_owned();
+
+        let mut reader = ::csv::Reader::from_path(path)?;
+        let headers: Vec<String> = reader.headers()?.iter().map(|h| h.to_string()).collect();
+
+        // The longitude and latitude columns are not included in the attributes, but
+        // the H3 cell and the geohash columns are, as they are often used as keys.
+        let (location_indices, excluded_indices) = match location {
+            CsvLocation::LonLat {
+                longitude_column,
+                latitude_column,
+            } => {
+                let longitude_column = longitude_column.as_deref();
+                let latitude_column = latitude_column.as_deref();
+                let lat_idx = find_column(&headers, latitude_column, LATITUDE_COLUMN_CANDIDATES)
+                    .ok_or_else(|| {
+                        column_not_found_error("latitude", latitude_column, &filename)
+                    })?;
+                let lon_idx = find_column(&headers, longitude_column, LONGITUDE_COLUMN_CANDIDATES)
+                    .ok_or_else(|| {
+                        column_not_found_error("longitude", longitude_column, &filename)
+                    })?;
+                (vec![lon_idx, lat_idx], vec![lon_idx, lat_idx])
+            }
+            CsvLocation::H3(name) => {
+                let idx = find_column(&headers, Some(name), &[])
+                    .ok_or_else(|| column_not_found_error("h3", Some(name), &filename))?;
+                (vec![idx], vec![])
+            }
+            CsvLocation::Geohash(name) => {
+                let idx = find_column(&headers, Some(name), &[])
+                    .ok_or_else(|| column_not_found_error("geohash", Some(name), &filename))?;
+                (vec![idx], vec![])
+            }
+        };
+
+        let attr_indices: Vec<usize> = (0..headers.len())
+            .filter(|i| !excluded_indices.contains(i))
+            .collect();
+
+        let mut rows: Vec<CsvRow> = Vec::new();
+        for record in reader.records() {
+            let record = record?;
+
+            let value = |i: usize| record.get(location_indices[i]).map(str::trim);
+            let geometry = match location {
+                CsvLocation::LonLat { .. } => {
+                    let coord = |i: usize| value(i).and_then(|v| v.parse::<f64>().ok());
+                    match (coord(0), coord(1)) {
+                        (Some(lon), Some(lat)) => Some(geo_types::Point::new(lon, lat).into()),
+                        _ => None,
+                    }
+                }
+                CsvLocation::H3(_) => value(0).and_then(h3_to_polygon).map(Into::into),
+                CsvLocation::Geohash(_) => value(0).and_then(geohash_to_polygon).map(Into::into),
+            };
+
+            let values = attr_indices
+                .iter()
+                .map(|&i| record.get(i).filter(|v| !v.is_empty()).map(String::from))
+                .collect();
+
+            rows.push(CsvRow { geometry, values });
+        }
+
+        let column_specs = attr_indices
+            .iter()
+            .enumerate()
+            .map(|(j, &i)| ColumnSpec {
+                name: headers[i].clone(),
+                // The cell IDs are kept as strings even if they are decimal integers,
+                // which DOUBLE cannot represent precisely.
+                column_type: if location_indices.contains(&i) {
+                    ColumnType::Varchar
+                } else {
+                    infer_column_type(rows.iter().take(100).filter_map(|r| r.values[j].as_deref()))
+                },
+            })
+            .collect();
+
+        Ok(Self {
+            rows,
+            filename,
+            column_specs,
+            mtime: None,
+        })
+    }
+}
+
+fn find_column(headers: &[String], specified: Option<&str>, candidates: &[&str]) -> Option<usize> {
+    match specified {
+        Some(name) => headers.iter().position(|h| h == name),
+        None => headers
+            .iter()
+            .position(|h| candidates.iter().any(|c| h.eq_ignore_ascii_case(c))),
+    }
+}
+
+fn column_not_found_error(
+    kind: &str,
+    specified: Option<&str>,
+    filename: &str,
+) -> Box<dyn std::error::Error> {
+    let filename = filename.replace('\\', "/");
+    match specified {
+        Some(name) => format!("Column '{name}' doesn't exist in {filename}").into(),
+        None => format!(
+            "Cannot find the {kind} column in {filename}. Please specify '{kind}_column' option"
+        )
+        .into(),
+    }
+}
+
+// Decode the H3 cell index into the polygon of the cell boundary. The index can be
+// either a hex string (e.g. `8928308280fffff`) or a decimal integer.
+fn h3_to_polygon(value: &str) -> Option<geo_types::Polygon<f64>> {
+    let cell = value.parse::<h3o::CellIndex>().ok().or_else(|| {
+        let n = value.parse::<u64>().ok()?;
+        h3o::CellIndex::try_from(n).ok()
+    })?;
+    let exterior: Vec<geo_types::Coord<f64>> = cell
+        .boundary()
+        .iter()
+        .map(|ll| geo_types::coord! { x: ll.lng(), y: ll.lat() })
+        .collect();
+    Some(geo_types::Polygon::new(exterior.into(), vec![]))
+}
+
+// Decode the geohash into the polygon of its bounding box.
+fn geohash_to_polygon(value: &str) -> Option<geo_types::Polygon<f64>> {
+    if value.is_empty() {
+        return None;
+    }
+    let rect = geohash::decode_bbox(&value.to_ascii_lowercase()).ok()?;
+    Some(rect.to_polygon())
+}
+
+// The values are inferred from the first 100 rows. A column is DOUBLE if all the
+// non-empty values are numbers. Otherwise, it's VARCHAR.
+fn infer_column_type<'a, I>(values: I) -> ColumnType
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut values = values.peekable();
+    if values.peek().is_none() {
+        return ColumnType::Varchar;
+    }
+
+    if values.all(|v| v.trim().parse::<f64>().is_ok()) {
+        ColumnType::Double
+    } else {
+        ColumnType::Varchar
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CsvLocation;
+    use crate::types::ColumnType;
+
+    fn lon_lat(longitude_column: Option<&str>, latitude_column: Option<&str>) -> CsvLocation {
+        CsvLocation::LonLat {
+            longitude_column: longitude_column.map(String::from),
+            latitude_column: latitude_column.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_csv_lon_lat() -> Result<(), Box<dyn std::error::Error>> {
+        let source =
+            super::CsvDataSource::new("./test/data/csv_lon_lat/points.csv", &lon_lat(None, None))?;
+
+        let names: Vec<&str> = source
+            .column_specs
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["name", "val"]);
+        assert_eq!(source.column_specs[0].column_type, ColumnType::Varchar);
+        assert_eq!(source.column_specs[1].column_type, ColumnType::Double);
+
+        let coords: Vec<Option<(f64, f64)>> = source
+            .rows
+            .iter()
+            .map(|r| match &r.geometry {
+                Some(geo_types::Geometry::Point(p)) => Some(p.x_y()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            coords,
+            vec![Some((139.7, 35.6)), Some((135.5, 34.7)), None, None]
+        );
+        assert_eq!(source.rows[3].values[1], None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_specified_columns() -> Result<(), Box<dyn std::error::Error>> {
+        let source = super::CsvDataSource::new(
+            "./test/data/csv_lon_lat/points.csv",
+            &lon_lat(Some("longitude"), Some("val")),
+        )?;
+
+        let names: Vec<&str> = source
+            .column_specs
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["name", "lat"]);
+
+        let result = super::CsvDataSource::new(
+            "./test/data/csv_lon_lat/points.csv",
+            &lon_lat(None, Some("no_such")),
+        );
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_h3_to_polygon() {
+        use geo::Contains;
+
+        // the cell of resolution 9 containing (37.7752702151959, -122.418307270836)
+        let polygon = super::h3_to_polygon("8928308280fffff").unwrap();
+        // a hexagon (the ring is closed)
+        assert_eq!(polygon.exterior().0.len(), 7);
+        assert!(polygon.contains(&geo_types::Point::new(-122.418307270836, 37.7752702151959)));
+
+        // the same cell in decimal
+        assert_eq!(
+            super::h3_to_polygon(&0x8928308280fffff_u64.to_string()),
+            Some(polygon)
+        );
+
+        assert_eq!(super::h3_to_polygon("not a cell"), None);
+        assert_eq!(super::h3_to_polygon("0"), None);
+    }
+
+    #[test]
+    fn test_geohash_to_polygon() {
+        let polygon = super::geohash_to_polygon("ezs42").unwrap();
+        let rect = geo_types::Rect::new(
+            geo_types::coord! { x: -5.625, y: 42.5830078125 },
+            geo_types::coord! { x: -5.5810546875, y: 42.626953125 },
+        );
+        assert_eq!(polygon, rect.to_polygon());
+
+        // case-insensitive
+        assert_eq!(super::geohash_to_polygon("EZS42"), Some(polygon));
+
+        assert_eq!(super::geohash_to_polygon("ezs42a"), None);
+        assert_eq!(super::geohash_to_polygon(""), None);
+    }
+
+    #[test]
+    fn test_csv_cells() -> Result<(), Box<dyn std::error::Error>> {
+        let path = "./test/data/csv_cells/cells.csv";
+
+        // the cell columns are kept as attributes
+        let source = super::CsvDataSource::new(path, &CsvLocation::H3("h3".to_string()))?;
+        let names: Vec<&str> = source
+            .column_specs
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["name", "h3", "geohash"]);
+        assert!(matches!(
+            source.rows[0].geometry,
+            Some(geo_types::Geometry::Polygon(_))
+        ));
+        assert!(source.rows[2].geometry.is_none());
+
+        let source = super::CsvDataSource::new(path, &CsvLocation::Geohash("geohash".to_string()))?;
+        assert_eq!(
+            source.rows[1].geometry,
+            super::geohash_to_polygon("xn76u").map(Into::into)
+        );
+        assert!(source.rows[2].geometry.is_none());
+
+        let result = super::CsvDataSource::new(path, &CsvLocation::H3("no_such".to_string()));
+        assert!(result.is_err());
+
+        Ok(())
+    }
+}
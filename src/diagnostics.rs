@@ -0,0 +1,248 @@
+// The warnings emitted during the last read (e.g. the files or the values skipped),
+// which can be queried by st_read_multi_warnings(). They are stored globally
+// because the function cannot access the bind data of another query. If multiple
+// reads run at the same time, their warnings are mixed.
+//
+// The warnings of a bind are also collected per thread until the bind finishes so
+// that strict mode only sees the warnings of its own bind.
+//
+// The warnings and the progress of a read are also sent to the `log` crate so that
+// the host can route them to its logger. Nothing is logged unless a logger is
+// installed; the warnings are printed to stderr instead.
+
+use std::{
+    cell::RefCell,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use duckdb::{
+    core::{DataChunkHandle, Inserter, LogicalTypeId},
+    vtab::{BindInfo, InitInfo, TableFunctionInfo, VTab},
+};
+
+use crate::VECTOR_SIZE;
+
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+thread_local! {
+    // The warnings of the bind running on this thread, if any
+    static BIND_WARNINGS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+// Log the warning and record it. The arguments are the same as format!().
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::diagnostics::record_warning(format!($($arg)*))
+    };
+}
+pub(crate) use warn;
+
+pub(crate) fn record_warning(message: String) {
+    // Fall back to stderr only when no logger takes the warning, so that it's not
+    // shown twice.
+    if log::log_enabled!(log::Level::Warn) {
+        log::warn!("{message}");
+    } else {
+        eprintln!("[WARN] {message}");
+    }
+    let message = BIND_WARNINGS.with(|w| match w.borrow_mut().as_mut() {
+        Some(bind_warnings) => {
+            bind_warnings.push(message);
+            None
+        }
+        None => Some(message),
+    });
+    if let Some(message) = message {
+        // The lock is poisoned only when some thread panicked while holding it. The
+        // warnings are still usable.
+        let mut warnings = WARNINGS.lock().unwrap_or_else(|e| e.into_inner());
+        warnings.push(message);
+    }
+}
+
+// Parse a file, logging the start and the end with the time taken.
+pub(crate) fn log_parse<T, E>(path: &Path, parse: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+    if !log::log_enabled!(log::Level::Debug) {
+        return parse();
+    }
+
+    let path = path.to_string_lossy().replace('\\', "/");
+    log::debug!("Parsing {path}");
+    let start = Instant::now();
+    let result = parse();
+    match &result {
+        Ok(_) => log::debug!("Parsed {path} in {:?}", start.elapsed()),
+        Err(_) => log::debug!("Failed to parse {path}"),
+    }
+    result
+}
+
+// Collects the warnings recorded on this thread while a bind is running. When
+// dropped, the collected warnings replace the ones of the previous read.
+pub(crate) struct BindWarnings;
+
+impl BindWarnings {
+    pub(crate) fn start() -> Self {
+        BIND_WARNINGS.with(|w| *w.borrow_mut() = Some(Vec::new()));
+        Self
+    }
+
+    // The first warning of this bind, which is an error in strict mode
+    pub(crate) fn first(&self) -> Option<String> {
+        BIND_WARNINGS.with(|w| w.borrow().as_ref().and_then(|w| w.first().cloned()))
+    }
+}
+
+impl Drop for BindWarnings {
+    fn drop(&mut self) {
+        if let Some(bind_warnings) = BIND_WARNINGS.with(|w| w.borrow_mut().take()) {
+            let mut warnings = WARNINGS.lock().unwrap_or_else(|e| e.into_inner());
+            *warnings = bind_warnings;
+        }
+    }
+}
+
+fn recorded_warnings() -> Vec<String> {
+    let warnings = WARNINGS.lock().unwrap_or_else(|e| e.into_inner());
+    warnings.clone()
+}
+
+#[repr(C)]
+pub struct StReadMultiWarningsBindData {
+    warnings: Vec<String>,
+}
+
+#[repr(C)]
+pub struct StReadMultiWarningsInitData {
+    // the index of the next warning to emit
+    next: AtomicUsize,
+}
+
+pub(crate) struct StReadMultiWarningsVTab;
+
+impl VTab for StReadMultiWarningsVTab {
+    type InitData = StReadMultiWarningsInitData;
+    type BindData = StReadMultiWarningsBindData;
+
+    fn bind(bind: &BindInfo) -> Result<Self::BindData, Box<dyn std::error::Error>> {
+        bind.add_result_column("message", LogicalTypeId::Varchar.into());
+
+        Ok(StReadMultiWarningsBindData {
+            warnings: recorded_warnings(),
+        })
+    }
+
+    fn init(_: &InitInfo) -> Result<Self::InitData, Box<dyn std::error::Error>> {
+        Ok(StReadMultiWarningsInitData {
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    fn func(
+        func: &TableFunctionInfo<Self>,
+        output: &mut DataChunkHandle,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let init_data = func.get_init_data();
+        let bind_data = func.get_bind_data();
+
+        let n_warnings = bind_data.warnings.len();
+        let start = init_data
+            .next
+            .fetch_add(VECTOR_SIZE, Ordering::Relaxed)
+            .min(n_warnings);
+        let end = (start + VECTOR_SIZE).min(n_warnings);
+
+        let vector = output.flat_vector(0);
+        for (row_idx, message) in bind_data.warnings[start..end].iter().enumerate() {
+            vector.insert(row_idx, message.as_str());
+        }
+        output.set_len(end - start);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::warn;
+
+    // A logger that keeps the messages of the records in memory
+    struct TestLogger {
+        messages: Mutex<Vec<String>>,
+    }
+
+    impl log::Log for TestLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            let mut messages = self.messages.lock().unwrap();
+            messages.push(format!("{} {}", record.level(), record.args()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: TestLogger = TestLogger {
+        messages: Mutex::new(Vec::new()),
+    };
+
+    #[test]
+    fn test_log() -> Result<(), Box<dyn std::error::Error>> {
+        // A logger can be installed only once per process
+        log::set_logger(&LOGGER).map_err(|e| e.to_string())?;
+        log::set_max_level(log::LevelFilter::Debug);
+
+        warn!("No such layer '{}' in {}", "test_log", "a.gpkg");
+        let n = super::log_parse(std::path::Path::new("data/a.geojson"), || {
+            Ok::<_, String>(1)
+        })?;
+        assert_eq!(n, 1);
+
+        let messages = LOGGER.messages.lock().unwrap();
+        assert!(messages.contains(&"WARN No such layer 'test_log' in a.gpkg".to_string()));
+        assert!(messages.contains(&"DEBUG Parsing data/a.geojson".to_string()));
+        assert!(messages
+            .iter()
+            .any(|m| m.starts_with("DEBUG Parsed data/a.geojson in ")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_warning() {
+        // The other tests might record warnings at the same time, so this only
+        // checks the warning is included.
+        warn!("No such layer '{}' in {}", "test_record_warning", "a.gpkg");
+        assert!(super::recorded_warnings()
+            .contains(&"No such layer 'test_record_warning' in a.gpkg".to_string()));
+
+        // The warnings of a bind are checked in the same test because they replace
+        // the recorded warnings
+        let bind_warnings = super::BindWarnings::start();
+
+        // A warning from another thread is not a warning of this bind
+        std::thread::spawn(|| warn!("from another thread"))
+            .join()
+            .unwrap();
+        assert_eq!(bind_warnings.first(), None);
+
+        warn!("No such layer '{}' in {}", "test_bind_warnings", "a.gpkg");
+        warn!("second");
+        assert_eq!(
+            bind_warnings.first().as_deref(),
+            Some("No such layer 'test_bind_warnings' in a.gpkg")
+        );
+
+        drop(bind_warnings);
+        assert!(super::recorded_warnings().contains(&"second".to_string()));
+    }
+}
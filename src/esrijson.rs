@@ -0,0 +1,290 @@
+// Read EsriJSON, the JSON format of the feature sets returned by ArcGIS REST
+// services. The features are converted to GeoJSON features so that they can be
+// read in the same way as GeoJSON.
+//
+// cf. https://developers.arcgis.com/documentation/common-data-types/featureset-object.htm
+
+use std::{fs::File, path::Path};
+
+use geo::{Intersects, Winding as _};
+use geo_types::{
+    Coord, Geometry, LineString, MultiLineString, MultiPoint, MultiPolygon, Point, Polygon,
+};
+use geojson::Feature;
+
+use crate::{
+    geojson::{infer_column_specs, open_reader, GeoJsonDataSource},
+    types::ColumnSpec,
+};
+
+pub(crate) fn parse_esrijson<P: AsRef<Path>>(
+    path: P,
+    preserve_order: bool,
+) -> Result<(Vec<GeoJsonDataSource>, Vec<ColumnSpec>), Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    let value: serde_json::Value = serde_json::from_reader(open_reader(path)?)?;
+
+    let Some(features) = value.get("features").and_then(|f| f.as_array()) else {
+        return Err(format!(
+            "EsriJSON file must have 'features': {}",
+            path.to_string_lossy().replace('\\', "/"),
+        )
+        .into());
+    };
+
+    let features = features
+        .iter()
+        .map(|feature| {
+            let geometry = match feature.get("geometry") {
+                Some(geometry) => esri_geometry_to_geo(geometry)?,
+                None => None,
+            };
+            Ok(Feature {
+                bbox: None,
+                geometry: geometry.map(|g| geojson::Geometry::new((&g).into())),
+                id: None,
+                properties: feature
+                    .get("attributes")
+                    .and_then(|a| a.as_object())
+                    .cloned(),
+                foreign_members: None,
+            })
+        })
+        .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+
+    let column_specs = infer_column_specs(
+        features.iter().map(|f| f.properties.as_ref()),
+        preserve_order,
+        false,
+        &[],
+    )?;
+
+    let crs = value
+        .get("spatialReference")
+        .and_then(parse_spatial_reference);
+
+    let data_sources = vec![GeoJsonDataSource {
+        features,
+        filename: path.to_string_lossy().into_owned(),
+        crs,
+        mtime: None,
+    }];
+
+    Ok((data_sources, column_specs))
+}
+
+// Check if the file looks like EsriJSON by the "esriGeometry*" value of
+// "geometryType" in the first 4KB. A bare array of features without it can be
+// read only with `format := 'esrijson'`.
+pub(crate) fn sniff_esrijson<P: AsRef<Path>>(path: P) -> bool {
+    use std::io::Read;
+
+    let mut buf = Vec::with_capacity(4096);
+    let Ok(f) = File::open(path) else {
+        return false;
+    };
+    if f.take(4096).read_to_end(&mut buf).is_err() {
+        return false;
+    }
+
+    let s = String::from_utf8_lossy(&buf);
+    s.trim_start_matches('\u{feff}')
+        .trim_start()
+        .starts_with('{')
+        && s.contains(r#""geometryType""#)
+        && s.contains(r#""esriGeometry"#)
+}
+
+// "latestWkid" is preferred because "wkid" can be a deprecated ESRI code (e.g.
+// 102100 for EPSG:3857).
+fn parse_spatial_reference(spatial_reference: &serde_json::Value) -> Option<String> {
+    let wkid = spatial_reference
+        .get("latestWkid")
+        .or_else(|| spatial_reference.get("wkid"))
+        .and_then(|v| v.as_i64())?;
+    Some(format!("EPSG:{wkid}"))
+}
+
+fn esri_geometry_to_geo(
+    value: &serde_json::Value,
+) -> Result<Option<Geometry<f64>>, Box<dyn std::error::Error>> {
+    let Some(object) = value.as_object() else {
+        return Ok(None);
+    };
+
+    if let Some(x) = object.get("x") {
+        // An empty point has NULL (or "NaN") coordinates
+        return Ok(
+            match (x.as_f64(), object.get("y").and_then(|y| y.as_f64())) {
+                (Some(x), Some(y)) => Some(Point::new(x, y).into()),
+                _ => None,
+            },
+        );
+    }
+
+    if let Some(points) = object.get("points") {
+        let points = parse_coords(points)?.into_iter().map(Point::from).collect();
+        return Ok(Some(MultiPoint::new(points).into()));
+    }
+
+    if let Some(paths) = object.get("paths") {
+        let mut lines = parse_rings(paths)?;
+        return Ok(Some(if lines.len() == 1 {
+            lines.remove(0).into()
+        } else {
+            MultiLineString::new(lines).into()
+        }));
+    }
+
+    if let Some(rings) = object.get("rings") {
+        let mut polygons = group_rings(parse_rings(rings)?);
+        return Ok(Some(if polygons.len() == 1 {
+            polygons.remove(0).into()
+        } else {
+            MultiPolygon::new(polygons).into()
+        }));
+    }
+
+    Err(format!("Unsupported EsriJSON geometry: {value}").into())
+}
+
+fn parse_coords(value: &serde_json::Value) -> Result<Vec<Coord<f64>>, Box<dyn std::error::Error>> {
+    let invalid = || format!("Invalid coordinates in EsriJSON geometry: {value}");
+    value
+        .as_array()
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|point| {
+            // Z and M values are dropped
+            match point.as_array().map(|p| p.as_slice()) {
+                Some([x, y, ..]) => match (x.as_f64(), y.as_f64()) {
+                    (Some(x), Some(y)) => Ok(Coord { x, y }),
+                    _ => Err(invalid().into()),
+                },
+                _ => Err(invalid().into()),
+            }
+        })
+        .collect()
+}
+
+fn parse_rings(
+    value: &serde_json::Value,
+) -> Result<Vec<LineString<f64>>, Box<dyn std::error::Error>> {
+    value
+        .as_array()
+        .ok_or_else(|| format!("Invalid paths or rings in EsriJSON geometry: {value}"))?
+        .iter()
+        .map(|ring| Ok(LineString::new(parse_coords(ring)?)))
+        .collect()
+}
+
+// Unlike GeoJSON, the rings of a polygon are not grouped. The clockwise rings are
+// the exterior rings and the counterclockwise ones are the holes, each of which
+// belongs to the exterior ring that contains it.
+fn group_rings(rings: Vec<LineString<f64>>) -> Vec<Polygon<f64>> {
+    let (exteriors, holes): (Vec<_>, Vec<_>) = rings.into_iter().partition(|r| r.is_cw());
+
+    // If there's no clockwise ring, the data doesn't follow the rule. Treat all the
+    // rings as exterior rings.
+    if exteriors.is_empty() {
+        return holes
+            .into_iter()
+            .map(|r| Polygon::new(r, Vec::new()))
+            .collect();
+    }
+
+    let mut polygons: Vec<(Polygon<f64>, Vec<LineString<f64>>)> = exteriors
+        .into_iter()
+        .map(|r| (Polygon::new(r, Vec::new()), Vec::new()))
+        .collect();
+    for hole in holes {
+        let container = hole.0.first().and_then(|c| {
+            polygons
+                .iter_mut()
+                .find(|(exterior, _)| exterior.intersects(c))
+        });
+        match container {
+            Some((_, interiors)) => interiors.push(hole),
+            // A hole outside of any exterior ring is read as a polygon
+            None => polygons.push((Polygon::new(hole, Vec::new()), Vec::new())),
+        }
+    }
+
+    polygons
+        .into_iter()
+        .map(|(polygon, interiors)| Polygon::new(polygon.into_inner().0, interiors))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{Geometry, LineString, Polygon};
+
+    #[test]
+    fn test_parse_esrijson() -> Result<(), Box<dyn std::error::Error>> {
+        let (data_sources, column_specs) =
+            super::parse_esrijson("./test/data/esrijson/polygon.json", true)?;
+
+        let names: Vec<&str> = column_specs.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["OBJECTID", "name", "area"]);
+        assert_eq!(data_sources[0].crs.as_deref(), Some("EPSG:3857"));
+
+        let features = &data_sources[0].features;
+        assert_eq!(features.len(), 2);
+
+        // a polygon with a hole
+        let geometry: Geometry<f64> = features[0].geometry.clone().unwrap().try_into()?;
+        let expected = Polygon::new(
+            LineString::from(vec![
+                (0.0, 0.0),
+                (0.0, 10.0),
+                (10.0, 10.0),
+                (10.0, 0.0),
+                (0.0, 0.0),
+            ]),
+            vec![LineString::from(vec![
+                (2.0, 2.0),
+                (8.0, 2.0),
+                (8.0, 8.0),
+                (2.0, 8.0),
+                (2.0, 2.0),
+            ])],
+        );
+        assert_eq!(geometry, Geometry::Polygon(expected));
+
+        // two exterior rings
+        let geometry: Geometry<f64> = features[1].geometry.clone().unwrap().try_into()?;
+        match geometry {
+            Geometry::MultiPolygon(mp) => {
+                assert_eq!(mp.0.len(), 2);
+                assert!(mp.0.iter().all(|p| p.interiors().is_empty()));
+            }
+            _ => panic!("Unexpected geometry: {geometry:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_esri_geometry_to_geo() -> Result<(), Box<dyn std::error::Error>> {
+        let convert = |s: &str| super::esri_geometry_to_geo(&serde_json::from_str(s).unwrap());
+
+        assert_eq!(
+            convert(r#"{"x": 1, "y": 2}"#)?,
+            Some(geo_types::Point::new(1.0, 2.0).into())
+        );
+        assert_eq!(convert(r#"{"x": null, "y": null}"#)?, None);
+        assert_eq!(
+            convert(r#"{"paths": [[[0, 0], [1, 1, 5]]]}"#)?,
+            Some(LineString::from(vec![(0.0, 0.0), (1.0, 1.0)]).into())
+        );
+        assert!(matches!(
+            convert(r#"{"points": [[0, 0], [1, 1]]}"#)?,
+            Some(Geometry::MultiPoint(mp)) if mp.0.len() == 2
+        ));
+        // envelopes are not supported
+        assert!(convert(r#"{"xmin": 0, "ymin": 0, "xmax": 1, "ymax": 1}"#).is_err());
+
+        Ok(())
+    }
+}
@@ -2,7 +2,11 @@ use std::{fs::File, path::Path};
 
 use geojson::Feature;
 
-use crate::types::{ColumnSpec, ColumnType};
+use crate::{
+    s3,
+    types::{CollectionMode, ColumnSpec, ColumnType, CoordinateOrder, SpatialFilter, Winding},
+    utils::{apply_collection_mode, is_zstd, swap_xy},
+};
 
 // Note: NULL must be handled outside of this function
 impl TryFrom<&serde_json::Value> for ColumnType {
@@ -11,10 +15,13 @@ impl TryFrom<&serde_json::Value> for ColumnType {
     fn try_from(value: &serde_json::Value) -> std::result::Result<Self, Self::Error> {
         match value {
             serde_json::Value::Bool(_) => Ok(Self::Boolean),
-            serde_json::Value::Number(_number) => {
+            // A whole number that doesn't fit in i32 is read as BIGINT so that large
+            // IDs don't lose precision as DOUBLE.
+            serde_json::Value::Number(number) => match number.as_i64() {
+                Some(n) if i32::try_from(n).is_err() => Ok(Self::BigInt),
                 // TODO: detect integer or double
-                Ok(Self::Double)
-            }
+                _ => Ok(Self::Double),
+            },
             serde_json::Value::String(_) => Ok(Self::Varchar),
             _ => Err(format!("Unsupported type: {value:?}").into()),
         }
@@ -25,52 +32,74 @@ impl TryFrom<&serde_json::Value> for ColumnType {
 pub struct GeoJsonDataSource {
     pub features: Vec<Feature>,
     pub filename: String,
+    // The CRS declared by the (deprecated) "crs" member, e.g. "EPSG:3857". None
+    // means the default, WGS84.
+    pub crs: Option<String>,
+    // The modification time of the file (only when `include_mtime` is specified)
+    pub mtime: Option<i64>,
 }
 
 impl GeoJsonDataSource {
     // For simplicty, split to the size of 2048.
+    //
+    // If `preserve_order` is true, the columns are ordered as they first appear in
+    // the file. Otherwise, they are sorted by name. The geometry of a feature is
+    // read from `geometry_member` instead of "geometry" if specified. If
+    // `json_pointer` is not empty, the FeatureCollection is read from the location
+    // it points to (RFC 6901) instead of the root.
     pub(crate) fn parse_and_split<P: AsRef<Path>>(
         path: P,
+        preserve_order: bool,
+        geometry_properties: &[String],
+        geometry_member: &str,
+        json_pointer: &str,
     ) -> Result<(Vec<Self>, Vec<ColumnSpec>), Box<dyn std::error::Error>> {
         let path = path.as_ref();
-        let mut column_specs: Vec<ColumnSpec> = Vec::new();
-
-        let f = File::open(path)?;
-        match geojson::GeoJson::from_reader(std::io::BufReader::new(f))? {
+        let reader = open_reader(path)?;
+        let geojson = if geometry_member == "geometry" && json_pointer.is_empty() {
+            geojson::GeoJson::from_reader(reader)?
+        } else {
+            let mut value: serde_json::Value = serde_json::from_reader(reader)?;
+            if !json_pointer.is_empty() {
+                value = value
+                    .pointer_mut(json_pointer)
+                    .map(serde_json::Value::take)
+                    .ok_or_else(|| {
+                        format!(
+                            "'json_pointer' '{json_pointer}' doesn't point to any value in {}",
+                            path.to_string_lossy().replace('\\', "/"),
+                        )
+                    })?;
+            }
+            if geometry_member != "geometry" {
+                rename_geometry_member(&mut value, geometry_member);
+            }
+            geojson::GeoJson::from_json_value(value)?
+        };
+        match geojson {
             geojson::GeoJson::FeatureCollection(feature_collection) => {
-                // Use first 100 features to determine schema
-                let sample_size = std::cmp::min(100, feature_collection.features.len());
-                let mut property_type_map: std::collections::HashMap<String, ColumnType> =
-                    std::collections::HashMap::new();
-
-                for i in 0..sample_size {
-                    for (key, val) in feature_collection.features[i].properties_iter() {
-                        // Skip NULL values
-                        if val.is_null() {
-                            continue;
-                        }
-
-                        let column_type: ColumnType = val.try_into()?;
-
-                        // If key doesn't exist yet or current type is more specific, update it
-                        property_type_map
-                            .entry(key.to_string())
-                            .or_insert(column_type);
-                    }
-                }
+                let column_specs = infer_column_specs(
+                    feature_collection
+                        .features
+                        .iter()
+                        .map(|f| f.properties.as_ref()),
+                    preserve_order,
+                    false,
+                    geometry_properties,
+                )?;
 
-                // Convert to ordered vector
-                for (name, column_type) in property_type_map {
-                    column_specs.push(ColumnSpec { name, column_type });
-                }
-
-                // Sort by name for consistent ordering
-                column_specs.sort_by(|a, b| a.name.cmp(&b.name));
+                let crs = feature_collection
+                    .foreign_members
+                    .as_ref()
+                    .and_then(|members| members.get("crs"))
+                    .and_then(parse_crs_member);
 
                 let filename = path.to_string_lossy().into_owned();
                 let data_sources = vec![GeoJsonDataSource {
                     features: feature_collection.features,
                     filename,
+                    crs,
+                    mtime: None,
                 }];
 
                 Ok((data_sources, column_specs))
@@ -82,27 +111,889 @@ impl GeoJsonDataSource {
             .into()),
         }
     }
+
+    // Change the type of the VARCHAR columns to DOUBLE if all the values are numeric
+    // strings (e.g. "12345" or "1.2e3"). Like the inference, only the first 100
+    // features are inspected.
+    pub(crate) fn coerce_numeric_strings(&self, column_specs: &mut [ColumnSpec]) {
+        for spec in column_specs
+            .iter_mut()
+            .filter(|s| s.column_type == ColumnType::Varchar)
+        {
+            let mut values = self
+                .features
+                .iter()
+                .take(100)
+                .filter_map(|f| f.properties.as_ref()?.get(&spec.name))
+                // NULLs and empty strings are just skipped
+                .filter(|v| match v {
+                    serde_json::Value::Null => false,
+                    serde_json::Value::String(s) => !s.trim().is_empty(),
+                    _ => true,
+                })
+                .peekable();
+
+            if values.peek().is_none() {
+                continue;
+            }
+
+            let all_numeric = values.all(|v| match v {
+                serde_json::Value::String(s) => parse_numeric_string(s).is_some(),
+                serde_json::Value::Number(_) => true,
+                _ => false,
+            });
+            if all_numeric {
+                spec.column_type = ColumnType::Double;
+            }
+        }
+    }
+
+    // Foreign members are the top-level members of a Feature other than the ones
+    // defined in the spec (e.g. "style"). Unlike properties, nested values are
+    // allowed and they are stored as a JSON string.
+    pub(crate) fn foreign_member_specs(
+        &self,
+        preserve_order: bool,
+    ) -> Result<Vec<ColumnSpec>, Box<dyn std::error::Error>> {
+        infer_column_specs(
+            self.features.iter().map(|f| f.foreign_members.as_ref()),
+            preserve_order,
+            true,
+            &[],
+        )
+    }
+}
+
+// Open the file, which might be on S3 and/or compressed with zstd. An object on S3
+// is buffered into memory.
+pub(crate) fn open_reader(
+    path: &Path,
+) -> Result<Box<dyn std::io::Read>, Box<dyn std::error::Error>> {
+    let path_str = path.to_string_lossy();
+    let reader: Box<dyn std::io::Read> = if s3::is_s3_url(&path_str) {
+        Box::new(std::io::Cursor::new(s3::get_object(&path_str)?))
+    } else {
+        Box::new(std::io::BufReader::new(File::open(path)?))
+    };
+    Ok(if is_zstd(path) {
+        Box::new(zstd::stream::read::Decoder::new(reader)?)
+    } else {
+        reader
+    })
+}
+
+// Move the member of each feature to "geometry" so that it's read as the geometry.
+// The original "geometry" member, if any, is dropped. A feature without the member
+// has a null geometry.
+fn rename_geometry_member(value: &mut serde_json::Value, geometry_member: &str) {
+    let Some(features) = value.get_mut("features").and_then(|v| v.as_array_mut()) else {
+        return;
+    };
+    for feature in features.iter_mut().filter_map(|f| f.as_object_mut()) {
+        let geometry = feature
+            .shift_remove(geometry_member)
+            .unwrap_or(serde_json::Value::Null);
+        feature.insert("geometry".to_string(), geometry);
+    }
+}
+
+// Parse the "crs" member, which was removed from the spec in RFC 7946 but is still
+// common. e.g. {"type": "name", "properties": {"name": "urn:ogc:def:crs:EPSG::3857"}}
+pub(crate) fn parse_crs_member(crs: &serde_json::Value) -> Option<String> {
+    let name = crs.get("properties")?.get("name")?.as_str()?;
+
+    if name.ends_with("CRS84") {
+        return Some("OGC:CRS84".to_string());
+    }
+
+    // "urn:ogc:def:crs:EPSG::3857", "urn:ogc:def:crs:EPSG:6.6:3857", or "EPSG:3857"
+    if let Some(i) = name.find("EPSG:") {
+        if let Some(code) = name[i..].rsplit(':').next() {
+            if !code.is_empty() && code.chars().all(|c| c.is_ascii_digit()) {
+                return Some(format!("EPSG:{code}"));
+            }
+        }
+    }
+
+    Some(name.to_string())
+}
+
+// Parse a quoted number. Unlike f64::from_str(), "inf" or "NaN" are not accepted
+// because they are more likely to be just strings.
+pub(crate) fn parse_numeric_string(s: &str) -> Option<f64> {
+    let s = s.trim();
+    if !s.bytes().any(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse::<f64>().ok()
+}
+
+// What to do with a value that cannot be converted to the type of the column. The
+// type is inferred from the first features, so a later feature can have a value of
+// a different type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OnTypeMismatch {
+    // read as NULL
+    Null,
+    // fail the query
+    Error,
+}
+
+impl std::str::FromStr for OnTypeMismatch {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "null" => Ok(Self::Null),
+            "error" => Ok(Self::Error),
+            _ => Err(format!(
+                "Unknown value in 'on_type_mismatch' option: {s} (must be 'null' or 'error')"
+            )
+            .into()),
+        }
+    }
+}
+
+// Some data uses 0/1 or strings for booleans (cf. `boolean_columns`). Returns None
+// if the value cannot be regarded as a boolean.
+pub(crate) fn coerce_to_bool(value: &serde_json::Value) -> Option<bool> {
+    match value {
+        serde_json::Value::Bool(b) => Some(*b),
+        serde_json::Value::Number(n) => {
+            let n = n.as_f64()?;
+            if n == 0.0 {
+                Some(false)
+            } else if n == 1.0 {
+                Some(true)
+            } else {
+                None
+            }
+        }
+        serde_json::Value::String(s) => match s.trim().to_ascii_lowercase().as_str() {
+            "true" | "yes" => Some(true),
+            "false" | "no" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// The values of the top-level "type" member of GeoJSON
+const GEOJSON_TYPES: &[&str] = &[
+    "FeatureCollection",
+    "Feature",
+    "Point",
+    "MultiPoint",
+    "LineString",
+    "MultiLineString",
+    "Polygon",
+    "MultiPolygon",
+    "GeometryCollection",
+];
+
+// Check if the file looks like GeoJSON by the first "type" member in the first
+// 4KB. This doesn't parse the whole file, so it's just a guess.
+pub(crate) fn sniff_geojson<P: AsRef<Path>>(path: P) -> bool {
+    use std::io::Read;
+
+    let mut buf = Vec::with_capacity(4096);
+    let Ok(f) = File::open(path) else {
+        return false;
+    };
+    if f.take(4096).read_to_end(&mut buf).is_err() {
+        return false;
+    }
+
+    let s = String::from_utf8_lossy(&buf);
+    let s = s.trim_start_matches('\u{feff}').trim_start();
+    if !s.starts_with('{') {
+        return false;
+    }
+
+    s.match_indices(r#""type""#).any(|(i, key)| {
+        let rest = s[i + key.len()..].trim_start();
+        let Some(rest) = rest.strip_prefix(':') else {
+            return false;
+        };
+        let Some(rest) = rest.trim_start().strip_prefix('"') else {
+            return false;
+        };
+        rest.split_once('"')
+            .is_some_and(|(value, _)| GEOJSON_TYPES.contains(&value))
+    })
+}
+
+// Check the geometry of the feature against `filter_wkt`. This is done before the
+// transformations like `centroid`, except for the axis order.
+pub(crate) fn feature_intersects(
+    feature: &Feature,
+    filter: &SpatialFilter,
+    coordinate_order: CoordinateOrder,
+) -> bool {
+    let Some(mut geometry) = feature
+        .geometry
+        .as_ref()
+        .and_then(|g| geo_types::Geometry::<f64>::try_from(g).ok())
+    else {
+        return false;
+    };
+    if coordinate_order == CoordinateOrder::LatLon {
+        swap_xy(&mut geometry);
+    }
+    filter.intersects(&geometry)
+}
+
+pub(crate) fn is_wgs84(crs: &str) -> bool {
+    crs == "EPSG:4326" || crs == "OGC:CRS84"
+}
+
+// Use first 100 features to determine schema. A feature without the object
+// (e.g. `"properties": null`) is just skipped. The members in `geometry_members`
+// are GeoJSON geometry objects (cf. `geometry_properties`).
+pub(crate) fn infer_column_specs<'a, I>(
+    objects: I,
+    preserve_order: bool,
+    nested_as_varchar: bool,
+    geometry_members: &[String],
+) -> Result<Vec<ColumnSpec>, Box<dyn std::error::Error>>
+where
+    I: Iterator<Item = Option<&'a geojson::JsonObject>>,
+{
+    let mut column_specs: Vec<ColumnSpec> = Vec::new();
+    let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
+
+    // Note: serde_json is built with preserve_order feature, so the members are
+    // iterated in the same order as the file.
+    for object in objects.take(100).flatten() {
+        for (key, val) in object {
+            // Skip NULL values
+            if val.is_null() {
+                continue;
+            }
+
+            // The first non-NULL value determines the type
+            if seen.contains(key) {
+                continue;
+            }
+
+            let column_type: ColumnType = match val {
+                _ if geometry_members.contains(key) => ColumnType::Geometry,
+                serde_json::Value::Object(_) | serde_json::Value::Array(_) if nested_as_varchar => {
+                    ColumnType::Varchar
+                }
+                _ => val.try_into()?,
+            };
+            seen.insert(key.to_string());
+            column_specs.push(ColumnSpec {
+                name: key.to_string(),
+                column_type,
+            });
+        }
+    }
+
+    // Sort by name for consistent ordering
+    if !preserve_order {
+        column_specs.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    Ok(column_specs)
+}
+
+// The order of the properties in the first feature of a data source. The features
+// of a file usually have the same properties in the same order, so such a feature
+// is read in one pass by the positions of the properties, instead of looking up
+// every column by name.
+pub struct PropertyLayout {
+    keys: Vec<String>,
+    // The column of each property. None if it's not a column (e.g. excluded by
+    // `exclude_columns`).
+    columns: Vec<Option<usize>>,
+}
+
+impl PropertyLayout {
+    pub fn new(source: &GeoJsonDataSource, column_specs: &[ColumnSpec]) -> Self {
+        let keys: Vec<String> = source
+            .features
+            .first()
+            .and_then(|f| f.properties.as_ref())
+            .map(|properties| properties.keys().cloned().collect())
+            .unwrap_or_default();
+        let columns = keys
+            .iter()
+            .map(|key| column_specs.iter().position(|spec| &spec.name == key))
+            .collect();
+        Self { keys, columns }
+    }
+
+    // Set the values of the properties in the order of the columns. A feature with
+    // the different properties falls back to the lookup by name.
+    pub fn read<'a>(
+        &self,
+        properties: &'a geojson::JsonObject,
+        column_specs: &[ColumnSpec],
+        values: &mut [Option<&'a serde_json::Value>],
+    ) {
+        if properties.len() == self.keys.len()
+            && properties.keys().zip(&self.keys).all(|(a, b)| a == b)
+        {
+            values.fill(None);
+            for (value, column) in properties.values().zip(&self.columns) {
+                if let Some(i) = column {
+                    values[*i] = Some(value);
+                }
+            }
+        } else {
+            for (spec, value) in column_specs.iter().zip(values.iter_mut()) {
+                *value = properties.get(&spec.name);
+            }
+        }
+    }
 }
 
 pub struct WkbConverter {
     buffer: Vec<u8>,
+    coordinate_order: CoordinateOrder,
+    coordinate_precision: Option<i32>,
+    // If true, the geometry is replaced with its centroid
+    centroid: bool,
+    // cf. `buffer_distance`
+    buffer_distance: Option<f64>,
+    // cf. `densify_max_segment_length`
+    max_segment_length: Option<f64>,
+    winding: Option<Winding>,
+    collection_mode: CollectionMode,
 }
 
 impl WkbConverter {
-    pub fn new() -> Self {
-        Self { buffer: Vec::new() }
+    pub fn new(
+        coordinate_order: CoordinateOrder,
+        coordinate_precision: Option<i32>,
+        centroid: bool,
+        buffer_distance: Option<f64>,
+        max_segment_length: Option<f64>,
+        winding: Option<Winding>,
+        collection_mode: CollectionMode,
+    ) -> Self {
+        Self {
+            buffer: Vec::new(),
+            coordinate_order,
+            coordinate_precision,
+            centroid,
+            buffer_distance,
+            max_segment_length,
+            winding,
+            collection_mode,
+        }
     }
 
-    pub fn convert(&mut self, feature: &Feature) -> Result<&[u8], Box<dyn std::error::Error>> {
+    // Returns None if the geometry is `null`. Note that an empty geometry (e.g. an
+    // empty GeometryCollection) is not null; it's converted to an EMPTY WKB. With
+    // `centroid`, however, it's None because there's no centroid. It's also None if
+    // `collection_mode` makes the geometry NULL.
+    pub fn convert(
+        &mut self,
+        feature: &Feature,
+    ) -> Result<Option<&[u8]>, Box<dyn std::error::Error>> {
         self.buffer.clear();
-        match &feature.geometry {
-            Some(geojson_geom) => {
-                let geometry: geo_types::Geometry = geojson_geom.try_into()?;
-                wkb::writer::write_geometry(&mut self.buffer, &geometry, &Default::default())
-                    .unwrap();
+        let Some(geojson_geom) = &feature.geometry else {
+            return Ok(None);
+        };
+        let geometry: geo_types::Geometry = geojson_geom.try_into()?;
+        let Some(mut geometry) = apply_collection_mode(geometry, self.collection_mode) else {
+            return Ok(None);
+        };
+        if self.coordinate_order == CoordinateOrder::LatLon {
+            swap_xy(&mut geometry);
+        }
+        if self.centroid {
+            use geo::Centroid;
+            match geometry.centroid() {
+                Some(point) => geometry = point.into(),
+                None => return Ok(None),
             }
-            None => panic!("Geometry should exist!"),
         }
-        Ok(&self.buffer)
+        if let Some(distance) = self.buffer_distance {
+            crate::utils::buffer(&mut geometry, distance);
+        }
+        if let Some(max_segment_length) = self.max_segment_length {
+            crate::utils::densify(&mut geometry, max_segment_length);
+        }
+        if let Some(precision) = self.coordinate_precision {
+            crate::utils::round_coordinates(&mut geometry, precision);
+        }
+        if let Some(winding) = self.winding {
+            crate::utils::orient_polygons(&mut geometry, winding);
+        }
+        wkb::writer::write_geometry(&mut self.buffer, &geometry, &Default::default()).unwrap();
+        Ok(Some(&self.buffer))
+    }
+}
+
+// This is the reverse of WkbConverter, used for writing GeoJSON.
+pub(crate) fn wkb_to_geojson_geometry(
+    wkb: &[u8],
+) -> Result<geojson::Geometry, Box<dyn std::error::Error>> {
+    use geo_traits::to_geo::ToGeoGeometry;
+
+    let geometry = wkb::reader::read_wkb(wkb).map_err(|e| e.to_string())?;
+    let geometry: geo_types::Geometry = geometry
+        .try_to_geometry()
+        .ok_or("Empty point cannot be written as GeoJSON")?;
+    Ok(geojson::Geometry::new((&geometry).into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{ColumnType, CoordinateOrder};
+
+    #[test]
+    fn test_column_specs_sorted_by_name() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, specs) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/property_order/points.geojson",
+            false,
+            &[],
+            "geometry",
+            "",
+        )?;
+
+        let names: Vec<&str> = specs.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["aaa", "mmm", "zzz"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_property_layout() -> Result<(), Box<dyn std::error::Error>> {
+        let (sources, specs) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/mixed_properties/points.geojson",
+            false,
+            &[],
+            "geometry",
+            "",
+        )?;
+        let layout = super::PropertyLayout::new(&sources[0], &specs);
+
+        let mut values = vec![None; specs.len()];
+        let rows: Vec<Vec<Option<String>>> = sources[0]
+            .features
+            .iter()
+            .map(|f| {
+                let Some(properties) = &f.properties else {
+                    return vec![None; specs.len()];
+                };
+                layout.read(properties, &specs, &mut values);
+                values.iter().map(|v| v.map(|v| v.to_string())).collect()
+            })
+            .collect();
+
+        let names: Vec<&str> = specs.iter().map(|s| s.name.as_str()).collect();
+        let expected: Vec<Vec<Option<String>>> = sources[0]
+            .features
+            .iter()
+            .map(|f| {
+                names
+                    .iter()
+                    .map(|name| f.property(name).map(|v| v.to_string()))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(rows, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_specs_bigint() -> Result<(), Box<dyn std::error::Error>> {
+        let (sources, specs) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/bigint/points.geojson",
+            false,
+            &[],
+            "geometry",
+            "",
+        )?;
+
+        assert_eq!(specs[0].name, "id");
+        assert_eq!(specs[0].column_type, ColumnType::BigInt);
+        // small whole numbers are still read as DOUBLE
+        assert_eq!(specs[1].name, "val");
+        assert_eq!(specs[1].column_type, ColumnType::Double);
+
+        let ids: Vec<Option<i64>> = sources[0]
+            .features
+            .iter()
+            .map(|f| f.property("id").and_then(|v| v.as_i64()))
+            .collect();
+        assert_eq!(
+            ids,
+            vec![Some(4000000000), Some(9007199254740993), Some(-4000000000)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_specs_preserve_order() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, specs) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/property_order/points.geojson",
+            true,
+            &[],
+            "geometry",
+            "",
+        )?;
+
+        let names: Vec<&str> = specs.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["zzz", "aaa", "mmm"]);
+        assert_eq!(specs[0].column_type, ColumnType::Varchar);
+        assert_eq!(specs[1].column_type, ColumnType::Double);
+        assert_eq!(specs[2].column_type, ColumnType::Boolean);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_foreign_member_specs() -> Result<(), Box<dyn std::error::Error>> {
+        let (sources, specs) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/foreign_members/points.geojson",
+            false,
+            &[],
+            "geometry",
+            "",
+        )?;
+        let names: Vec<&str> = specs.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["val"]);
+
+        let foreign_specs = sources[0].foreign_member_specs(false)?;
+        let names: Vec<&str> = foreign_specs.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["style", "title"]);
+        // a nested value is stored as a JSON string
+        assert_eq!(foreign_specs[0].column_type, ColumnType::Varchar);
+        assert_eq!(foreign_specs[1].column_type, ColumnType::Varchar);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_crs_member() -> Result<(), Box<dyn std::error::Error>> {
+        let crs = |name: &str| {
+            super::parse_crs_member(&serde_json::json!({
+                "type": "name",
+                "properties": { "name": name }
+            }))
+        };
+
+        assert_eq!(
+            crs("urn:ogc:def:crs:EPSG::3857").as_deref(),
+            Some("EPSG:3857")
+        );
+        assert_eq!(crs("EPSG:3857").as_deref(), Some("EPSG:3857"));
+        assert_eq!(
+            crs("urn:ogc:def:crs:OGC:1.3:CRS84").as_deref(),
+            Some("OGC:CRS84")
+        );
+        assert_eq!(crs("something").as_deref(), Some("something"));
+        assert_eq!(super::parse_crs_member(&serde_json::json!(null)), None);
+
+        let (sources, _) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/geojson_crs/points_3857.geojson",
+            false,
+            &[],
+            "geometry",
+            "",
+        )?;
+        assert_eq!(sources[0].crs.as_deref(), Some("EPSG:3857"));
+
+        let (sources, _) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/points.geojson",
+            false,
+            &[],
+            "geometry",
+            "",
+        )?;
+        assert_eq!(sources[0].crs, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_zstd() -> Result<(), Box<dyn std::error::Error>> {
+        let (plain, plain_specs) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/points.geojson",
+            false,
+            &[],
+            "geometry",
+            "",
+        )?;
+        let (compressed, compressed_specs) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/zstd/points3.json.zst",
+            false,
+            &[],
+            "geometry",
+            "",
+        )?;
+
+        assert_eq!(compressed[0].features, plain[0].features);
+        let names = |specs: &[super::ColumnSpec]| -> Vec<String> {
+            specs.iter().map(|s| s.name.clone()).collect()
+        };
+        assert_eq!(names(&compressed_specs), names(&plain_specs));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_member_last() -> Result<(), Box<dyn std::error::Error>> {
+        // "type" comes after "features" both in the FeatureCollection and in the
+        // features.
+        let (sources, specs) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/member_order/features_first.geojson",
+            false,
+            &[],
+            "geometry",
+            "",
+        )?;
+
+        let names: Vec<&str> = specs.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["val"]);
+        assert_eq!(sources[0].features.len(), 2);
+        assert_eq!(sources[0].crs.as_deref(), Some("EPSG:3857"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_geometry_properties() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, specs) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/geometry_properties/points.geojson",
+            false,
+            &["centroid".to_string()],
+            "geometry",
+            "",
+        )?;
+        assert_eq!(specs[0].name, "centroid");
+        assert_eq!(specs[0].column_type, ColumnType::Geometry);
+        assert_eq!(specs[1].column_type, ColumnType::Varchar);
+
+        // a nested object is not allowed unless it's specified
+        assert!(super::GeoJsonDataSource::parse_and_split(
+            "./test/data/geometry_properties/points.geojson",
+            false,
+            &[],
+            "geometry",
+            ""
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_pointer() -> Result<(), Box<dyn std::error::Error>> {
+        let parse = |json_pointer: &str| {
+            super::GeoJsonDataSource::parse_and_split(
+                "./test/data/json_pointer/wrapped.json",
+                false,
+                &[],
+                "geometry",
+                json_pointer,
+            )
+        };
+
+        let (sources, specs) = parse("/data")?;
+        let names: Vec<&str> = specs.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["name"]);
+        assert_eq!(sources[0].features.len(), 2);
+
+        // the root is not a FeatureCollection
+        assert!(parse("").is_err());
+        // no such member
+        assert!(parse("/result").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_geometry_member() -> Result<(), Box<dyn std::error::Error>> {
+        let (sources, specs) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/geometry_member/points.json",
+            false,
+            &[],
+            "geom",
+            "",
+        )?;
+        let names: Vec<&str> = specs.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["val"]);
+
+        let features = &sources[0].features;
+        assert_eq!(
+            features[0].geometry,
+            Some(geojson::Geometry::new(geojson::Value::Point(vec![
+                1.0, 2.0
+            ])))
+        );
+        assert_eq!(features[2].geometry, None);
+        // the member is not left as a foreign member
+        assert!(features[0]
+            .foreign_members
+            .as_ref()
+            .is_none_or(|m| !m.contains_key("geom")));
+
+        // "geometry" is missing
+        assert!(super::GeoJsonDataSource::parse_and_split(
+            "./test/data/geometry_member/points.json",
+            false,
+            &[],
+            "geometry",
+            ""
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_coerce_numeric_strings() -> Result<(), Box<dyn std::error::Error>> {
+        let (sources, mut specs) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/quoted_numbers/points.geojson",
+            true,
+            &[],
+            "geometry",
+            "",
+        )?;
+        assert!(specs.iter().all(|s| s.column_type == ColumnType::Varchar));
+
+        sources[0].coerce_numeric_strings(&mut specs);
+        let types: Vec<(&str, ColumnType)> = specs
+            .iter()
+            .map(|s| (s.name.as_str(), s.column_type))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("name", ColumnType::Varchar),
+                ("population", ColumnType::Double),
+                ("area", ColumnType::Double),
+            ]
+        );
+
+        assert_eq!(super::parse_numeric_string(" 12345 "), Some(12345.0));
+        assert_eq!(super::parse_numeric_string("1.2e3"), Some(1200.0));
+        assert_eq!(super::parse_numeric_string("NaN"), None);
+        assert_eq!(super::parse_numeric_string("12a"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_coerce_to_bool() {
+        use serde_json::json;
+
+        assert_eq!(super::coerce_to_bool(&json!(true)), Some(true));
+        assert_eq!(super::coerce_to_bool(&json!(false)), Some(false));
+        assert_eq!(super::coerce_to_bool(&json!(1)), Some(true));
+        assert_eq!(super::coerce_to_bool(&json!(0)), Some(false));
+        assert_eq!(super::coerce_to_bool(&json!(1.0)), Some(true));
+        assert_eq!(super::coerce_to_bool(&json!(2)), None);
+        assert_eq!(super::coerce_to_bool(&json!("true")), Some(true));
+        assert_eq!(super::coerce_to_bool(&json!("False")), Some(false));
+        assert_eq!(super::coerce_to_bool(&json!("yes")), Some(true));
+        assert_eq!(super::coerce_to_bool(&json!("NO")), Some(false));
+        assert_eq!(super::coerce_to_bool(&json!("maybe")), None);
+        assert_eq!(super::coerce_to_bool(&json!(null)), None);
+    }
+
+    #[test]
+    fn test_null_and_empty_geometry() -> Result<(), Box<dyn std::error::Error>> {
+        let (sources, _) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/null_geometry/features.geojson",
+            false,
+            &[],
+            "geometry",
+            "",
+        )?;
+        let features = &sources[0].features;
+        let mut converter = super::WkbConverter::new(
+            CoordinateOrder::LonLat,
+            None,
+            false,
+            None,
+            None,
+            None,
+            super::CollectionMode::Keep,
+        );
+
+        // null
+        assert_eq!(converter.convert(&features[0])?, None);
+
+        // GEOMETRYCOLLECTION EMPTY (little endian, type 7, 0 geometries)
+        assert_eq!(
+            converter.convert(&features[1])?,
+            Some([1, 7, 0, 0, 0, 0, 0, 0, 0].as_slice())
+        );
+
+        // POLYGON EMPTY (little endian, type 3, 0 rings)
+        assert_eq!(
+            converter.convert(&features[2])?,
+            Some([1, 3, 0, 0, 0, 0, 0, 0, 0].as_slice())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_coordinate_order() -> Result<(), Box<dyn std::error::Error>> {
+        let (sources, _) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/points.geojson",
+            false,
+            &[],
+            "geometry",
+            "",
+        )?;
+        let feature = &sources[0].features[0];
+
+        let mut converter = super::WkbConverter::new(
+            CoordinateOrder::LatLon,
+            None,
+            false,
+            None,
+            None,
+            None,
+            super::CollectionMode::Keep,
+        );
+
+        // POINT (2 1) (little endian, type 1, x = 2.0, y = 1.0)
+        let mut expected = vec![1, 1, 0, 0, 0];
+        expected.extend_from_slice(&2.0_f64.to_le_bytes());
+        expected.extend_from_slice(&1.0_f64.to_le_bytes());
+        assert_eq!(converter.convert(feature)?, Some(expected.as_slice()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wkb_to_geojson_geometry() -> Result<(), Box<dyn std::error::Error>> {
+        let (sources, _) = super::GeoJsonDataSource::parse_and_split(
+            "./test/data/points.geojson",
+            false,
+            &[],
+            "geometry",
+            "",
+        )?;
+        let feature = &sources[0].features[0];
+
+        let mut converter = super::WkbConverter::new(
+            CoordinateOrder::LonLat,
+            None,
+            false,
+            None,
+            None,
+            None,
+            super::CollectionMode::Keep,
+        );
+        let wkb = converter.convert(feature)?.unwrap();
+
+        let geometry = super::wkb_to_geojson_geometry(wkb)?;
+        assert_eq!(Some(geometry), feature.geometry);
+
+        Ok(())
     }
 }
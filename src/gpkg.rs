@@ -1,10 +1,15 @@
-use crate::types::{ColumnSpec, ColumnType};
-use crate::VECTOR_SIZE;
+use crate::{
+    blob::{blob_to_wkb, detect_blob_flavor, BlobFlavor},
+    diagnostics::warn,
+    types::{Bbox, ColumnFilter, ColumnSpec, ColumnType, SpatialFilter, Winding},
+};
 
-use rusqlite::{Connection, OpenFlags, Result, Row};
+use rusqlite::{types::ValueRef, Connection, OpenFlags, OptionalExtension, Result, Row};
 use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    ops::Range,
     path::Path,
-    sync::{Arc, Mutex},
 };
 
 #[repr(C)]
@@ -12,59 +17,170 @@ pub struct GpkgDataSource {
     pub layer_name: String,
     pub column_specs: Vec<ColumnSpec>,
     pub sql: String,
-    pub gpkg: Gpkg,
+    // The indices of the output columns that the columns of this data source are
+    // written to. This is not just 0..n when `union_by_name` is specified.
+    pub column_indices: Vec<usize>,
+    pub n_rows: usize,
+    // The connection is not shared between the data sources. Each scan opens its own
+    // connection lazily so that the reads of the same file don't contend on a lock.
+    pub path: String,
+    // The modification time of the file (only when `include_mtime` is specified)
+    pub mtime: Option<i64>,
+    // The rows are checked against this while scanning (only when `bbox` is
+    // specified). The spatial index, if any, is already used in `sql`, but it's
+    // just a rough filter.
+    pub bbox: Option<Bbox>,
+    // The metadata of the layer declared in gpkg_contents
+    pub contents: LayerContents,
+    // The geometry type declared in gpkg_geometry_columns (e.g. "POINT"). None if
+    // the layer has no geometry.
+    pub geometry_type: Option<String>,
+}
+
+// The columns of gpkg_contents. Note that these are what the file declares, so
+// they might not match the actual data.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LayerContents {
+    // The last modification time in microseconds since Unix epoch (UTC)
+    pub last_change: Option<i64>,
+    pub min_x: Option<f64>,
+    pub min_y: Option<f64>,
+    pub max_x: Option<f64>,
+    pub max_y: Option<f64>,
 }
 
-#[derive(Clone)]
 pub struct Gpkg {
-    pub conn: Arc<Mutex<GpkgConnection>>,
+    pub conn: GpkgConnection,
     pub path: String,
     pub layers: Vec<String>,
+    // If true, the columns of the tables related one-to-one to the layer (cf. the
+    // Related Tables Extension) are joined.
+    pub follow_relations: bool,
+    // The geometry column to read as "the" geometry of a layer with multiple
+    // geometry columns. The others are read as attribute columns.
+    pub geometry_column: Option<String>,
+    // If true, the schema of the first layer is used for the rest of the layers as
+    // well. The rest are not inspected, so a mismatch is an error at the scan.
+    pub schema_from_first: bool,
+}
+
+// A relation defined in gpkgext_relations.
+// cf. https://docs.ogc.org/is/18-000/18-000.html
+struct Relation {
+    base_primary_column: String,
+    related_table_name: String,
+    related_primary_column: String,
+    mapping_table_name: String,
 }
 
+// The number of the prepared statements cached per connection. fetch_rows() uses
+// one statement per layer, so this is the number of the layers in a file that can
+// be read without preparing the statements again.
+const STATEMENT_CACHE_CAPACITY: usize = 256;
+
 pub struct GpkgConnection {
-    // TODO: probably, this should contain Statement instaed of Connection.
-    // But, it seems it's not possible due to the lifetime requirement.
+    // A Statement cannot be stored next to the Connection it borrows, so the
+    // statements are kept in the statement cache of the connection instead. The
+    // connection is pooled across the chunks (cf. StReadMultiInitData), so a
+    // statement is prepared only once per connection.
     pub conn: Connection,
+    pub path: String,
 }
 
 impl GpkgConnection {
-    fn new(conn: Connection) -> Self {
-        Self { conn }
+    // If `immutable` is true, the file is opened with the immutable flag, which tells
+    // SQLite that the file never changes during the connection. SQLite doesn't take
+    // any locks then, so the file can be read while some other process is writing
+    // to it (but the result might be inconsistent if it actually changes).
+    pub(crate) fn open<P: AsRef<Path>>(path: P, immutable: bool) -> Result<Self> {
+        let conn = if immutable {
+            Connection::open_with_flags(
+                sqlite_uri(path.as_ref(), "immutable=1"),
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI,
+            )?
+        } else {
+            Connection::open_with_flags(
+                path.as_ref(),
+                OpenFlags::SQLITE_OPEN_READ_ONLY, // open as read only
+            )?
+        };
+        conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+
+        Ok(Self {
+            conn,
+            path: path.as_ref().to_string_lossy().to_string(),
+        })
     }
 
-    // Returns the number of rows fetched.
-    pub fn fetch_rows<F>(&mut self, sql: &str, offset: usize, mut f: F) -> Result<usize>
+    // Fetch the rows in the range. `f` returns false if it skips the row, so that
+    // the next row is written to the same index. Returns the number of rows written.
+    pub fn fetch_rows<F>(&mut self, sql: &str, range: Range<usize>, mut f: F) -> Result<usize>
     where
-        F: FnMut(&Row<'_>, usize) -> Result<()>,
+        F: FnMut(&Row<'_>, usize) -> Result<bool>,
     {
         let mut row_idx: usize = 0;
 
         let mut stmt = self.conn.prepare_cached(sql)?;
-        let result = stmt
-            .query_map([offset as isize], |row| {
-                let result = f(row, row_idx);
+        stmt.query_map([range.len() as isize, range.start as isize], |row| {
+            if f(row, row_idx)? {
                 row_idx += 1;
-                result
-            })?
-            // result needs to be consumed, otherwise, the closure is not executed.
-            .collect::<Result<Vec<()>>>()?;
+            }
+            Ok(())
+        })?
+        // result needs to be consumed, otherwise, the closure is not executed.
+        .collect::<Result<Vec<()>>>()?;
+
+        Ok(row_idx)
+    }
+}
+
+// Convert the path to a URI filename with the query parameters. '?' and '#' in the
+// path have special meanings in a URI, so they are percent-encoded.
+//
+// cf. https://www.sqlite.org/uri.html
+fn sqlite_uri(path: &Path, query: &str) -> String {
+    let path = path
+        .to_string_lossy()
+        .replace('\\', "/")
+        .replace('%', "%25")
+        .replace('?', "%3f")
+        .replace('#', "%23");
+    format!("file:{path}?{query}")
+}
 
-        Ok(result.len())
+// Hash a raw value of a column (used for `distinct`). The type is also hashed so
+// that e.g. 1 and '1' are distinguished.
+pub(crate) fn hash_value_ref<H: Hasher>(val: ValueRef<'_>, state: &mut H) {
+    match val {
+        ValueRef::Null => 0u8.hash(state),
+        ValueRef::Integer(v) => (1u8, v).hash(state),
+        ValueRef::Real(v) => (2u8, v.to_bits()).hash(state),
+        ValueRef::Text(v) => (3u8, v).hash(state),
+        ValueRef::Blob(v) => (4u8, v).hash(state),
     }
 }
 
+// Quote a table or column name to embed in SQL. A layer name can contain any
+// characters, so the double quotes in it are escaped by doubling them.
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!(r#""{}""#, name.replace('"', r#""""#))
+}
+
+// Quote a string literal to embed in SQL (e.g. the argument of pragma_table_info()).
+// The single quotes in it are escaped by doubling them.
+pub(crate) fn quote_literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
 impl Gpkg {
     pub(crate) fn new<P: AsRef<Path>>(
         path: P,
         layer_name: Option<String>,
+        immutable: bool,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let conn = Connection::open_with_flags(
-            path.as_ref(),
-            OpenFlags::SQLITE_OPEN_READ_ONLY, // open as read only
-        )?;
+        let conn = GpkgConnection::open(path.as_ref(), immutable)?;
 
-        let mut stmt = conn.prepare("SELECT table_name FROM gpkg_contents")?;
+        let mut stmt = conn.conn.prepare("SELECT table_name FROM gpkg_contents")?;
         let layers = stmt
             .query_map([], |row| row.get(0))?
             .collect::<Result<Vec<String>, _>>()?;
@@ -73,23 +189,29 @@ impl Gpkg {
         let path = path.as_ref().to_string_lossy().to_string();
         if let Some(layer_name) = layer_name {
             let layers = if !layers.contains(&layer_name) {
-                eprintln!("[WARN] No such layer '{layer_name}' in {path}",);
+                warn!("No such layer '{layer_name}' in {path}",);
                 vec![]
             } else {
                 vec![layer_name]
             };
 
             Ok(Self {
-                conn: Arc::new(Mutex::new(GpkgConnection::new(conn))),
+                conn,
                 path,
                 layers,
+                follow_relations: false,
+                geometry_column: None,
+                schema_from_first: false,
             })
         } else {
             // If layer is not specified, return all the layers
             Ok(Self {
-                conn: Arc::new(Mutex::new(GpkgConnection::new(conn))),
+                conn,
                 path,
                 layers,
+                follow_relations: false,
+                geometry_column: None,
+                schema_from_first: false,
             })
         }
     }
@@ -100,18 +222,42 @@ impl Gpkg {
         table_name: T,
     ) -> Result<String, Box<dyn std::error::Error>> {
         let query = format!(
-            "SELECT name FROM pragma_table_info('{}') WHERE pk = 1",
-            table_name.as_ref()
+            "SELECT name FROM pragma_table_info({}) WHERE pk = 1",
+            quote_literal(table_name.as_ref())
         );
         let pk: String = conn.query_row(&query, [], |row| row.get(0))?;
         Ok(pk)
     }
 
+    // SQLite allows a column without a declared type. The type of such a column is
+    // guessed from the storage class of the first non-null value. If all the values
+    // are NULL, it's VARCHAR.
+    fn guess_column_type(
+        conn: &Connection,
+        table_name: &str,
+        column_name: &str,
+    ) -> Result<ColumnType> {
+        let query = format!(
+            "SELECT typeof({0}) FROM {1} WHERE {0} IS NOT NULL LIMIT 1",
+            quote_identifier(column_name),
+            quote_identifier(table_name)
+        );
+        let storage_class: Option<String> =
+            conn.query_row(&query, [], |row| row.get(0)).optional()?;
+        let column_type = match storage_class.as_deref() {
+            Some("integer") => ColumnType::Integer,
+            Some("real") => ColumnType::Double,
+            Some("blob") => ColumnType::Blob,
+            _ => ColumnType::Varchar,
+        };
+        Ok(column_type)
+    }
+
     pub(crate) fn get_column_specs<T: AsRef<str>>(
         &self,
         table_name: T,
     ) -> Result<Vec<ColumnSpec>, Box<dyn std::error::Error>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = &self.conn;
 
         let pk_column = Self::get_pk_column(&conn.conn, table_name.as_ref())?;
 
@@ -128,9 +274,9 @@ impl Gpkg {
         };
 
         let query = format!(
-            "SELECT name, type FROM pragma_table_info('{}') WHERE name != '{}'",
-            table_name.as_ref(),
-            pk_column
+            "SELECT name, type FROM pragma_table_info({}) WHERE name != {}",
+            quote_literal(table_name.as_ref()),
+            quote_literal(&pk_column)
         );
         let mut stmt = conn.conn.prepare(&query)?;
 
@@ -155,8 +301,10 @@ impl Gpkg {
                 "BOOLEAN" => ColumnType::Boolean,
                 // cf. https://www.geopackage.org/spec140/index.html#geometry_types
                 "DATE" => ColumnType::Date,
-                "DATETIME" => ColumnType::Timestamp,
+                "DATETIME" => ColumnType::TimestampTz,
                 "BLOB" => ColumnType::Blob,
+                // no declared type
+                "" => Self::guess_column_type(&conn.conn, table_name.as_ref(), &name)?,
                 // cf. https://www.geopackage.org/spec140/index.html#geometry_types
                 "GEOMETRY" | "POINT" | "LINESTRING" | "POLYGON" | "MULTIPOINT"
                 | "MULTILINESTRING" | "MULTIPOLYGON" | "GEOMETRYCOLLECTION" => ColumnType::Geometry,
@@ -173,83 +321,409 @@ impl Gpkg {
         })?;
 
         let result: Result<Vec<ColumnSpec>, rusqlite::Error> = column_specs.collect();
-        Ok(result?)
+        let mut column_specs = result?;
+
+        // DATETIME is just a TEXT for SQLite, so the values are not necessarily valid.
+        // If some value cannot be parsed, fall back to VARCHAR.
+        for spec in column_specs
+            .iter_mut()
+            .filter(|s| s.column_type == ColumnType::TimestampTz)
+        {
+            let query = format!(
+                "SELECT {0} FROM {1} WHERE {0} IS NOT NULL LIMIT 100",
+                quote_identifier(&spec.name),
+                quote_identifier(table_name.as_ref())
+            );
+            let mut stmt = conn.conn.prepare(&query)?;
+            let values = stmt
+                .query_map([], |row| row.get::<_, rusqlite::types::Value>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let all_valid = values.iter().all(|v| match v {
+                rusqlite::types::Value::Text(s) => parse_datetime_to_utc_micros(s).is_some(),
+                _ => false,
+            });
+            if !all_valid {
+                warn!(
+                    "Column '{}' of {} in {} has invalid DATETIME values; read as VARCHAR",
+                    spec.name,
+                    table_name.as_ref(),
+                    self.path
+                );
+                spec.column_type = ColumnType::Varchar;
+            }
+        }
+
+        // Put the geometry column first so that the column layout is the same as the
+        // other formats. The sort is stable, so the order of the others is kept.
+        column_specs.sort_by_key(|s| s.column_type != ColumnType::Geometry);
+
+        Ok(column_specs)
     }
 
+    // If `column_specs` is supplied, it's used for all the layers instead of
+    // inspecting the schema of each layer. If `geometry_only` is true, only the
+    // geometry columns are read. Otherwise, the attribute columns are filtered by
+    // `column_filter`. If `bbox` is supplied, the rows are filtered by the spatial
+    // index if the layer has it. If `follow_relations` is true, the columns of the
+    // related tables are appended after the columns of the layer.
     pub(crate) fn list_data_sources(
         &self,
+        column_specs: Option<&[ColumnSpec]>,
+        geometry_only: bool,
+        column_filter: &ColumnFilter,
+        bbox: Option<&Bbox>,
     ) -> Result<Vec<GpkgDataSource>, Box<dyn std::error::Error>> {
         let mut sources = Vec::new();
 
         for layer in &self.layers {
-            let column_specs = self.get_column_specs(layer)?;
+            let relations = if self.follow_relations {
+                self.find_one_to_one_relations(layer)?
+            } else {
+                vec![]
+            };
 
-            let pk_column = {
-                let conn = self.conn.lock().unwrap();
-                Self::get_pk_column(&conn.conn, layer)?
+            let column_specs = column_specs.or_else(|| {
+                sources
+                    .first()
+                    .filter(|_| self.schema_from_first)
+                    .map(|s: &GpkgDataSource| s.column_specs.as_slice())
+            });
+            let specified = column_specs.is_some();
+            let mut column_specs = match column_specs {
+                Some(specs) => specs.to_vec(),
+                None => {
+                    let mut specs = self.get_column_specs(layer)?;
+                    self.select_geometry_column(layer, &mut specs)?;
+                    specs
+                }
             };
 
+            // The table alias of each related column
+            let mut related_columns: HashMap<String, String> = HashMap::new();
+            if !relations.is_empty() {
+                let layer_columns = self.get_column_names(layer)?;
+                for (i, relation) in relations.iter().enumerate() {
+                    for spec in self.get_column_specs(&relation.related_table_name)? {
+                        if spec.column_type == ColumnType::Geometry {
+                            continue;
+                        }
+                        if layer_columns.contains(&spec.name)
+                            || related_columns.contains_key(&spec.name)
+                        {
+                            warn!(
+                                "Column '{}' of the related table '{}' is ignored because the name is already used: {layer} in {}",
+                                spec.name, relation.related_table_name, self.path
+                            );
+                            continue;
+                        }
+                        related_columns.insert(spec.name.clone(), format!("r{i}"));
+                        if !specified {
+                            column_specs.push(spec);
+                        }
+                    }
+                }
+            }
+            if geometry_only {
+                column_specs.retain(|s| s.column_type == ColumnType::Geometry);
+            } else {
+                column_specs.retain(|s| {
+                    s.column_type == ColumnType::Geometry || column_filter.keeps(&s.name)
+                });
+            }
+
+            let pk_column = format!(
+                "{}.{}",
+                quote_identifier(layer),
+                quote_identifier(&Self::get_pk_column(&self.conn.conn, layer)?)
+            );
+
+            // The geometry column comes first (cf. get_column_specs()). A layer
+            // without geometry (i.e. an attribute table) is not filtered.
+            let geometry_column = column_specs
+                .first()
+                .filter(|s| s.column_type == ColumnType::Geometry)
+                .map(|s| s.name.as_str());
+            let bbox = match (bbox, geometry_column) {
+                (Some(_), None) => {
+                    warn!(
+                        "'bbox' is ignored for the layer without geometry: {layer} in {}",
+                        self.path
+                    );
+                    None
+                }
+                (bbox, _) => bbox.copied(),
+            };
+
+            let where_clause = match (bbox, geometry_column) {
+                (Some(bbox), Some(geometry_column)) => {
+                    match self.find_rtree(layer, geometry_column)? {
+                        Some(rtree) => format!(
+                            " WHERE {} IN (SELECT id FROM {} WHERE minx <= {:?} AND maxx >= {:?} AND miny <= {:?} AND maxy >= {:?})",
+                            pk_column,
+                            quote_identifier(&rtree),
+                            bbox.xmax, bbox.xmin, bbox.ymax, bbox.ymin,
+                        ),
+                        // no spatial index; all the rows are checked while scanning
+                        None => String::new(),
+                    }
+                }
+                _ => String::new(),
+            };
+
+            // The related tables are joined via the mapping tables. The relations are
+            // one-to-one, so this doesn't change the number of rows.
+            let joins: String = relations
+                .iter()
+                .enumerate()
+                .map(|(i, relation)| {
+                    format!(
+                        " LEFT JOIN {} AS m{i} ON m{i}.base_id = {}.{} LEFT JOIN {} AS r{i} ON r{i}.{} = m{i}.related_id",
+                        quote_identifier(&relation.mapping_table_name),
+                        quote_identifier(layer),
+                        quote_identifier(&relation.base_primary_column),
+                        quote_identifier(&relation.related_table_name),
+                        quote_identifier(&relation.related_primary_column),
+                    )
+                })
+                .collect();
+
             let sql = format!(
-                r#"SELECT {} FROM "{}" ORDER BY "{}" LIMIT {VECTOR_SIZE} OFFSET ?"#,
+                "SELECT {} FROM {}{}{} ORDER BY {} LIMIT ? OFFSET ?",
                 column_specs
                     .iter()
-                    .map(|s| format!(r#""{}""#, s.name))
+                    .map(|s| {
+                        let table = match related_columns.get(&s.name) {
+                            Some(alias) => alias.clone(),
+                            None => quote_identifier(layer),
+                        };
+                        format!("{table}.{}", quote_identifier(&s.name))
+                    })
                     .collect::<Vec<String>>()
                     .join(","),
-                layer,
+                quote_identifier(layer),
+                joins,
+                where_clause,
                 pk_column,
             );
 
+            let n_rows: usize = self.conn.conn.query_row(
+                &format!(
+                    "SELECT count(*) FROM {}{where_clause}",
+                    quote_identifier(layer)
+                ),
+                [],
+                |row| row.get(0),
+            )?;
+
+            let geometry_type = match geometry_column {
+                Some(geometry_column) => self.get_geometry_type(layer, geometry_column)?,
+                None => None,
+            };
+
             sources.push(GpkgDataSource {
                 layer_name: layer.to_string(),
+                column_indices: (0..column_specs.len()).collect(),
                 column_specs,
                 sql,
-                gpkg: self.clone(),
+                n_rows,
+                path: self.path.clone(),
+                mtime: None,
+                bbox,
+                contents: self.get_layer_contents(layer)?,
+                geometry_type,
             });
         }
 
         Ok(sources)
     }
+
+    // Move the geometry column specified by `geometry_column` to the first so that
+    // it becomes the geometry of the layer. The order of the others is kept.
+    fn select_geometry_column(
+        &self,
+        layer: &str,
+        column_specs: &mut [ColumnSpec],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(name) = &self.geometry_column else {
+            return Ok(());
+        };
+        match column_specs.iter().position(|s| s.name == *name) {
+            Some(i) if column_specs[i].column_type == ColumnType::Geometry => {
+                column_specs[..=i].rotate_right(1);
+                Ok(())
+            }
+            _ => Err(format!(
+                "No such geometry column '{name}' in layer '{layer}' of {}",
+                self.path
+            )
+            .into()),
+        }
+    }
+
+    fn get_layer_contents(&self, layer: &str) -> Result<LayerContents, Box<dyn std::error::Error>> {
+        let contents = self.conn.conn.query_row(
+            "SELECT last_change, min_x, min_y, max_x, max_y FROM gpkg_contents WHERE table_name = ?",
+            [layer],
+            |row| {
+                let last_change: Option<String> = row.get(0)?;
+                Ok(LayerContents {
+                    last_change: last_change.as_deref().and_then(parse_datetime_to_utc_micros),
+                    min_x: row.get(1)?,
+                    min_y: row.get(2)?,
+                    max_x: row.get(3)?,
+                    max_y: row.get(4)?,
+                })
+            },
+        )?;
+        Ok(contents)
+    }
+
+    // Returns the geometry type declared in gpkg_geometry_columns, in upper case
+    fn get_geometry_type(
+        &self,
+        layer: &str,
+        geometry_column: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let geometry_type: Option<String> = self
+            .conn
+            .conn
+            .query_row(
+                "SELECT geometry_type_name FROM gpkg_geometry_columns WHERE table_name = ? AND column_name = ?",
+                [layer, geometry_column],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(geometry_type.map(|t| t.to_uppercase()))
+    }
+
+    // Returns the name of the spatial index (R*Tree) of the geometry column, if
+    // any. cf. https://www.geopackage.org/spec140/index.html#extension_rtree
+    fn find_rtree(
+        &self,
+        layer: &str,
+        geometry_column: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let rtree = format!("rtree_{layer}_{geometry_column}");
+        let exists: bool = self.conn.conn.query_row(
+            "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?",
+            [&rtree],
+            |row| row.get(0),
+        )?;
+        Ok(exists.then_some(rtree))
+    }
+
+    fn get_column_names(
+        &self,
+        table_name: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut stmt = self
+            .conn
+            .conn
+            .prepare("SELECT name FROM pragma_table_info(?)")?;
+        let names = stmt
+            .query_map([table_name], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(names)
+    }
+
+    // Returns the relations whose base table is the layer. The relations that are
+    // not one-to-one are skipped with a warning.
+    fn find_one_to_one_relations(
+        &self,
+        layer: &str,
+    ) -> Result<Vec<Relation>, Box<dyn std::error::Error>> {
+        let conn = &self.conn.conn;
+        let exists: bool = conn.query_row(
+            "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'gpkgext_relations'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Ok(vec![]);
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT base_primary_column, related_table_name, related_primary_column, mapping_table_name FROM gpkgext_relations WHERE base_table_name = ? ORDER BY id",
+        )?;
+        let relations = stmt
+            .query_map([layer], |row| {
+                Ok(Relation {
+                    base_primary_column: row.get(0)?,
+                    related_table_name: row.get(1)?,
+                    related_primary_column: row.get(2)?,
+                    mapping_table_name: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<Relation>, _>>()?;
+
+        let mut one_to_one = Vec::new();
+        for relation in relations {
+            // A relation is one-to-one if neither side appears more than once in
+            // the mapping table.
+            let query = format!(
+                "SELECT NOT EXISTS (SELECT 1 FROM {0} GROUP BY base_id HAVING count(*) > 1) AND NOT EXISTS (SELECT 1 FROM {0} GROUP BY related_id HAVING count(*) > 1)",
+                quote_identifier(&relation.mapping_table_name)
+            );
+            let is_one_to_one: bool = conn.query_row(&query, [], |row| row.get(0))?;
+            if is_one_to_one {
+                one_to_one.push(relation);
+            } else {
+                warn!(
+                    "The relation to '{}' is ignored because it's not one-to-one: {layer} in {}",
+                    relation.related_table_name, self.path
+                );
+            }
+        }
+
+        Ok(one_to_one)
+    }
 }
 
-/// Parse "YYYY-MM-DD" to days since Unix epoch (1970-01-01).
-pub(crate) fn parse_date_to_unix_days(s: &str) -> i32 {
+/// Parse "YYYY-MM-DD" to days since Unix epoch (1970-01-01). None if it's not a date.
+pub(crate) fn parse_date_to_unix_days(s: &str) -> Option<i32> {
     let b = s.as_bytes();
+    let is_date = b.len() >= 10
+        && b[4] == b'-'
+        && b[7] == b'-'
+        && [0, 1, 2, 3, 5, 6, 8, 9]
+            .iter()
+            .all(|&i| b[i].is_ascii_digit());
+    if !is_date {
+        return None;
+    }
     let year = parse_digits(b, 0, 4) as i32;
     let month = parse_digits(b, 5, 2) as u32;
     let day = parse_digits(b, 8, 2) as u32;
-    days_from_civil(year, month, day)
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day))
 }
 
-/// Parse "YYYY-MM-DDTHH:MM:SS", "YYYY-MM-DDTHH:MM:SS.SSSZ", or "YYYY-MM-DD HH:MM:SS" variants
-/// to microseconds since Unix epoch.
-pub(crate) fn parse_datetime_to_unix_micros(s: &str) -> i64 {
-    let b = s.as_bytes();
-    let days = parse_date_to_unix_days(s) as i64;
-    // byte 10 is 'T' or ' '
-    let hour = parse_digits(b, 11, 2) as i64;
-    let min = parse_digits(b, 14, 2) as i64;
-    let sec = parse_digits(b, 17, 2) as i64;
-
-    let mut micros = 0i64;
-    // Optional fractional seconds: ".SSS"
-    if b.len() > 19 && b[19] == b'.' {
-        let frac_start = 20;
-        let frac_end = b[frac_start..]
-            .iter()
-            .position(|&c| !c.is_ascii_digit())
-            .map_or(b.len(), |p| frac_start + p);
-        let frac_len = frac_end - frac_start;
-        let frac_val = parse_digits(b, frac_start, frac_len) as i64;
-        // Scale to microseconds (6 digits)
-        micros = if frac_len <= 6 {
-            frac_val * 10i64.pow(6 - frac_len as u32)
-        } else {
-            frac_val / 10i64.pow(frac_len as u32 - 6)
-        };
+/// Parse an ISO 8601 datetime (e.g. "2024-01-15T10:30:00.000Z", "2024-01-15 10:30:00+09:00")
+/// or a date (e.g. "2024-01-15") to microseconds since Unix epoch in UTC. If the offset
+/// is missing, the value is assumed to be in UTC as the spec requires.
+pub(crate) fn parse_datetime_to_utc_micros(s: &str) -> Option<i64> {
+    use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+
+    // SQLite often uses a space as the separator instead of "T"
+    let s = s.trim().replacen(' ', "T", 1);
+
+    // with offset (e.g. "Z", "+09:00")
+    if let Ok(dt) = DateTime::parse_from_rfc3339(&s) {
+        return Some(dt.timestamp_micros());
     }
 
-    days * 86_400_000_000 + hour * 3_600_000_000 + min * 60_000_000 + sec * 1_000_000 + micros
+    // without offset
+    if let Ok(dt) = NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M:%S%.f") {
+        return Some(dt.and_utc().timestamp_micros());
+    }
+
+    // date only
+    NaiveDate::parse_from_str(&s, "%Y-%m-%d")
+        .ok()
+        .map(|d| d.and_time(NaiveTime::MIN).and_utc().timestamp_micros())
 }
 
 fn parse_digits(b: &[u8], offset: usize, len: usize) -> i64 {
@@ -272,9 +746,286 @@ fn days_from_civil(year: i32, month: u32, day: u32) -> i32 {
     (era * 146097 + doe as i32 - 719468) as i32
 }
 
+// Check if the file is a GeoPackage, i.e., an SQLite database with gpkg_contents.
+pub(crate) fn sniff_gpkg<P: AsRef<Path>>(path: P) -> bool {
+    use std::io::Read;
+
+    let mut magic = [0u8; 16];
+    let is_sqlite = std::fs::File::open(path.as_ref())
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok_and(|_| &magic == b"SQLite format 3\0");
+    if !is_sqlite {
+        return false;
+    }
+
+    GpkgConnection::open(path, false)
+        .and_then(|conn| {
+            conn.conn.query_row(
+                "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'gpkg_contents'",
+                [],
+                |row| row.get(0),
+            )
+        })
+        .unwrap_or(false)
+}
+
+// How the geometry is stored in the geometry columns.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GeometryColumnEncoding {
+    // GeoPackage binary (the standard)
+    Gpkg,
+    // GeoJSON string
+    GeoJson,
+    // Detected from the first bytes of each BLOB: GeoPackage binary, WKB, EWKB, or
+    // SpatiaLite BLOB (cf. blob.rs)
+    Auto,
+}
+
+impl std::str::FromStr for GeometryColumnEncoding {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gpkg" => Ok(Self::Gpkg),
+            "geojson" => Ok(Self::GeoJson),
+            "auto" => Ok(Self::Auto),
+            _ => Err(format!(
+                "Unknown value in 'geometry_column_encoding' option: {s} (must be 'gpkg', 'geojson', or 'auto')"
+            )
+            .into()),
+        }
+    }
+}
+
+// How to read a database in WAL mode (`wal_mode` option). The changes written by
+// some other process stay in the `-wal` sidecar file until they are checkpointed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WalMode {
+    // Read the sidecar as well, as SQLite usually does. The result contains the
+    // latest committed changes, but a commit while scanning might be seen only
+    // partially because each chunk is read by a separate query.
+    #[default]
+    Read,
+    // Ignore the sidecar and read only the main database file (same as
+    // `immutable`). The changes not yet checkpointed are not seen.
+    Ignore,
+    // Raise an error if the sidecar exists, i.e. the file might be being written.
+    Error,
+}
+
+impl std::str::FromStr for WalMode {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "read" => Ok(Self::Read),
+            "ignore" => Ok(Self::Ignore),
+            "error" => Ok(Self::Error),
+            _ => Err(format!(
+                "Unknown value in 'wal_mode' option: {s} (must be 'read', 'ignore', or 'error')"
+            )
+            .into()),
+        }
+    }
+}
+
+// Check if the database has a non-empty `-wal` sidecar file. The file is removed
+// when the last connection is closed, so this means some connection is open.
+pub(crate) fn has_wal<P: AsRef<Path>>(path: P) -> bool {
+    let mut wal_path = path.as_ref().as_os_str().to_owned();
+    wal_path.push("-wal");
+    std::fs::metadata(wal_path).is_ok_and(|m| m.len() > 0)
+}
+
+// The geometry type that the layers must have (`enforce_geometry_type` option).
+#[derive(Clone, Debug, PartialEq)]
+pub enum GeometryTypeConstraint {
+    // All the layers have the same geometry type, whatever it is
+    Same,
+    // All the layers have this geometry type (in upper case)
+    Exact(String),
+}
+
+impl std::str::FromStr for GeometryTypeConstraint {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // cf. https://www.geopackage.org/spec140/index.html#geometry_types
+        const GEOMETRY_TYPES: [&str; 8] = [
+            "GEOMETRY",
+            "POINT",
+            "LINESTRING",
+            "POLYGON",
+            "MULTIPOINT",
+            "MULTILINESTRING",
+            "MULTIPOLYGON",
+            "GEOMETRYCOLLECTION",
+        ];
+
+        let upper = s.to_uppercase();
+        if upper == "SAME" {
+            Ok(Self::Same)
+        } else if GEOMETRY_TYPES.contains(&upper.as_str()) {
+            Ok(Self::Exact(upper))
+        } else {
+            Err(format!(
+                "Unknown value in 'enforce_geometry_type' option: {s} (must be 'same' or a geometry type like 'point')"
+            )
+            .into())
+        }
+    }
+}
+
+// Check if the geometry types declared for the layers satisfy the constraint. The
+// layers without geometry are not checked.
+pub(crate) fn validate_geometry_types(
+    sources: &[GpkgDataSource],
+    constraint: &GeometryTypeConstraint,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut sources = sources
+        .iter()
+        .filter_map(|s| s.geometry_type.as_deref().map(|t| (s, t)));
+
+    let expected = match constraint {
+        GeometryTypeConstraint::Exact(expected) => expected.as_str(),
+        GeometryTypeConstraint::Same => match sources.next() {
+            Some((_, geometry_type)) => geometry_type,
+            None => return Ok(()),
+        },
+    };
+
+    match sources.find(|(_, t)| *t != expected) {
+        Some((source, geometry_type)) => Err(format!(
+            "Geometry type of layer '{}' in {} is {geometry_type}, but expected {expected} (cf. 'enforce_geometry_type' option)",
+            source.layer_name, source.path
+        )
+        .into()),
+        None => Ok(()),
+    }
+}
+
+// Parse a GeoJSON geometry object and convert it to WKB.
+pub(crate) fn geojson_geometry_to_wkb(
+    s: &str,
+    buffer_distance: Option<f64>,
+    max_segment_length: Option<f64>,
+    coordinate_precision: Option<i32>,
+    winding: Option<Winding>,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let geojson_geom: ::geojson::Geometry = serde_json::from_str(s)?;
+    let mut geometry: geo_types::Geometry = geojson_geom.try_into()?;
+    if let Some(distance) = buffer_distance {
+        crate::utils::buffer(&mut geometry, distance);
+    }
+    if let Some(max_segment_length) = max_segment_length {
+        crate::utils::densify(&mut geometry, max_segment_length);
+    }
+    if let Some(precision) = coordinate_precision {
+        crate::utils::round_coordinates(&mut geometry, precision);
+    }
+    if let Some(winding) = winding {
+        crate::utils::orient_polygons(&mut geometry, winding);
+    }
+    let mut buffer = Vec::new();
+    wkb::writer::write_geometry(&mut buffer, &geometry, &Default::default())
+        .map_err(|e| e.to_string())?;
+    Ok(buffer)
+}
+
+// Check if the geometry (the raw value of the geometry column) intersects with the
+// bbox. The envelope is compared, not the geometry itself. NULL and empty
+// geometries never intersect.
+pub(crate) fn geometry_intersects_bbox(
+    val: ValueRef<'_>,
+    encoding: GeometryColumnEncoding,
+    bbox: &Bbox,
+) -> bool {
+    let envelope = match (val, encoding) {
+        (ValueRef::Blob(b), GeometryColumnEncoding::Gpkg) => gpkg_geometry_envelope(b),
+        (ValueRef::Blob(b), GeometryColumnEncoding::Auto) => match detect_blob_flavor(b) {
+            Some(BlobFlavor::Gpkg) => gpkg_geometry_envelope(b),
+            _ => blob_to_wkb(b).ok().and_then(|wkb| {
+                use geo_traits::to_geo::ToGeoGeometry;
+
+                let geometry = wkb::reader::read_wkb(&wkb).ok()?;
+                crate::utils::geometry_bounds(&geometry.try_to_geometry()?)
+            }),
+        },
+        (ValueRef::Text(s), GeometryColumnEncoding::GeoJson) => {
+            serde_json::from_slice::<::geojson::Geometry>(s)
+                .ok()
+                .and_then(|g| geo_types::Geometry::<f64>::try_from(g).ok())
+                .and_then(|g| crate::utils::geometry_bounds(&g))
+        }
+        _ => None,
+    };
+    envelope.is_some_and(|e| e.intersects(bbox))
+}
+
+// The rows are already filtered by the bbox of the filter (cf. `bbox`), so this
+// always does the exact intersection test.
+pub(crate) fn geometry_intersects_filter(
+    val: ValueRef<'_>,
+    encoding: GeometryColumnEncoding,
+    filter: &SpatialFilter,
+) -> bool {
+    match (val, encoding) {
+        (ValueRef::Blob(b), GeometryColumnEncoding::Gpkg) => {
+            filter.intersects_wkb(gpkg_geometry_to_wkb(b))
+        }
+        (ValueRef::Blob(b), GeometryColumnEncoding::Auto) => {
+            blob_to_wkb(b).is_ok_and(|wkb| filter.intersects_wkb(&wkb))
+        }
+        (ValueRef::Text(s), GeometryColumnEncoding::GeoJson) => {
+            serde_json::from_slice::<::geojson::Geometry>(s)
+                .ok()
+                .and_then(|g| geo_types::Geometry::<f64>::try_from(g).ok())
+                .is_some_and(|g| filter.intersects(&g))
+        }
+        _ => false,
+    }
+}
+
+// Read the envelope from the header. If the header doesn't contain the envelope,
+// calculate it from the geometry.
+pub(crate) fn gpkg_geometry_envelope(b: &[u8]) -> Option<Bbox> {
+    use geo_traits::to_geo::ToGeoGeometry;
+
+    let flags = *b.get(3)?;
+    // empty geometry
+    if flags & 0b00010000 != 0 {
+        return None;
+    }
+
+    if flags & 0b00001110 != 0 {
+        let little_endian = flags & 0b00000001 != 0;
+        let read = |i: usize| -> Option<f64> {
+            let bytes: [u8; 8] = b.get((8 + 8 * i)..(16 + 8 * i))?.try_into().ok()?;
+            Some(if little_endian {
+                f64::from_le_bytes(bytes)
+            } else {
+                f64::from_be_bytes(bytes)
+            })
+        };
+        // [minx, maxx, miny, maxy, ...]
+        return Some(Bbox {
+            xmin: read(0)?,
+            xmax: read(1)?,
+            ymin: read(2)?,
+            ymax: read(3)?,
+        });
+    }
+
+    let geometry = wkb::reader::read_wkb(gpkg_geometry_to_wkb(b)).ok()?;
+    crate::utils::geometry_bounds(&geometry.try_to_geometry()?)
+}
+
 // cf. https://www.geopackage.org/spec140/index.html#gpb_format
 pub(crate) fn gpkg_geometry_to_wkb(b: &[u8]) -> &[u8] {
-    let flags = b[3];
+    // too short to have the header
+    let Some(&flags) = b.get(3) else {
+        return &[];
+    };
     let envelope_size: usize = match flags & 0b00001110 {
         0b00000000 => 0,  // no envelope
         0b00000010 => 32, // envelope is [minx, maxx, miny, maxy], 32 bytes
@@ -285,7 +1036,8 @@ pub(crate) fn gpkg_geometry_to_wkb(b: &[u8]) -> &[u8] {
     };
     let offset = 8 + envelope_size;
 
-    &b[offset..]
+    // too short to have the envelope
+    b.get(offset..).unwrap_or(&[])
 }
 
 #[cfg(test)]
@@ -294,7 +1046,7 @@ mod tests {
 
     #[test]
     fn test_get_column_specs() -> Result<(), Box<dyn std::error::Error>> {
-        let gpkg = super::Gpkg::new("./test/data/points.gpkg", None)?;
+        let gpkg = super::Gpkg::new("./test/data/points.gpkg", None, false)?;
         let layers = gpkg.get_column_specs("points")?;
 
         assert_eq!(layers.len(), 3);
@@ -308,9 +1060,46 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_get_column_specs_typeless() -> Result<(), Box<dyn std::error::Error>> {
+        let gpkg = super::Gpkg::new("./test/data/gpkg_typeless/points.gpkg", None, false)?;
+        let specs = gpkg.get_column_specs("points")?;
+
+        let types: Vec<(&str, ColumnType)> = specs
+            .iter()
+            .map(|s| (s.name.as_str(), s.column_type))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("geom", ColumnType::Geometry),
+                ("int_val", ColumnType::Integer),
+                ("real_val", ColumnType::Double),
+                ("text_val", ColumnType::Varchar),
+                ("blob_val", ColumnType::Blob),
+                ("null_val", ColumnType::Varchar),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_column_specs_geometry_first() -> Result<(), Box<dyn std::error::Error>> {
+        // The geometry column "shape" is the last column in the table.
+        let gpkg = super::Gpkg::new("./test/data/gpkg_geom_last/points.gpkg", None, false)?;
+        let specs = gpkg.get_column_specs("points")?;
+
+        let names: Vec<&str> = specs.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["shape", "val1", "val2"]);
+        assert_eq!(specs[0].column_type, ColumnType::Geometry);
+
+        Ok(())
+    }
+
     #[test]
     fn test_get_column_specs_with_date() -> Result<(), Box<dyn std::error::Error>> {
-        let gpkg = super::Gpkg::new("./test/data/dates.gpkg", None)?;
+        let gpkg = super::Gpkg::new("./test/data/dates.gpkg", None, false)?;
         let specs = gpkg.get_column_specs("dates")?;
 
         assert_eq!(specs.len(), 4);
@@ -321,7 +1110,7 @@ mod tests {
         assert_eq!(&specs[2].name, "event_date");
         assert_eq!(specs[2].column_type, ColumnType::Date);
         assert_eq!(&specs[3].name, "event_datetime");
-        assert_eq!(specs[3].column_type, ColumnType::Timestamp);
+        assert_eq!(specs[3].column_type, ColumnType::TimestampTz);
 
         Ok(())
     }
@@ -331,7 +1120,11 @@ mod tests {
         // points_blob_geom.gpkg has the geometry column declared as BLOB in the
         // SQLite schema, but registered in gpkg_geometry_columns.  The column
         // should still be classified as ColumnType::Geometry.
-        let gpkg = super::Gpkg::new("./test/data/gpkg_blob_geom/points_blob_geom.gpkg", None)?;
+        let gpkg = super::Gpkg::new(
+            "./test/data/gpkg_blob_geom/points_blob_geom.gpkg",
+            None,
+            false,
+        )?;
         let specs = gpkg.get_column_specs("points")?;
 
         assert_eq!(specs.len(), 3);
@@ -349,9 +1142,12 @@ mod tests {
     fn test_gpkg_geometry_to_wkb_strips_header_for_blob_geom() -> Result<(), Box<dyn std::error::Error>> {
         // Verify that gpkg_geometry_to_wkb correctly strips the GPKG binary header
         // so the returned bytes start with the WKB byte-order marker (0x00 or 0x01).
-        let gpkg = super::Gpkg::new("./test/data/gpkg_blob_geom/points_blob_geom.gpkg", None)?;
-        let conn = gpkg.conn.lock().unwrap();
-        let mut stmt = conn.conn.prepare("SELECT geom FROM points LIMIT 1")?;
+        let gpkg = super::Gpkg::new(
+            "./test/data/gpkg_blob_geom/points_blob_geom.gpkg",
+            None,
+            false,
+        )?;
+        let mut stmt = gpkg.conn.conn.prepare("SELECT geom FROM points LIMIT 1")?;
         let blob: Vec<u8> = stmt.query_row([], |row| row.get(0))?;
 
         // Sanity check: raw blob starts with the GPKG magic 'GP'
@@ -368,39 +1164,586 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_fetch_rows_with_separate_connections() -> Result<(), Box<dyn std::error::Error>> {
+        let gpkg = super::Gpkg::new("./test/data/many_rows/points_2049.gpkg", None, false)?;
+        let sources = gpkg.list_data_sources(None, false, &Default::default(), None)?;
+        assert_eq!(sources.len(), 1);
+        let source = &sources[0];
+
+        // Each thread opens its own connection, so the reads don't block each other.
+        let counts = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..2)
+                .map(|i| {
+                    s.spawn(move || {
+                        let mut conn = super::GpkgConnection::open(&source.path, false).unwrap();
+                        let offset = i * crate::VECTOR_SIZE;
+                        let range = offset..(offset + crate::VECTOR_SIZE);
+                        conn.fetch_rows(&source.sql, range, |_, _| Ok(true))
+                            .unwrap()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<usize>>()
+        });
+
+        assert_eq!(counts, vec![crate::VECTOR_SIZE, 1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_rows_with_cached_statements() -> Result<(), Box<dyn std::error::Error>> {
+        let gpkg = super::Gpkg::new("./test/data/multi_layers.gpkg", None, false)?;
+        let sources = gpkg.list_data_sources(None, false, &Default::default(), None)?;
+        let sql = |layer: &str| {
+            let source = sources.iter().find(|s| s.layer_name == layer).unwrap();
+            source.sql.clone()
+        };
+
+        // The statements of the layers are reused with the different ranges, reading
+        // the layers alternately.
+        let mut conn = super::GpkgConnection::open("./test/data/multi_layers.gpkg", false)?;
+        let mut values: Vec<i64> = Vec::new();
+        for (layer, range) in [
+            ("points_point", 0..1),
+            ("points2_point", 0..1),
+            ("points_point", 1..2),
+            ("points2_point", 1..2),
+        ] {
+            conn.fetch_rows(&sql(layer), range, |row, _| {
+                values.push(row.get(1)?);
+                Ok(true)
+            })?;
+        }
+
+        assert_eq!(values, vec![1, 5, 2, 6]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_column_specs_geojson_geom() -> Result<(), Box<dyn std::error::Error>> {
+        // The geometry column is declared as TEXT and stores GeoJSON strings.
+        let gpkg = super::Gpkg::new(
+            "./test/data/gpkg_geojson_geom/points_geojson_geom.gpkg",
+            None,
+            false,
+        )?;
+        let specs = gpkg.get_column_specs("points")?;
+
+        assert_eq!(&specs[0].name, "geom");
+        assert_eq!(specs[0].column_type, ColumnType::Geometry);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_data_sources_bbox() -> Result<(), Box<dyn std::error::Error>> {
+        let bbox = crate::types::Bbox {
+            xmin: 0.0,
+            ymin: 0.0,
+            xmax: 5.0,
+            ymax: 5.0,
+        };
+
+        // the spatial index is used
+        let gpkg = super::Gpkg::new("./test/data/points.gpkg", None, false)?;
+        let sources = gpkg.list_data_sources(None, false, &Default::default(), Some(&bbox))?;
+        assert!(sources[0].sql.contains(r#""rtree_points_geom""#));
+        assert_eq!(sources[0].n_rows, 1);
+        assert_eq!(sources[0].bbox, Some(bbox));
+
+        // no spatial index; all the rows are scanned
+        let gpkg = super::Gpkg::new("./test/data/gpkg_geom_last/points.gpkg", None, false)?;
+        let sources = gpkg.list_data_sources(None, false, &Default::default(), Some(&bbox))?;
+        assert!(!sources[0].sql.contains("rtree"));
+        assert_eq!(sources[0].n_rows, 2);
+        assert_eq!(sources[0].bbox, Some(bbox));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(super::quote_identifier("points"), r#""points""#);
+        assert_eq!(super::quote_identifier(r#"the "val""#), r#""the ""val""""#);
+        assert_eq!(
+            super::quote_literal("my layer's data"),
+            "'my layer''s data'"
+        );
+    }
+
+    #[test]
+    fn test_quoted_names() -> Result<(), Box<dyn std::error::Error>> {
+        let layer = "my layer's data";
+        let gpkg = super::Gpkg::new("./test/data/gpkg_quoted_names/layers.gpkg", None, false)?;
+
+        let specs = gpkg.get_column_specs(layer)?;
+        let names: Vec<&str> = specs.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["geom", r#"the "val""#]);
+
+        let bbox = crate::types::Bbox {
+            xmin: 0.0,
+            ymin: 0.0,
+            xmax: 5.0,
+            ymax: 5.0,
+        };
+        let sources = gpkg.list_data_sources(None, false, &Default::default(), Some(&bbox))?;
+        assert_eq!(sources[0].layer_name, layer);
+        assert_eq!(sources[0].n_rows, 1);
+
+        let mut conn =
+            super::GpkgConnection::open("./test/data/gpkg_quoted_names/layers.gpkg", false)?;
+        let mut values: Vec<i64> = Vec::new();
+        conn.fetch_rows(&sources[0].sql, 0..1, |row, _| {
+            values.push(row.get(1)?);
+            Ok(true)
+        })?;
+        assert_eq!(values, vec![1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_follow_relations() -> Result<(), Box<dyn std::error::Error>> {
+        let path = "./test/data/gpkg_relations/relations.gpkg";
+        let mut gpkg = super::Gpkg::new(path, Some("points".to_string()), false)?;
+
+        let sources = gpkg.list_data_sources(None, false, &Default::default(), None)?;
+        let names: Vec<&str> = sources[0]
+            .column_specs
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["geom", "name"]);
+
+        // "tags" is skipped because it's one-to-many
+        gpkg.follow_relations = true;
+        let sources = gpkg.list_data_sources(None, false, &Default::default(), None)?;
+        let names: Vec<&str> = sources[0]
+            .column_specs
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["geom", "name", "population", "note"]);
+        assert_eq!(sources[0].n_rows, 3);
+
+        let mut conn = super::GpkgConnection::open(path, false)?;
+        let mut values: Vec<(String, Option<i64>, Option<String>)> = Vec::new();
+        conn.fetch_rows(&sources[0].sql, 0..3, |row, _| {
+            values.push((row.get(1)?, row.get(2)?, row.get(3)?));
+            Ok(true)
+        })?;
+        assert_eq!(
+            values,
+            vec![
+                ("a".to_string(), Some(100), Some("foo".to_string())),
+                ("b".to_string(), Some(200), Some("bar".to_string())),
+                ("c".to_string(), None, None),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_geometry_column() -> Result<(), Box<dyn std::error::Error>> {
+        let path = "./test/data/gpkg_two_geoms/places.gpkg";
+        let mut gpkg = super::Gpkg::new(path, None, false)?;
+        let names = |sources: &[super::GpkgDataSource]| -> Vec<String> {
+            sources[0]
+                .column_specs
+                .iter()
+                .map(|s| s.name.clone())
+                .collect()
+        };
+
+        // By default, the first geometry column is the geometry
+        let sources = gpkg.list_data_sources(None, false, &Default::default(), None)?;
+        assert_eq!(names(&sources), vec!["location", "area", "name"]);
+        assert_eq!(sources[0].geometry_type.as_deref(), Some("POINT"));
+
+        gpkg.geometry_column = Some("area".to_string());
+        let sources = gpkg.list_data_sources(None, false, &Default::default(), None)?;
+        assert_eq!(names(&sources), vec!["area", "location", "name"]);
+        assert_eq!(sources[0].geometry_type.as_deref(), Some("POLYGON"));
+
+        // not a geometry column
+        gpkg.geometry_column = Some("name".to_string());
+        assert!(gpkg
+            .list_data_sources(None, false, &Default::default(), None)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_geometry_types() -> Result<(), Box<dyn std::error::Error>> {
+        use super::GeometryTypeConstraint;
+
+        let gpkg = super::Gpkg::new(
+            "./test/data/gpkg_mixed_geometry_types/layers.gpkg",
+            None,
+            false,
+        )?;
+        let sources = gpkg.list_data_sources(None, false, &Default::default(), None)?;
+        let geometry_types: Vec<Option<&str>> =
+            sources.iter().map(|s| s.geometry_type.as_deref()).collect();
+        assert_eq!(geometry_types, vec![Some("POINT"), Some("POLYGON")]);
+
+        assert!(super::validate_geometry_types(&sources, &GeometryTypeConstraint::Same).is_err());
+        assert!(
+            super::validate_geometry_types(&sources[..1], &GeometryTypeConstraint::Same).is_ok()
+        );
+        assert!(super::validate_geometry_types(
+            &sources[..1],
+            &"point".parse::<GeometryTypeConstraint>()?
+        )
+        .is_ok());
+        assert!(super::validate_geometry_types(
+            &sources,
+            &"point".parse::<GeometryTypeConstraint>()?
+        )
+        .is_err());
+        assert!("pointz".parse::<GeometryTypeConstraint>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sqlite_uri() {
+        use std::path::Path;
+
+        assert_eq!(
+            super::sqlite_uri(Path::new("./data/points.gpkg"), "immutable=1"),
+            "file:./data/points.gpkg?immutable=1"
+        );
+        assert_eq!(
+            super::sqlite_uri(Path::new("./data/100%?#.gpkg"), "immutable=1"),
+            "file:./data/100%25%3f%23.gpkg?immutable=1"
+        );
+    }
+
+    #[test]
+    fn test_open_immutable() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("st_read_multi_test_open_immutable.gpkg");
+        std::fs::copy("./test/data/points.gpkg", &path)?;
+
+        // Some other process is writing to the file
+        let writer = rusqlite::Connection::open(&path)?;
+        writer.execute_batch("BEGIN EXCLUSIVE; DELETE FROM points WHERE fid = 1;")?;
+
+        let count = |conn: &super::GpkgConnection| {
+            conn.conn
+                .query_row("SELECT count(*) FROM points", [], |row| {
+                    row.get::<_, i64>(0)
+                })
+        };
+
+        // The file is locked
+        let conn = super::GpkgConnection::open(&path, false)?;
+        assert!(count(&conn).is_err());
+
+        // The immutable flag skips the lock, and the uncommitted change is not seen
+        let conn = super::GpkgConnection::open(&path, true)?;
+        assert_eq!(count(&conn)?, 2);
+
+        drop(writer);
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wal_mode() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("st_read_multi_test_wal_mode.gpkg");
+        std::fs::copy("./test/data/points.gpkg", &path)?;
+        assert!(!super::has_wal(&path));
+
+        // Some other process committed a change, but it's not checkpointed yet
+        let writer = rusqlite::Connection::open(&path)?;
+        writer.execute_batch(
+            "PRAGMA journal_mode = WAL; PRAGMA wal_autocheckpoint = 0; DELETE FROM points WHERE fid = 1;",
+        )?;
+        assert!(super::has_wal(&path));
+
+        let count = |conn: &super::GpkgConnection| {
+            conn.conn
+                .query_row("SELECT count(*) FROM points", [], |row| {
+                    row.get::<_, i64>(0)
+                })
+        };
+
+        // The change in the sidecar is seen
+        let conn = super::GpkgConnection::open(&path, false)?;
+        assert_eq!(count(&conn)?, 1);
+
+        // The sidecar is ignored
+        let conn = super::GpkgConnection::open(&path, true)?;
+        assert_eq!(count(&conn)?, 2);
+
+        assert_eq!("Ignore".parse::<super::WalMode>()?, super::WalMode::Ignore);
+        assert!("none".parse::<super::WalMode>().is_err());
+
+        drop(writer);
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema_from_first_many_layers() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("st_read_multi_test_many_layers.gpkg");
+        let _ = std::fs::remove_file(&path);
+
+        // The layers but the first declare a type that fails the inspection
+        let n_layers = 200;
+        let writer = rusqlite::Connection::open(&path)?;
+        writer.execute_batch(
+            "CREATE TABLE gpkg_contents (table_name TEXT PRIMARY KEY, data_type TEXT, last_change DATETIME, min_x DOUBLE, min_y DOUBLE, max_x DOUBLE, max_y DOUBLE);
+             CREATE TABLE gpkg_geometry_columns (table_name TEXT, column_name TEXT, geometry_type_name TEXT);",
+        )?;
+        for i in 0..n_layers {
+            let val_type = if i == 0 { "INTEGER" } else { "UNKNOWN" };
+            writer.execute_batch(&format!(
+                "CREATE TABLE layer{i} (fid INTEGER PRIMARY KEY, geom POINT, val {val_type});
+                 INSERT INTO layer{i} (val) VALUES ({i});
+                 INSERT INTO gpkg_contents (table_name, data_type) VALUES ('layer{i}', 'features');
+                 INSERT INTO gpkg_geometry_columns VALUES ('layer{i}', 'geom', 'POINT');"
+            ))?;
+        }
+        drop(writer);
+
+        let mut gpkg = super::Gpkg::new(&path, None, false)?;
+        assert!(gpkg
+            .list_data_sources(None, false, &Default::default(), None)
+            .is_err());
+
+        // Only the first layer is inspected
+        gpkg.schema_from_first = true;
+        let sources = gpkg.list_data_sources(None, false, &Default::default(), None)?;
+        assert_eq!(sources.len(), n_layers);
+        for source in &sources {
+            let names: Vec<&str> = source
+                .column_specs
+                .iter()
+                .map(|s| s.name.as_str())
+                .collect();
+            assert_eq!(names, vec!["geom", "val"]);
+        }
+
+        // The rest are read when they are scanned
+        let mut conn = super::GpkgConnection::open(&path, false)?;
+        let mut vals = Vec::new();
+        conn.fetch_rows(&sources[n_layers - 1].sql, 0..1, |row, _| {
+            vals.push(row.get::<_, i64>(1)?);
+            Ok(true)
+        })?;
+        assert_eq!(vals, vec![n_layers as i64 - 1]);
+
+        drop(gpkg);
+        drop(conn);
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_schema_different_column_order() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::utils::{sorted_by_name, SchemaValidator};
+
+        let specs = |path: &str| -> Result<Vec<ColumnSpec>, Box<dyn std::error::Error>> {
+            let gpkg = super::Gpkg::new(path, None, false)?;
+            let sources = gpkg.list_data_sources(None, false, &Default::default(), None)?;
+            Ok(sources[0].column_specs.clone())
+        };
+        let specs_a = specs("./test/data/gpkg_column_order/a.gpkg")?;
+        let specs_b = specs("./test/data/gpkg_column_order/b.gpkg")?;
+        let path = std::path::Path::new("./test/data/gpkg_column_order/b.gpkg");
+
+        // the columns are in the order of the table definition
+        let names: Vec<&str> = specs_b.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["geom", "val2", "val1"]);
+        let mut validator = SchemaValidator::new(false);
+        assert!(validator.validate(&specs_a, &specs_b, path).is_err());
+
+        validator.validate(&sorted_by_name(&specs_a), &sorted_by_name(&specs_b), path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_layer_contents() -> Result<(), Box<dyn std::error::Error>> {
+        let gpkg = super::Gpkg::new("./test/data/gpkg_geom_last/points.gpkg", None, false)?;
+        let sources = gpkg.list_data_sources(None, false, &Default::default(), None)?;
+        assert_eq!(
+            sources[0].contents,
+            super::LayerContents {
+                // 2025-01-01T00:00:00Z
+                last_change: Some(1_735_689_600_000_000),
+                min_x: Some(1.0),
+                min_y: Some(2.0),
+                max_x: Some(10.0),
+                max_y: Some(20.0),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gpkg_geometry_envelope() {
+        // POINT (1 2) without envelope
+        let mut b = vec![0x47, 0x50, 0x00, 0x01, 0xe6, 0x10, 0x00, 0x00];
+        let wkb = [1, 1, 0, 0, 0];
+        b.extend_from_slice(&wkb);
+        b.extend_from_slice(&1f64.to_le_bytes());
+        b.extend_from_slice(&2f64.to_le_bytes());
+        let point = b.clone();
+        assert_eq!(
+            super::gpkg_geometry_envelope(&b),
+            Some(crate::types::Bbox {
+                xmin: 1.0,
+                ymin: 2.0,
+                xmax: 1.0,
+                ymax: 2.0,
+            })
+        );
+
+        // the envelope in the header is used as is
+        let mut b = vec![0x47, 0x50, 0x00, 0b00000011, 0xe6, 0x10, 0x00, 0x00];
+        for v in [0.0f64, 10.0, -1.0, 3.0] {
+            b.extend_from_slice(&v.to_le_bytes());
+        }
+        b.extend_from_slice(&point[8..]);
+        assert_eq!(
+            super::gpkg_geometry_envelope(&b),
+            Some(crate::types::Bbox {
+                xmin: 0.0,
+                ymin: -1.0,
+                xmax: 10.0,
+                ymax: 3.0,
+            })
+        );
+
+        // empty geometry
+        let mut b = point;
+        b[3] |= 0b00010000;
+        assert_eq!(super::gpkg_geometry_envelope(&b), None);
+    }
+
+    #[test]
+    fn test_geojson_geometry_to_wkb() {
+        let wkb = super::geojson_geometry_to_wkb(
+            r#"{"type": "Point", "coordinates": [1.0, 2.0]}"#,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        // little-endian Point
+        assert_eq!(wkb[0], 0x01);
+        assert_eq!(u32::from_le_bytes(wkb[1..5].try_into().unwrap()), 1);
+        assert_eq!(f64::from_le_bytes(wkb[5..13].try_into().unwrap()), 1.0);
+        assert_eq!(f64::from_le_bytes(wkb[13..21].try_into().unwrap()), 2.0);
+
+        assert!(super::geojson_geometry_to_wkb("not a geojson", None, None, None, None).is_err());
+
+        let wkb = super::geojson_geometry_to_wkb(
+            r#"{"type": "Point", "coordinates": [1.26, 2.34]}"#,
+            None,
+            None,
+            Some(1),
+            None,
+        )
+        .unwrap();
+        assert_eq!(f64::from_le_bytes(wkb[5..13].try_into().unwrap()), 1.3);
+        assert_eq!(f64::from_le_bytes(wkb[13..21].try_into().unwrap()), 2.3);
+    }
+
     #[test]
     fn test_parse_date_to_unix_days() {
         // 1970-01-01 = day 0
-        assert_eq!(super::parse_date_to_unix_days("1970-01-01"), 0);
+        assert_eq!(super::parse_date_to_unix_days("1970-01-01"), Some(0));
         // 1970-01-02 = day 1
-        assert_eq!(super::parse_date_to_unix_days("1970-01-02"), 1);
+        assert_eq!(super::parse_date_to_unix_days("1970-01-02"), Some(1));
         // 1969-12-31 = day -1
-        assert_eq!(super::parse_date_to_unix_days("1969-12-31"), -1);
+        assert_eq!(super::parse_date_to_unix_days("1969-12-31"), Some(-1));
         // 2024-01-15 = 19737
-        assert_eq!(super::parse_date_to_unix_days("2024-01-15"), 19737);
+        assert_eq!(super::parse_date_to_unix_days("2024-01-15"), Some(19737));
+        // not a date
+        assert_eq!(super::parse_date_to_unix_days("2024/01/15"), None);
+        assert_eq!(super::parse_date_to_unix_days("2024-13-01"), None);
+        assert_eq!(super::parse_date_to_unix_days("n/a"), None);
     }
 
     #[test]
-    fn test_parse_datetime_to_unix_micros() {
+    fn test_parse_datetime_to_utc_micros() {
         // 1970-01-01T00:00:00Z = 0
         assert_eq!(
-            super::parse_datetime_to_unix_micros("1970-01-01T00:00:00Z"),
-            0
+            super::parse_datetime_to_utc_micros("1970-01-01T00:00:00Z"),
+            Some(0)
         );
         // 1970-01-01T00:00:01Z = 1_000_000
         assert_eq!(
-            super::parse_datetime_to_unix_micros("1970-01-01T00:00:01Z"),
-            1_000_000
+            super::parse_datetime_to_utc_micros("1970-01-01T00:00:01Z"),
+            Some(1_000_000)
         );
         // with milliseconds
         assert_eq!(
-            super::parse_datetime_to_unix_micros("1970-01-01T00:00:00.500Z"),
-            500_000
+            super::parse_datetime_to_utc_micros("1970-01-01T00:00:00.500Z"),
+            Some(500_000)
         );
         // space separator (common in SQLite)
         assert_eq!(
-            super::parse_datetime_to_unix_micros("2024-01-15 12:30:45Z"),
-            19737 * 86_400_000_000i64 + 12 * 3_600_000_000 + 30 * 60_000_000 + 45 * 1_000_000
+            super::parse_datetime_to_utc_micros("2024-01-15 12:30:45Z"),
+            Some(
+                19737 * 86_400_000_000i64 + 12 * 3_600_000_000 + 30 * 60_000_000 + 45 * 1_000_000
+            )
+        );
+        // offset
+        assert_eq!(
+            super::parse_datetime_to_utc_micros("1970-01-01T09:00:00+09:00"),
+            Some(0)
+        );
+        assert_eq!(
+            super::parse_datetime_to_utc_micros("1969-12-31 19:00:00-05:00"),
+            Some(0)
+        );
+        // no offset means UTC
+        assert_eq!(
+            super::parse_datetime_to_utc_micros("1970-01-01T00:00:01.000"),
+            Some(1_000_000)
         );
+        // date only
+        assert_eq!(
+            super::parse_datetime_to_utc_micros("1970-01-02"),
+            Some(86_400_000_000)
+        );
+        assert_eq!(super::parse_datetime_to_utc_micros("yesterday"), None);
+    }
+
+    #[test]
+    fn test_get_column_specs_datetime_fallback() -> Result<(), Box<dyn std::error::Error>> {
+        let gpkg = super::Gpkg::new("./test/data/gpkg_datetime_tz/datetimes.gpkg", None, false)?;
+        let specs = gpkg.get_column_specs("datetimes")?;
+
+        assert_eq!(&specs[0].name, "dt_utc");
+        assert_eq!(specs[0].column_type, ColumnType::TimestampTz);
+        assert_eq!(&specs[1].name, "dt_offset");
+        assert_eq!(specs[1].column_type, ColumnType::TimestampTz);
+        assert_eq!(&specs[2].name, "dt_date_only");
+        assert_eq!(specs[2].column_type, ColumnType::TimestampTz);
+        // unparsable values
+        assert_eq!(&specs[3].name, "dt_invalid");
+        assert_eq!(specs[3].column_type, ColumnType::Varchar);
+
+        Ok(())
     }
 }
@@ -2,11 +2,20 @@ extern crate duckdb;
 extern crate duckdb_loadable_macros;
 extern crate libduckdb_sys;
 
+mod blob;
+mod csv;
+mod diagnostics;
+mod esrijson;
 mod geojson;
 mod gpkg;
+mod mapinfo;
+mod s3;
+mod selftest;
 mod shapefile;
+mod twkb;
 mod types;
 mod utils;
+mod writer;
 
 use duckdb::{
     core::{DataChunkHandle, FlatVector, Inserter, LogicalTypeHandle, LogicalTypeId},
@@ -14,192 +23,2068 @@ use duckdb::{
     Connection, Result,
 };
 use duckdb_loadable_macros::duckdb_entrypoint_c_api;
+use geo::Centroid;
 use geojson::WkbConverter;
-use glob::glob;
 use libduckdb_sys::{duckdb_date, duckdb_timestamp};
 use std::{
+    borrow::Cow,
     error::Error,
-    path::PathBuf,
+    hash::{DefaultHasher, Hash, Hasher},
+    marker::PhantomData,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
 
 use crate::{
-    geojson::GeoJsonDataSource,
-    gpkg::{gpkg_geometry_to_wkb, Gpkg, GpkgDataSource},
-    shapefile::ShapefileDataSource,
+    blob::blob_to_wkb,
+    csv::{CsvDataSource, CsvLocation},
+    diagnostics::{log_parse, warn, BindWarnings, StReadMultiWarningsVTab},
+    esrijson::parse_esrijson,
+    geojson::{
+        coerce_to_bool, feature_intersects, is_wgs84, parse_numeric_string, GeoJsonDataSource,
+        OnTypeMismatch, PropertyLayout,
+    },
+    gpkg::{
+        geojson_geometry_to_wkb, geometry_intersects_bbox, geometry_intersects_filter,
+        gpkg_geometry_to_wkb, has_wal, hash_value_ref, validate_geometry_types,
+        GeometryColumnEncoding, GeometryTypeConstraint, Gpkg, GpkgDataSource, WalMode,
+    },
+    mapinfo::{parse_mid_date, parse_mid_datetime, MifDataSource},
+    selftest::StReadMultiSelftestVTab,
+    shapefile::{datetime_to_unix_micros, EncodingOption, Metadata, ShapefileDataSource},
+    twkb::{wkb_to_twkb, TWKB_PRECISION_RANGE},
     types::{
-        ColumnSpec, ColumnType, Cursor, GeoJsonBindData, GpkgBindData, ShapefileBindData,
-        StReadMultiBindData, StReadMultiInitData,
+        Bbox, CollectionMode, ColumnFilter, ColumnSpec, ColumnType, CoordinateOrder, CsvBindData,
+        Format, GeoJsonBindData, GeometryFormat, GpkgBindData, MifBindData, ShapefileBindData,
+        SpatialFilter, StReadMultiBindData, StReadMultiInitData, TargetGeometryType, Winding,
+    },
+    utils::{
+        apply_collection_mode_wkb, centroid_wkb, coerce_geometry_type_wkb, detect_format,
+        expand_glob, expand_tilde, file_mtime, filter_modified_after, has_non_finite_coords,
+        merge_schema, network_share_root, parse_list_value, read_manifest, relative_filename,
+        rewrite_wkb, round_coordinates, sorted_by_name, swap_xy, SchemaValidator,
     },
-    utils::{expand_tilde, is_geojson, is_gpkg, is_shp, validate_schema},
+    writer::{StWriteGeoJsonVTab, WriterConnection},
 };
 
-// The data chunk size. This can be obtained via libduckdb_sys::duckdb_vector_size(),
-// but use a fixed value here.
-pub(crate) const VECTOR_SIZE: usize = 2048;
+// The data chunk size. This can be obtained via libduckdb_sys::duckdb_vector_size(),
+// but use a fixed value here.
+pub(crate) const VECTOR_SIZE: usize = 2048;
+
+// All the formats return the geometry as the first column, followed by the
+// attribute columns and the columns to track the source (e.g. `.filename`).
+const COLUMN_NAME_GEOMETRY: &str = "geometry";
+const COLUMN_NAME_FILENAME: &str = ".filename";
+const COLUMN_NAME_CRS: &str = ".crs";
+const COLUMN_NAME_LAYER: &str = ".layer";
+const COLUMN_NAME_ENCODING: &str = ".encoding";
+const COLUMN_NAME_TITLE: &str = ".title";
+const COLUMN_NAME_ABSTRACT: &str = ".abstract";
+const COLUMN_NAME_IS_DELETED: &str = ".is_deleted";
+const COLUMN_NAME_RAW_BYTES: &str = ".raw_bytes";
+const COLUMN_NAME_MTIME: &str = ".mtime";
+const COLUMN_NAME_LAST_CHANGE: &str = ".last_change";
+const COLUMN_NAMES_EXTENT: [&str; 4] = [".min_x", ".min_y", ".max_x", ".max_y"];
+// The STRUCT column to bundle the attribute columns (only when `struct_attributes`
+// is specified)
+const COLUMN_NAME_ATTRIBUTES: &str = "attributes";
+
+// Table functions that skip the format detection and read the files as the specified format.
+const FUNCTION_NAME_GEOJSON: &str = "st_read_geojson";
+const FUNCTION_NAME_GPKG: &str = "st_read_gpkg";
+const FUNCTION_NAME_SHAPEFILE: &str = "st_read_shp";
+
+const FUNCTION_NAME_WRITE_GEOJSON: &str = "st_write_geojson";
+
+// The warnings emitted during the last read
+const FUNCTION_NAME_WARNINGS: &str = "st_read_multi_warnings";
+
+// Reads the fixtures of each format to check the build
+const FUNCTION_NAME_SELFTEST: &str = "st_read_multi_selftest";
+
+/// Determines which reader is used for the input files.
+trait FormatSelector {
+    /// `None` means the format is detected from the file extensions.
+    const FORMAT: Option<Format>;
+}
+
+struct AutoDetect;
+struct ForceGeoJson;
+struct ForceGpkg;
+struct ForceShapefile;
+
+impl FormatSelector for AutoDetect {
+    const FORMAT: Option<Format> = None;
+}
+
+impl FormatSelector for ForceGeoJson {
+    const FORMAT: Option<Format> = Some(Format::GeoJson);
+}
+
+impl FormatSelector for ForceGpkg {
+    const FORMAT: Option<Format> = Some(Format::Gpkg);
+}
+
+impl FormatSelector for ForceShapefile {
+    const FORMAT: Option<Format> = Some(Format::Shapefile);
+}
+
+struct StReadMultiVTab<F: FormatSelector> {
+    _format: PhantomData<F>,
+}
+
+// The number of decimal places to round the coordinates. This is common to all the formats.
+fn get_coordinate_precision(bind: &BindInfo) -> Result<Option<i32>, Box<dyn std::error::Error>> {
+    match bind.get_named_parameter("coordinate_precision") {
+        Some(v) => {
+            let n = v.to_int64();
+            if !(0..=15).contains(&n) {
+                return Err(format!("'coordinate_precision' must be between 0 and 15: {n}").into());
+            }
+            Ok(Some(n as i32))
+        }
+        None => Ok(None),
+    }
+}
+
+// The number of rows emitted per func() call. A DataChunk cannot hold more than
+// VECTOR_SIZE rows, so a larger value is clamped. This is common to all the formats.
+fn get_chunk_size(bind: &BindInfo) -> Result<usize, Box<dyn std::error::Error>> {
+    match bind.get_named_parameter("chunk_size") {
+        Some(v) => {
+            let n = v.to_int64();
+            if n < 1 {
+                return Err(format!("'chunk_size' must be a positive number: {n}").into());
+            }
+            if n as usize > VECTOR_SIZE {
+                warn!("'chunk_size' is clamped to {VECTOR_SIZE}: {n}");
+                return Ok(VECTOR_SIZE);
+            }
+            Ok(n as usize)
+        }
+        None => Ok(VECTOR_SIZE),
+    }
+}
+
+// If true, the rows identical to some row emitted before are skipped. The source
+// columns (e.g. `.filename`) are not compared. This is common to all the formats.
+fn get_distinct(bind: &BindInfo) -> bool {
+    bind.get_named_parameter("distinct")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false)
+}
+
+// If true, the anomalies that are usually tolerated (a warning, a NULL geometry, a
+// type mismatch, a non-finite coordinate) are errors. This is common to all the
+// formats.
+fn get_strict(bind: &BindInfo) -> bool {
+    bind.get_named_parameter("strict")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false)
+}
+
+// The rows whose geometries don't intersect this geometry are skipped. This is
+// common to all the formats.
+fn get_spatial_filter(
+    bind: &BindInfo,
+) -> Result<Option<SpatialFilter>, Box<dyn std::error::Error>> {
+    bind.get_named_parameter("filter_wkt")
+        .map(|v| v.to_string().parse())
+        .transpose()
+}
+
+// If true, Z and M coordinates are dropped so that the output is always 2D. GeoJSON
+// input is always read as 2D, so this is only for GeoPackage and Shapefile.
+fn get_force_2d(bind: &BindInfo) -> bool {
+    bind.get_named_parameter("force_2d")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false)
+}
+
+// If true, the files of the next data source are read in a background thread while
+// the current one is scanned so that they are in the OS page cache by the time they
+// are needed. GeoJSON, CSV, and MIF files are read at bind time, so this is only
+// for GeoPackage and Shapefile.
+fn get_prefetch(bind: &BindInfo) -> bool {
+    bind.get_named_parameter("prefetch")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false)
+}
+
+// If true, the geometry is replaced with its centroid. An empty geometry becomes
+// NULL. For CSV input, this gives the center of the H3 cells or the geohashes.
+fn get_centroid(bind: &BindInfo) -> bool {
+    bind.get_named_parameter("centroid")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false)
+}
+
+// The encoding of the geometry column. `twkb_precision` is the number of decimal
+// places of the coordinates in TWKB (7 by default). This is common to all the
+// formats.
+fn get_geometry_format(bind: &BindInfo) -> Result<GeometryFormat, Box<dyn std::error::Error>> {
+    let twkb_precision = match bind.get_named_parameter("twkb_precision") {
+        Some(v) => {
+            let n = v.to_int64();
+            if !TWKB_PRECISION_RANGE.contains(&n) {
+                return Err(format!("'twkb_precision' must be between -7 and 7: {n}").into());
+            }
+            Some(n as i32)
+        }
+        None => None,
+    };
+
+    let format = bind
+        .get_named_parameter("geometry_format")
+        .map(|v| v.to_string().to_lowercase());
+    match (format.as_deref(), twkb_precision) {
+        (None | Some("wkb"), None) => Ok(GeometryFormat::Wkb),
+        (None | Some("wkb"), Some(_)) => {
+            Err("'twkb_precision' can be used only with geometry_format='twkb'".into())
+        }
+        (Some("twkb"), precision) => Ok(GeometryFormat::Twkb(precision.unwrap_or(7))),
+        (Some(s), _) => Err(format!(
+            "Unknown value in 'geometry_format' option: {s} (must be 'wkb' or 'twkb')"
+        )
+        .into()),
+    }
+}
+
+// Reorient the polygon rings so that the exterior rings are clockwise (`cw`) or
+// counterclockwise (`ccw` or `rfc7946`). This is ignored for CSV input, whose
+// polygons (H3 cells or geohashes) are always counterclockwise.
+fn get_winding(bind: &BindInfo) -> Result<Option<Winding>, Box<dyn std::error::Error>> {
+    bind.get_named_parameter("winding")
+        .map(|v| v.to_string().parse())
+        .transpose()
+}
+
+// Buffer the geometries by the distance (in the unit of the coordinates). A negative
+// distance shrinks the polygons. This is ignored for CSV input.
+fn get_buffer_distance(bind: &BindInfo) -> Result<Option<f64>, Box<dyn std::error::Error>> {
+    match bind.get_named_parameter("buffer_distance") {
+        Some(v) => {
+            let n = v.to_string().parse::<f64>().unwrap_or(f64::NAN);
+            if !n.is_finite() {
+                return Err(format!("'buffer_distance' must be a number: {v}").into());
+            }
+            Ok(Some(n))
+        }
+        None => Ok(None),
+    }
+}
+
+// Densify the geometries so that no segment is longer than the length (in the unit
+// of the coordinates). This is ignored for CSV input.
+fn get_max_segment_length(bind: &BindInfo) -> Result<Option<f64>, Box<dyn std::error::Error>> {
+    match bind.get_named_parameter("densify_max_segment_length") {
+        Some(v) => {
+            let n = v.to_string().parse::<f64>().unwrap_or(f64::NAN);
+            if !(n.is_finite() && n > 0.0) {
+                return Err(
+                    format!("'densify_max_segment_length' must be a positive number: {v}").into(),
+                );
+            }
+            Ok(Some(n))
+        }
+        None => Ok(None),
+    }
+}
+
+// Swap the axes if the input is in latitude/longitude order. Only for GeoJSON and
+// CSV; the other formats declare the axis order by their CRS.
+fn get_coordinate_order(bind: &BindInfo) -> Result<CoordinateOrder, Box<dyn std::error::Error>> {
+    bind.get_named_parameter("coordinate_order")
+        .map(|v| v.to_string().parse())
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+// How to handle GeometryCollections. Only GeoJSON and GeoPackage can contain
+// GeometryCollections, so this doesn't change anything for the other formats.
+fn get_collection_mode(bind: &BindInfo) -> Result<CollectionMode, Box<dyn std::error::Error>> {
+    match bind.get_named_parameter("collection_mode") {
+        Some(v) => v.to_string().parse(),
+        None => Ok(CollectionMode::Keep),
+    }
+}
+
+// The geometry type all the geometries are coerced to, and how to handle the ones
+// that cannot be coerced. This is for GeoJSON, EsriJSON, and Shapefile.
+fn get_geometry_type(
+    bind: &BindInfo,
+) -> Result<(Option<TargetGeometryType>, OnTypeMismatch), Box<dyn std::error::Error>> {
+    let geometry_type = match bind.get_named_parameter("geometry_type") {
+        Some(v) => Some(v.to_string().parse::<TargetGeometryType>()?),
+        None => None,
+    };
+    let on_mismatch = match bind.get_named_parameter("on_geometry_type_mismatch") {
+        Some(v) => match v.to_string().to_lowercase().as_str() {
+            "null" => OnTypeMismatch::Null,
+            "error" => OnTypeMismatch::Error,
+            s => {
+                return Err(format!(
+                    "Unknown value in 'on_geometry_type_mismatch' option: {s} (must be 'null' or 'error')"
+                )
+                .into())
+            }
+        },
+        None => OnTypeMismatch::Error,
+    };
+
+    match geometry_type {
+        // The centroid is always a point
+        Some(_) if get_centroid(bind) => {
+            return Err("'geometry_type' cannot be used with 'centroid'".into());
+        }
+        None if bind
+            .get_named_parameter("on_geometry_type_mismatch")
+            .is_some() =>
+        {
+            warn!("Named parameter 'on_geometry_type_mismatch' is ignored because 'geometry_type' is not specified");
+        }
+        _ => {}
+    }
+
+    Ok((geometry_type, on_mismatch))
+}
+
+// If true, the schema of the first file is used for all the files without
+// validation. This is common to all the formats.
+fn get_schema_from_first(bind: &BindInfo) -> bool {
+    bind.get_named_parameter("schema_from_first")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false)
+}
+
+// If true, the schema mismatches of all the files are reported at once instead of
+// failing at the first one. This is common to all the formats.
+fn get_schema_validator(bind: &BindInfo) -> SchemaValidator {
+    let report_all = bind
+        .get_named_parameter("report_all_mismatches")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false);
+    SchemaValidator::new(report_all)
+}
+
+// The pairs of the named parameters that cannot be used together. Otherwise, one of
+// them would be silently ignored.
+const CONFLICTING_PARAMETERS: &[(&str, &str)] = &[
+    ("union_by_name", "schema_from_first"),
+    ("union_by_name", "report_all_mismatches"),
+    ("schema_from_first", "report_all_mismatches"),
+    ("geometry_only", "include_columns"),
+    ("geometry_only", "exclude_columns"),
+    ("geometry_only", "include_crs"),
+    ("geometry_only", "include_mtime"),
+    ("geometry_only", "include_layer_metadata"),
+    ("geometry_only", "include_metadata"),
+    ("geometry_only", "include_encoding"),
+    ("geometry_only", "include_deleted"),
+    ("geometry_only", "include_foreign_members"),
+    ("geometry_only", "raw_bytes_for"),
+    ("geometry_only", "struct_attributes"),
+];
+
+// Check the combination of the named parameters before reading any file. A
+// boolean parameter set to false is the same as not specifying it.
+fn validate_parameters(bind: &BindInfo) -> Result<(), Box<dyn std::error::Error>> {
+    let is_specified = |name: &str| {
+        bind.get_named_parameter(name)
+            .is_some_and(|v| v.to_string() != "false")
+    };
+    for (a, b) in CONFLICTING_PARAMETERS {
+        if is_specified(a) && is_specified(b) {
+            return Err(format!("'{a}' and '{b}' cannot be used together").into());
+        }
+    }
+    Ok(())
+}
+
+// If true, only the geometry column is returned; no attribute columns, nor the
+// columns to track the source. This is common to all the formats.
+fn get_geometry_only(bind: &BindInfo) -> bool {
+    bind.get_named_parameter("geometry_only")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false)
+}
+
+// If true, the geometry column comes last instead of first. This is common to all
+// the formats.
+fn get_geometry_last(bind: &BindInfo) -> Result<bool, Box<dyn std::error::Error>> {
+    match bind.get_named_parameter("geometry_position") {
+        Some(v) => match v.to_string().as_str() {
+            "first" => Ok(false),
+            "last" => Ok(true),
+            other => Err(format!(
+                "Unknown value in 'geometry_position' option: {other} (must be 'first' or 'last')"
+            )
+            .into()),
+        },
+        None => Ok(false),
+    }
+}
+
+// The attribute columns to read. This is common to all the formats.
+fn get_column_filter(bind: &BindInfo) -> ColumnFilter {
+    ColumnFilter {
+        include: bind
+            .get_named_parameter("include_columns")
+            .map(|v| parse_list_value(&v.to_string())),
+        exclude: bind
+            .get_named_parameter("exclude_columns")
+            .map(|v| parse_list_value(&v.to_string()))
+            .unwrap_or_default(),
+    }
+}
+
+// The bbox to filter the rows, specified as [xmin, ymin, xmax, ymax]. Only
+// GeoPackage supports this.
+fn get_bbox(bind: &BindInfo) -> Result<Option<Bbox>, Box<dyn std::error::Error>> {
+    let Some(v) = bind.get_named_parameter("bbox") else {
+        return Ok(None);
+    };
+
+    let s = v.to_string();
+    let values = parse_list_value(&s)
+        .iter()
+        .map(|v| v.parse::<f64>().ok().filter(|v| v.is_finite()))
+        .collect::<Option<Vec<f64>>>();
+    match values.as_deref() {
+        Some(&[xmin, ymin, xmax, ymax]) if xmin <= xmax && ymin <= ymax => Ok(Some(Bbox {
+            xmin,
+            ymin,
+            xmax,
+            ymax,
+        })),
+        _ => Err(format!("'bbox' must be [xmin, ymin, xmax, ymax]: {s}").into()),
+    }
+}
+
+// If true, the attribute columns are bundled into one STRUCT column. This is
+// common to all the formats.
+fn get_struct_attributes(bind: &BindInfo) -> bool {
+    bind.get_named_parameter("struct_attributes")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false)
+}
+
+// Add the attribute columns, or the STRUCT column that contains them. Note that a
+// STRUCT must have at least one field, so nothing is added if there's no attribute.
+fn add_attribute_columns<'a, I>(bind: &BindInfo, specs: I, struct_attributes: bool)
+where
+    I: Iterator<Item = &'a ColumnSpec>,
+{
+    if !struct_attributes {
+        for spec in specs {
+            bind.add_result_column(&spec.name, spec.column_type.into());
+        }
+        return;
+    }
+
+    let fields: Vec<(&str, LogicalTypeHandle)> = specs
+        .map(|spec| (spec.name.as_str(), spec.column_type.into()))
+        .collect();
+    if !fields.is_empty() {
+        bind.add_result_column(
+            COLUMN_NAME_ATTRIBUTES,
+            LogicalTypeHandle::struct_type(&fields),
+        );
+    }
+}
+
+// The number of the output columns taken by `n` attributes.
+fn n_attribute_columns(n: usize, struct_attributes: bool) -> usize {
+    if struct_attributes {
+        (n > 0) as usize
+    } else {
+        n
+    }
+}
+
+// Returns the vectors to write `n` attributes to. `idx` is the (logical) index of
+// the first attribute column, or the STRUCT column.
+fn attribute_vectors(
+    output: &DataChunkHandle,
+    idx: usize,
+    n: usize,
+    struct_attributes: bool,
+    col: impl Fn(usize) -> usize,
+) -> Vec<FlatVector> {
+    if !struct_attributes {
+        return (0..n).map(|i| output.flat_vector(col(idx + i))).collect();
+    }
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let struct_vector = output.struct_vector(col(idx));
+    (0..n)
+        .map(|i| struct_vector.child(i, VECTOR_SIZE))
+        .collect()
+}
+
+// `offset` rows are skipped and at most `limit` rows are emitted, counting across
+// all the data sources. This is common to all the formats.
+fn get_offset_limit(bind: &BindInfo) -> Result<(usize, Option<usize>), Box<dyn std::error::Error>> {
+    let offset = match bind.get_named_parameter("offset") {
+        Some(v) => {
+            let n = v.to_int64();
+            if n < 0 {
+                return Err(format!("'offset' must not be negative: {n}").into());
+            }
+            n as usize
+        }
+        None => 0,
+    };
+    let limit = match bind.get_named_parameter("limit") {
+        Some(v) => {
+            let n = v.to_int64();
+            if n < 0 {
+                return Err(format!("'limit' must not be negative: {n}").into());
+            }
+            Some(n as usize)
+        }
+        None => None,
+    };
+    Ok((offset, limit))
+}
+
+// In func(), the columns are indexed as if the geometry column comes first. This
+// maps the index to the actual one.
+fn column_index(i: usize, n_columns: usize, geometry_last: bool) -> usize {
+    match (geometry_last, i) {
+        (false, _) => i,
+        (true, 0) => n_columns - 1,
+        (true, _) => i - 1,
+    }
+}
+
+// The base directory to emit `.filename` relative to
+fn get_filename_relative_to(bind: &BindInfo) -> Option<PathBuf> {
+    bind.get_named_parameter("filename_relative_to")
+        .map(|v| PathBuf::from(expand_tilde(&v.to_string())))
+}
+
+// If true, the modification time of the file is added as `.mtime` column. This is
+// common to all the formats.
+fn get_include_mtime(bind: &BindInfo) -> bool {
+    bind.get_named_parameter("include_mtime")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false)
+}
+
+// Write a TIMESTAMP value in microseconds since Unix epoch (e.g. `.mtime`, which
+// is always the last column, or the one before the geometry column when
+// `geometry_position` is 'last').
+fn write_timestamp(vector: &mut FlatVector, row_idx: usize, micros: Option<i64>) {
+    match micros {
+        Some(micros) => unsafe {
+            vector.as_mut_slice::<duckdb_timestamp>()[row_idx] = duckdb_timestamp { micros };
+        },
+        None => vector.set_null(row_idx),
+    }
+}
+
+// Write the WKB to the geometry column, encoding it as TWKB if it's requested.
+fn insert_geometry(
+    vector: &FlatVector,
+    row_idx: usize,
+    wkb: &[u8],
+    format: GeometryFormat,
+    strict: bool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if strict && has_non_finite_coords(wkb)? {
+        return Err("Found a non-finite coordinate (strict mode)".into());
+    }
+    match format {
+        GeometryFormat::Wkb => vector.insert(row_idx, wkb),
+        GeometryFormat::Twkb(precision) => {
+            vector.insert(row_idx, wkb_to_twkb(wkb, precision)?.as_slice())
+        }
+    }
+    Ok(())
+}
+
+// Coerce the geometry by `geometry_type` option. A geometry that cannot be coerced
+// is NULL, or an error with `on_geometry_type_mismatch='error'`.
+fn coerce_geometry<'a>(
+    wkb: Option<&'a [u8]>,
+    geometry_type: Option<TargetGeometryType>,
+    on_mismatch: OnTypeMismatch,
+    filename: &str,
+) -> Result<Option<Cow<'a, [u8]>>, Box<dyn Error>> {
+    let (Some(wkb), Some(geometry_type)) = (wkb, geometry_type) else {
+        return Ok(wkb.map(Cow::Borrowed));
+    };
+    match coerce_geometry_type_wkb(wkb, geometry_type).map_err(|e| -> Box<dyn Error> { e })? {
+        Some(wkb) => Ok(Some(wkb)),
+        None if on_mismatch == OnTypeMismatch::Error => Err(format!(
+            "Found a geometry that cannot be coerced to {geometry_type} in {} (cf. 'geometry_type' option)",
+            filename.replace('\\', "/")
+        )
+        .into()),
+        None => Ok(None),
+    }
+}
+
+// Write NULL to the geometry column. In strict mode, a NULL geometry is an error.
+fn set_null_geometry(
+    vector: &mut FlatVector,
+    row_idx: usize,
+    strict: bool,
+    filename: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if strict {
+        return Err(format!(
+            "Found a NULL geometry in {} (strict mode)",
+            filename.replace('\\', "/")
+        )
+        .into());
+    }
+    vector.set_null(row_idx);
+    Ok(())
+}
+
+// Set NULL for a value that cannot be parsed as the column type, which is an error
+// in strict mode.
+fn set_null_unparsable(
+    vector: &mut FlatVector,
+    row_idx: usize,
+    strict: bool,
+    spec: &ColumnSpec,
+    val: &str,
+    filename: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if strict {
+        return Err(format!(
+            "Cannot parse '{val}' as {:?} in column '{}' of {} (strict mode)",
+            spec.column_type,
+            spec.name,
+            filename.replace('\\', "/")
+        )
+        .into());
+    }
+    vector.set_null(row_idx);
+    Ok(())
+}
+
+// ==================== //
+//     GeoJSON          //
+// ==================== //
+
+// EsriJSON is converted to GeoJSON features while parsing, so it's read here too.
+fn bind_geojson(
+    bind: &BindInfo,
+    paths: Vec<PathBuf>,
+    esrijson: bool,
+) -> Result<StReadMultiBindData, Box<dyn std::error::Error>> {
+    let encoding_option = bind.get_named_parameter("encoding").map(|v| v.to_string());
+    let preserve_order = bind
+        .get_named_parameter("preserve_order")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false);
+    let include_foreign_members = bind
+        .get_named_parameter("include_foreign_members")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false);
+    let include_crs = bind
+        .get_named_parameter("include_crs")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false);
+    let coerce_numeric_strings = bind
+        .get_named_parameter("coerce_numeric_strings")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false);
+    let numbers_as_double = bind
+        .get_named_parameter("numbers_as_double")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false);
+    let boolean_columns: Vec<String> = bind
+        .get_named_parameter("boolean_columns")
+        .map(|v| parse_list_value(&v.to_string()))
+        .unwrap_or_default();
+    // the properties that hold GeoJSON geometry objects, which are read as WKB
+    let geometry_properties: Vec<String> = bind
+        .get_named_parameter("geometry_properties")
+        .map(|v| parse_list_value(&v.to_string()))
+        .unwrap_or_default();
+    // Some loosely structured JSON puts the geometry under a different name than
+    // "geometry" (e.g. "geom")
+    let geometry_member = bind
+        .get_named_parameter("geometry_member")
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "geometry".to_string());
+    // Some APIs wrap the FeatureCollection in a larger JSON document
+    let json_pointer = bind
+        .get_named_parameter("json_pointer")
+        .map(|v| v.to_string())
+        .unwrap_or_default();
+    if !json_pointer.is_empty() && !json_pointer.starts_with('/') {
+        return Err(
+            format!("'json_pointer' must be empty or start with '/': {json_pointer}").into(),
+        );
+    }
+    let strict = get_strict(bind);
+    let spatial_filter = get_spatial_filter(bind)?;
+    let on_type_mismatch: OnTypeMismatch = match bind.get_named_parameter("on_type_mismatch") {
+        Some(v) => v.to_string().parse()?,
+        None if strict => OnTypeMismatch::Error,
+        None => OnTypeMismatch::Null,
+    };
+    if strict && on_type_mismatch == OnTypeMismatch::Null {
+        return Err("'on_type_mismatch' = 'null' cannot be used with 'strict'".into());
+    }
+    let coordinate_order = get_coordinate_order(bind)?;
+    let coordinate_precision = get_coordinate_precision(bind)?;
+    let centroid = get_centroid(bind);
+    let geometry_format = get_geometry_format(bind)?;
+    let winding = get_winding(bind)?;
+    let buffer_distance = get_buffer_distance(bind)?;
+    let max_segment_length = get_max_segment_length(bind)?;
+    let collection_mode = get_collection_mode(bind)?;
+    let (geometry_type, on_geometry_type_mismatch) = get_geometry_type(bind)?;
+    let chunk_size = get_chunk_size(bind)?;
+    let distinct = get_distinct(bind);
+    let schema_from_first = get_schema_from_first(bind);
+    let mut schema_validator = get_schema_validator(bind);
+    let geometry_only = get_geometry_only(bind);
+    let geometry_last = get_geometry_last(bind)?;
+    let struct_attributes = get_struct_attributes(bind);
+    let (offset, limit) = get_offset_limit(bind)?;
+    let include_mtime = get_include_mtime(bind) && !geometry_only;
+    let filename_relative_to = get_filename_relative_to(bind);
+    let column_filter = get_column_filter(bind);
+
+    if encoding_option.is_some() {
+        warn!("Named parameter 'encoding' is ignored for GeoJSON and EsriJSON input");
+    }
+
+    let include_crs = include_crs && !geometry_only;
+
+    let mut sources: Vec<GeoJsonDataSource> = Vec::new();
+    let mut column_specs: Option<Vec<ColumnSpec>> = None;
+    let mut foreign_member_specs: Option<Vec<ColumnSpec>> = None;
+
+    for path in paths {
+        let (mut data_sources, mut column_specs_local) = log_parse(&path, || {
+            if esrijson {
+                parse_esrijson(&path, preserve_order)
+            } else {
+                GeoJsonDataSource::parse_and_split(
+                    &path,
+                    preserve_order,
+                    &geometry_properties,
+                    &geometry_member,
+                    &json_pointer,
+                )
+            }
+        })?;
+
+        if coerce_numeric_strings {
+            data_sources[0].coerce_numeric_strings(&mut column_specs_local);
+        }
+
+        let mut foreign_member_specs_local = if include_foreign_members {
+            data_sources[0].foreign_member_specs(preserve_order)?
+        } else {
+            Vec::new()
+        };
+
+        // Read all the numbers as DOUBLE regardless of the integer detection
+        if numbers_as_double {
+            column_specs_local
+                .iter_mut()
+                .chain(foreign_member_specs_local.iter_mut())
+                .filter(|s| matches!(s.column_type, ColumnType::Integer | ColumnType::BigInt))
+                .for_each(|s| s.column_type = ColumnType::Double);
+        }
+
+        // The coordinates are returned as they are, so the user needs to know the
+        // CRS is not the default one.
+        if !include_crs {
+            if let Some(crs) = data_sources[0].crs.as_deref() {
+                if !is_wgs84(crs) {
+                    warn!(
+                        "{} declares non-WGS84 CRS '{crs}'. Consider specifying include_crs := true",
+                        path.to_string_lossy().replace('\\', "/")
+                    );
+                }
+            }
+        }
+
+        if include_mtime {
+            let mtime = file_mtime(&path);
+            data_sources.iter_mut().for_each(|s| s.mtime = mtime);
+        }
+
+        sources.append(&mut data_sources);
+
+        if let (Some(existing_specs), Some(existing_foreign_specs)) =
+            (&column_specs, &foreign_member_specs)
+        {
+            // check if the schema matches. With `preserve_order`, the columns are
+            // in the order of the file, so compare them in the order of the names.
+            // The values are looked up by name in func().
+            if !schema_from_first {
+                schema_validator.validate(
+                    &sorted_by_name(existing_specs),
+                    &sorted_by_name(&column_specs_local),
+                    &path,
+                )?;
+                schema_validator.validate(
+                    &sorted_by_name(existing_foreign_specs),
+                    &sorted_by_name(&foreign_member_specs_local),
+                    &path,
+                )?;
+            }
+        } else {
+            // if it's the first file, use the spec as the base.
+            let _ = column_specs.insert(column_specs_local);
+            let _ = foreign_member_specs.insert(foreign_member_specs_local);
+        }
+    }
+    schema_validator.finish()?;
+
+    let (mut column_specs, mut foreign_member_specs) = if geometry_only {
+        (Vec::new(), Vec::new())
+    } else {
+        (column_specs.unwrap(), foreign_member_specs.unwrap())
+    };
+    column_specs.retain(|s| column_filter.keeps(&s.name));
+    foreign_member_specs.retain(|s| column_filter.keeps(&s.name));
+    if !geometry_only {
+        column_filter.validate(column_specs.iter().chain(foreign_member_specs.iter()))?;
+    }
+
+    // The values of these columns are coerced to booleans in func()
+    for name in &boolean_columns {
+        match column_specs.iter_mut().find(|s| s.name == *name) {
+            Some(spec) => spec.column_type = ColumnType::Boolean,
+            None if !geometry_only => {
+                warn!("Column '{name}' in 'boolean_columns' doesn't exist")
+            }
+            None => {}
+        }
+    }
+    for name in &geometry_properties {
+        if !geometry_only && !column_specs.iter().any(|s| s.name == *name) {
+            warn!("Column '{name}' in 'geometry_properties' doesn't exist");
+        }
+    }
+
+    if let Some(spec) = foreign_member_specs
+        .iter()
+        .find(|spec| column_specs.iter().any(|s| s.name == spec.name))
+    {
+        return Err(format!(
+            "Foreign member '{}' has the same name as a property",
+            spec.name
+        )
+        .into());
+    }
+
+    if !geometry_last {
+        bind.add_result_column(COLUMN_NAME_GEOMETRY, LogicalTypeId::Blob.into());
+    }
+    add_attribute_columns(
+        bind,
+        column_specs.iter().chain(foreign_member_specs.iter()),
+        struct_attributes,
+    );
+
+    // filename column to track source file
+    if !geometry_only {
+        bind.add_result_column(COLUMN_NAME_FILENAME, LogicalTypeId::Varchar.into());
+    }
+
+    if include_crs {
+        bind.add_result_column(COLUMN_NAME_CRS, LogicalTypeId::Varchar.into());
+    }
+
+    if include_mtime {
+        bind.add_result_column(COLUMN_NAME_MTIME, LogicalTypeId::Timestamp.into());
+    }
+
+    if geometry_last {
+        bind.add_result_column(COLUMN_NAME_GEOMETRY, LogicalTypeId::Blob.into());
+    }
+
+    let property_layouts = sources
+        .iter()
+        .map(|s| PropertyLayout::new(s, &column_specs))
+        .collect();
+
+    Ok(GeoJsonBindData {
+        sources,
+        column_specs,
+        property_layouts,
+        foreign_member_specs,
+        coordinate_order,
+        coordinate_precision,
+        centroid,
+        geometry_format,
+        winding,
+        buffer_distance,
+        max_segment_length,
+        collection_mode,
+        geometry_type,
+        on_geometry_type_mismatch,
+        chunk_size,
+        distinct,
+        strict,
+        spatial_filter,
+        geometry_only,
+        include_mtime,
+        filename_relative_to,
+        geometry_last,
+        struct_attributes,
+        offset,
+        limit,
+        include_crs,
+        on_type_mismatch,
+    }
+    .into())
+}
+
+// Returns false if the value cannot be converted to the column type, in which case
+// NULL is written instead. This can happen because the type is inferred only from
+// the first features (and from the first file with `schema_from_first`).
+fn write_geojson_value(
+    vector: &mut FlatVector,
+    row_idx: usize,
+    column_type: ColumnType,
+    val: Option<&serde_json::Value>,
+    bind_data: &GeoJsonBindData,
+) -> bool {
+    use serde_json::Value;
+
+    match (column_type, val) {
+        // Handle NULL or missing values
+        (_, None | Some(Value::Null)) => vector.set_null(row_idx),
+        // Varchar needs insert()
+        (ColumnType::Varchar, Some(Value::String(s))) => vector.insert(row_idx, s.as_str()),
+        // numbers, booleans, and nested values (only allowed for foreign members) are
+        // stored as JSON
+        (ColumnType::Varchar, Some(v)) => vector.insert(row_idx, v.to_string().as_str()),
+        // 0/1 and "true"/"false" are also accepted (cf. `boolean_columns`)
+        (ColumnType::Boolean, Some(v)) => match coerce_to_bool(v) {
+            Some(b) => unsafe { vector.as_mut_slice()[row_idx] = b },
+            None => {
+                vector.set_null(row_idx);
+                return false;
+            }
+        },
+        (ColumnType::BigInt, Some(v)) => match v.as_i64() {
+            Some(n) => unsafe { vector.as_mut_slice()[row_idx] = n },
+            None => {
+                vector.set_null(row_idx);
+                return false;
+            }
+        },
+        (ColumnType::Double, Some(v)) => {
+            let n = match v {
+                Value::Number(n) => n.as_f64(),
+                // quoted numbers (cf. `coerce_numeric_strings`)
+                Value::String(s) => parse_numeric_string(s),
+                _ => None,
+            };
+            match n {
+                Some(n) => unsafe { vector.as_mut_slice()[row_idx] = n },
+                None => {
+                    vector.set_null(row_idx);
+                    return false;
+                }
+            }
+        }
+        // a geometry object in `geometry_properties`
+        (ColumnType::Geometry, Some(v)) => {
+            let result = geojson_geometry_to_wkb(
+                &v.to_string(),
+                bind_data.buffer_distance,
+                bind_data.max_segment_length,
+                bind_data.coordinate_precision,
+                bind_data.winding,
+            )
+            .and_then(|wkb| {
+                insert_geometry(vector, row_idx, &wkb, bind_data.geometry_format, false)
+            });
+            match result {
+                Ok(()) => {}
+                Err(_) => {
+                    vector.set_null(row_idx);
+                    return false;
+                }
+            }
+        }
+        // GeoJSON columns are never inferred as the other types
+        _ => {
+            vector.set_null(row_idx);
+            return false;
+        }
+    }
+
+    true
+}
+
+fn type_mismatch_error(
+    spec: &ColumnSpec,
+    val: Option<&serde_json::Value>,
+    feature_idx: usize,
+    filename: &str,
+) -> Box<dyn std::error::Error> {
+    format!(
+        "Type mismatch in {}: column '{}' is {:?}, but feature #{} has {}",
+        filename.replace('\\', "/"),
+        spec.name,
+        spec.column_type,
+        feature_idx + 1,
+        val.map(|v| v.to_string()).unwrap_or_default()
+    )
+    .into()
+}
+
+// ==================== //
+//     Gpkg             //
+// ==================== //
+
+fn bind_gpkg(
+    bind: &BindInfo,
+    paths: Vec<PathBuf>,
+) -> Result<StReadMultiBindData, Box<dyn std::error::Error>> {
+    let encoding_option = bind.get_named_parameter("encoding").map(|v| v.to_string());
+
+    if encoding_option.is_some() {
+        warn!("Named parameter 'encoding' is ignored for GeoPackage input");
+    }
+
+    // Check if user specified a layer parameter
+    let layer_name = bind.get_named_parameter("layer").map(|v| v.to_string());
+
+    // The layers of the same name in different files are distinguished only by
+    // `.filename` column. If true, `.layer` column also contains the filename (e.g.
+    // `data/a.gpkg:roads`) so that it's unique by itself.
+    let qualify_layer_with_filename = bind
+        .get_named_parameter("qualify_layer_with_filename")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false);
+
+    let max_open_files = match bind.get_named_parameter("max_open_files") {
+        Some(v) => {
+            let n = v.to_int64();
+            if n < 1 {
+                return Err(format!("'max_open_files' must be a positive integer: {n}").into());
+            }
+            n as usize
+        }
+        None => usize::MAX,
+    };
+
+    // Open the files with the immutable flag so that they can be read while some
+    // other process is writing to them.
+    let immutable = bind
+        .get_named_parameter("immutable")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false);
+    let wal_mode: Option<WalMode> = bind
+        .get_named_parameter("wal_mode")
+        .map(|v| v.to_string().parse())
+        .transpose()?;
+    if immutable && wal_mode == Some(WalMode::Read) {
+        warn!("'wal_mode' is ignored because 'immutable' is specified");
+    }
+    // Ignoring the -wal file is what the immutable flag does.
+    let immutable = immutable || wal_mode == Some(WalMode::Ignore);
+    let prefetch = get_prefetch(bind);
+    // Join the columns of the tables related one-to-one to the layers.
+    let follow_relations = bind
+        .get_named_parameter("follow_relations")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false);
+    // The geometry column of the layers with multiple geometry columns
+    let geometry_column = bind
+        .get_named_parameter("geometry_column")
+        .map(|v| v.to_string());
+
+    let geometry_column_encoding: GeometryColumnEncoding =
+        match bind.get_named_parameter("geometry_column_encoding") {
+            Some(v) => v.to_string().parse()?,
+            None => GeometryColumnEncoding::Gpkg,
+        };
+    let spatial_filter = get_spatial_filter(bind)?;
+    // The bbox of `filter_wkt` is used as a rough filter with the spatial index
+    let bbox = match get_bbox(bind)? {
+        Some(bbox) => Some(bbox),
+        None => spatial_filter.as_ref().map(|f| f.bbox),
+    };
+    // If specified, the geometry types of the layers are checked so that a point
+    // layer and a polygon layer are not combined accidentally.
+    let enforce_geometry_type: Option<GeometryTypeConstraint> = bind
+        .get_named_parameter("enforce_geometry_type")
+        .map(|v| v.to_string().parse())
+        .transpose()?;
+
+    let coordinate_precision = get_coordinate_precision(bind)?;
+    let centroid = get_centroid(bind);
+    let geometry_format = get_geometry_format(bind)?;
+    let winding = get_winding(bind)?;
+    let buffer_distance = get_buffer_distance(bind)?;
+    let max_segment_length = get_max_segment_length(bind)?;
+    let collection_mode = get_collection_mode(bind)?;
+    let chunk_size = get_chunk_size(bind)?;
+    let distinct = get_distinct(bind);
+    let strict = get_strict(bind);
+    let force_2d = get_force_2d(bind);
+    let schema_from_first = get_schema_from_first(bind);
+    let mut schema_validator = get_schema_validator(bind);
+    let union_by_name = bind
+        .get_named_parameter("union_by_name")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false);
+    let geometry_only = get_geometry_only(bind);
+    let include_layer_metadata = bind
+        .get_named_parameter("include_layer_metadata")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false)
+        && !geometry_only;
+    let geometry_last = get_geometry_last(bind)?;
+    let struct_attributes = get_struct_attributes(bind);
+    let (offset, limit) = get_offset_limit(bind)?;
+    let include_mtime = get_include_mtime(bind) && !geometry_only;
+    let filename_relative_to = get_filename_relative_to(bind);
+    let column_filter = get_column_filter(bind);
+
+    let mut sources: Vec<GpkgDataSource> = Vec::new();
+    let mut column_specs: Option<Vec<ColumnSpec>> = None;
+
+    for path in paths {
+        if wal_mode == Some(WalMode::Error) && has_wal(&path) {
+            return Err(format!(
+                "{} has a -wal file, which means it might be being written by some other process. Specify wal_mode='read' or wal_mode='ignore' to read it anyway",
+                path.to_string_lossy()
+            )
+            .into());
+        }
+        let mut gpkg = Gpkg::new(&path, layer_name.clone(), immutable)?;
+        gpkg.follow_relations = follow_relations;
+        gpkg.geometry_column = geometry_column.clone();
+        gpkg.schema_from_first = schema_from_first && !union_by_name;
+
+        // The schema of the rest of the layers is not inspected at all.
+        let specified_specs = column_specs
+            .as_deref()
+            .filter(|_| schema_from_first && !union_by_name);
+
+        let mtime = if include_mtime {
+            file_mtime(&path)
+        } else {
+            None
+        };
+
+        let data_sources = log_parse(&path, || {
+            gpkg.list_data_sources(
+                specified_specs,
+                geometry_only,
+                &column_filter,
+                bbox.as_ref(),
+            )
+        })?;
+        for mut source in data_sources {
+            source.mtime = mtime;
+            if let Some(existing_specs) = &mut column_specs {
+                if union_by_name {
+                    merge_schema(existing_specs, &source.column_specs, &path)?;
+                } else if !schema_from_first {
+                    // check if the schema matches. The columns are in the order of
+                    // the table definition, so compare them in the order of the
+                    // names. The order is reconciled by `column_indices` below.
+                    schema_validator.validate(
+                        &sorted_by_name(existing_specs),
+                        &sorted_by_name(&source.column_specs),
+                        &path,
+                    )?;
+                }
+            } else {
+                // if it's the first file, use the spec as the base.
+                let _ = column_specs.insert(source.column_specs.clone());
+            }
+            sources.push(source);
+        }
+    }
+    schema_validator.finish()?;
+
+    let column_specs = column_specs.ok_or("No layers are found")?;
+    if !geometry_only {
+        column_filter.validate(&column_specs)?;
+    }
+    if let Some(constraint) = &enforce_geometry_type {
+        validate_geometry_types(&sources, constraint)?;
+    }
+
+    // map the columns of each layer to the output columns by name because the
+    // order of the columns can differ between layers
+    if !schema_from_first || union_by_name {
+        for source in sources.iter_mut() {
+            source.column_indices = source
+                .column_specs
+                .iter()
+                .map(|spec| {
+                    column_specs
+                        .iter()
+                        .position(|s| s.name == spec.name)
+                        .unwrap()
+                })
+                .collect();
+        }
+    }
+
+    // The geometry column comes first (cf. Gpkg::get_column_specs()). Rename it so
+    // that it can be queried in the same way as the other formats, unless it
+    // conflicts with an attribute column.
+    let rename_geometry = column_specs
+        .iter()
+        .all(|s| s.name != COLUMN_NAME_GEOMETRY || s.column_type == ColumnType::Geometry);
+    let geometry_column_name = match column_specs.first() {
+        Some(spec) if spec.column_type == ColumnType::Geometry && rename_geometry => {
+            Some(COLUMN_NAME_GEOMETRY)
+        }
+        Some(spec) if spec.column_type == ColumnType::Geometry => Some(spec.name.as_str()),
+        _ => None,
+    };
+    // a layer without geometry (i.e. an attribute table) has nothing to move
+    let geometry_last = geometry_last && geometry_column_name.is_some();
+    if let Some(name) = geometry_column_name.filter(|_| !geometry_last) {
+        bind.add_result_column(name, LogicalTypeId::Blob.into());
+    }
+    let n_geom = geometry_column_name.is_some() as usize;
+    add_attribute_columns(bind, column_specs[n_geom..].iter(), struct_attributes);
+
+    // filename and layer column to track source
+    if !geometry_only {
+        bind.add_result_column(COLUMN_NAME_FILENAME, LogicalTypeId::Varchar.into());
+        bind.add_result_column(COLUMN_NAME_LAYER, LogicalTypeId::Varchar.into());
+    }
+
+    // the last change and the extent declared in gpkg_contents
+    if include_layer_metadata {
+        bind.add_result_column(COLUMN_NAME_LAST_CHANGE, LogicalTypeId::Timestamp.into());
+        for name in COLUMN_NAMES_EXTENT {
+            bind.add_result_column(name, LogicalTypeId::Double.into());
+        }
+    }
+
+    if include_mtime {
+        bind.add_result_column(COLUMN_NAME_MTIME, LogicalTypeId::Timestamp.into());
+    }
+
+    if let Some(name) = geometry_column_name.filter(|_| geometry_last) {
+        bind.add_result_column(name, LogicalTypeId::Blob.into());
+    }
+
+    Ok(GpkgBindData {
+        sources,
+        column_specs,
+        max_open_files,
+        immutable,
+        prefetch,
+        qualify_layer_with_filename,
+        geometry_column_encoding,
+        coordinate_precision,
+        centroid,
+        geometry_format,
+        winding,
+        buffer_distance,
+        max_segment_length,
+        collection_mode,
+        chunk_size,
+        distinct,
+        strict,
+        spatial_filter,
+        force_2d,
+        geometry_only,
+        include_layer_metadata,
+        include_mtime,
+        filename_relative_to,
+        geometry_last,
+        struct_attributes,
+        offset,
+        limit,
+    }
+    .into())
+}
+
+// ==================== //
+//     CSV              //
+// ==================== //
+
+fn bind_csv(
+    bind: &BindInfo,
+    paths: Vec<PathBuf>,
+) -> Result<StReadMultiBindData, Box<dyn std::error::Error>> {
+    let latitude_column = bind
+        .get_named_parameter("latitude_column")
+        .map(|v| v.to_string());
+    let longitude_column = bind
+        .get_named_parameter("longitude_column")
+        .map(|v| v.to_string());
+    let h3_column = bind.get_named_parameter("h3_column").map(|v| v.to_string());
+    let geohash_column = bind
+        .get_named_parameter("geohash_column")
+        .map(|v| v.to_string());
+
+    let has_lon_lat_columns = latitude_column.is_some() || longitude_column.is_some();
+    let location = match (h3_column, geohash_column) {
+        (Some(_), Some(_)) => {
+            return Err("'h3_column' and 'geohash_column' cannot be used together".into())
+        }
+        (Some(_), None) | (None, Some(_)) if has_lon_lat_columns => {
+            return Err(
+                "'h3_column' or 'geohash_column' cannot be used with 'latitude_column' or 'longitude_column'"
+                    .into(),
+            )
+        }
+        (Some(name), None) => CsvLocation::H3(name),
+        (None, Some(name)) => CsvLocation::Geohash(name),
+        (None, None) => CsvLocation::LonLat {
+            longitude_column,
+            latitude_column,
+        },
+    };
+    let coordinate_order = get_coordinate_order(bind)?;
+    let coordinate_precision = get_coordinate_precision(bind)?;
+    let centroid = get_centroid(bind);
+    let geometry_format = get_geometry_format(bind)?;
+    let chunk_size = get_chunk_size(bind)?;
+    let distinct = get_distinct(bind);
+    let strict = get_strict(bind);
+    let spatial_filter = get_spatial_filter(bind)?;
+    let schema_from_first = get_schema_from_first(bind);
+    let mut schema_validator = get_schema_validator(bind);
+    let geometry_only = get_geometry_only(bind);
+    let geometry_last = get_geometry_last(bind)?;
+    let struct_attributes = get_struct_attributes(bind);
+    let (offset, limit) = get_offset_limit(bind)?;
+    let include_mtime = get_include_mtime(bind) && !geometry_only;
+    let filename_relative_to = get_filename_relative_to(bind);
+    let column_filter = get_column_filter(bind);
+
+    let mut sources: Vec<CsvDataSource> = Vec::new();
+    let mut column_specs: Option<Vec<ColumnSpec>> = None;
+
+    for path in paths {
+        let mut source = log_parse(&path, || CsvDataSource::new(&path, &location))?;
+        let column_specs_local = source.column_specs.clone();
+
+        if let Some(existing_specs) = &column_specs {
+            if !schema_from_first {
+                schema_validator.validate(existing_specs, &column_specs_local, &path)?;
+            }
+        } else {
+            let _ = column_specs.insert(column_specs_local);
+        }
+
+        if include_mtime {
+            source.mtime = file_mtime(&path);
+        }
+
+        sources.push(source);
+    }
+    schema_validator.finish()?;
+
+    let mut column_specs = if geometry_only {
+        Vec::new()
+    } else {
+        column_specs.unwrap()
+    };
+    // The values are stored in the same order as the columns, so drop them as well.
+    for row in sources.iter_mut().flat_map(|s| s.rows.iter_mut()) {
+        column_filter.retain_values(&column_specs, &mut row.values);
+    }
+    column_specs.retain(|s| column_filter.keeps(&s.name));
+    if !geometry_only {
+        column_filter.validate(&column_specs)?;
+    }
+
+    if !geometry_last {
+        bind.add_result_column(COLUMN_NAME_GEOMETRY, LogicalTypeId::Blob.into());
+    }
+    add_attribute_columns(bind, column_specs.iter(), struct_attributes);
+
+    // filename column to track source file
+    if !geometry_only {
+        bind.add_result_column(COLUMN_NAME_FILENAME, LogicalTypeId::Varchar.into());
+    }
+
+    if include_mtime {
+        bind.add_result_column(COLUMN_NAME_MTIME, LogicalTypeId::Timestamp.into());
+    }
+
+    if geometry_last {
+        bind.add_result_column(COLUMN_NAME_GEOMETRY, LogicalTypeId::Blob.into());
+    }
+
+    Ok(CsvBindData {
+        sources,
+        column_specs,
+        coordinate_order,
+        coordinate_precision,
+        centroid,
+        geometry_format,
+        chunk_size,
+        distinct,
+        strict,
+        spatial_filter,
+        geometry_only,
+        include_mtime,
+        filename_relative_to,
+        geometry_last,
+        struct_attributes,
+        offset,
+        limit,
+    }
+    .into())
+}
+
+// Write a value read as a text (CSV and MID). An empty value is NULL. A value that
+// cannot be parsed as the column type becomes NULL, or is an error in strict mode.
+fn write_text_value(
+    vector: &mut FlatVector,
+    row_idx: usize,
+    spec: &ColumnSpec,
+    val: Option<&str>,
+    strict: bool,
+    filename: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(val) = val.filter(|v| spec.column_type == ColumnType::Varchar || !v.trim().is_empty())
+    else {
+        vector.set_null(row_idx);
+        return Ok(());
+    };
+
+    let parsed = match spec.column_type {
+        ColumnType::Varchar => {
+            vector.insert(row_idx, val);
+            true
+        }
+        ColumnType::Double => match val.trim().parse::<f64>() {
+            Ok(v) => {
+                unsafe { vector.as_mut_slice()[row_idx] = v };
+                true
+            }
+            Err(_) => false,
+        },
+        ColumnType::Integer => match val.trim().parse::<i32>() {
+            Ok(v) => {
+                unsafe { vector.as_mut_slice()[row_idx] = v };
+                true
+            }
+            Err(_) => false,
+        },
+        ColumnType::Boolean => match val.trim() {
+            "T" | "t" | "true" | "1" => {
+                unsafe { vector.as_mut_slice()[row_idx] = true };
+                true
+            }
+            "F" | "f" | "false" | "0" => {
+                unsafe { vector.as_mut_slice()[row_idx] = false };
+                true
+            }
+            _ => false,
+        },
+        ColumnType::Date => match parse_mid_date(val.trim()) {
+            Some(days) => {
+                unsafe { vector.as_mut_slice::<duckdb_date>()[row_idx] = duckdb_date { days } };
+                true
+            }
+            None => false,
+        },
+        ColumnType::Timestamp => match parse_mid_datetime(val.trim()) {
+            Some(micros) => {
+                unsafe {
+                    vector.as_mut_slice::<duckdb_timestamp>()[row_idx] = duckdb_timestamp { micros }
+                };
+                true
+            }
+            None => false,
+        },
+        // not inferred for CSV and MID
+        _ => {
+            vector.set_null(row_idx);
+            true
+        }
+    };
+    if !parsed {
+        set_null_unparsable(vector, row_idx, strict, spec, val, filename)?;
+    }
+    Ok(())
+}
+
+// ==================== //
+//     MapInfo          //
+// ==================== //
+
+fn bind_mif(
+    bind: &BindInfo,
+    paths: Vec<PathBuf>,
+) -> Result<StReadMultiBindData, Box<dyn std::error::Error>> {
+    let coordinate_precision = get_coordinate_precision(bind)?;
+    let centroid = get_centroid(bind);
+    let geometry_format = get_geometry_format(bind)?;
+    let winding = get_winding(bind)?;
+    let buffer_distance = get_buffer_distance(bind)?;
+    let max_segment_length = get_max_segment_length(bind)?;
+    let chunk_size = get_chunk_size(bind)?;
+    let distinct = get_distinct(bind);
+    let strict = get_strict(bind);
+    let spatial_filter = get_spatial_filter(bind)?;
+    let schema_from_first = get_schema_from_first(bind);
+    let mut schema_validator = get_schema_validator(bind);
+    let geometry_only = get_geometry_only(bind);
+    let geometry_last = get_geometry_last(bind)?;
+    let struct_attributes = get_struct_attributes(bind);
+    let (offset, limit) = get_offset_limit(bind)?;
+    let include_mtime = get_include_mtime(bind) && !geometry_only;
+    let filename_relative_to = get_filename_relative_to(bind);
+    let column_filter = get_column_filter(bind);
+
+    let mut sources: Vec<MifDataSource> = Vec::new();
+    let mut column_specs: Option<Vec<ColumnSpec>> = None;
+
+    for path in paths {
+        let mut source = log_parse(&path, || MifDataSource::new(&path))?;
+        let column_specs_local = source.column_specs.clone();
+
+        if let Some(existing_specs) = &column_specs {
+            if !schema_from_first {
+                schema_validator.validate(existing_specs, &column_specs_local, &path)?;
+            }
+        } else {
+            let _ = column_specs.insert(column_specs_local);
+        }
+
+        if include_mtime {
+            source.mtime = file_mtime(&path);
+        }
+
+        sources.push(source);
+    }
+    schema_validator.finish()?;
+
+    let mut column_specs = if geometry_only {
+        Vec::new()
+    } else {
+        column_specs.unwrap()
+    };
+    // The values are stored in the same order as the columns, so drop them as well.
+    for row in sources.iter_mut().flat_map(|s| s.rows.iter_mut()) {
+        column_filter.retain_values(&column_specs, &mut row.values);
+    }
+    column_specs.retain(|s| column_filter.keeps(&s.name));
+    if !geometry_only {
+        column_filter.validate(&column_specs)?;
+    }
+
+    if !geometry_last {
+        bind.add_result_column(COLUMN_NAME_GEOMETRY, LogicalTypeId::Blob.into());
+    }
+    add_attribute_columns(bind, column_specs.iter(), struct_attributes);
+
+    // filename column to track source file
+    if !geometry_only {
+        bind.add_result_column(COLUMN_NAME_FILENAME, LogicalTypeId::Varchar.into());
+    }
+
+    if include_mtime {
+        bind.add_result_column(COLUMN_NAME_MTIME, LogicalTypeId::Timestamp.into());
+    }
+
+    if geometry_last {
+        bind.add_result_column(COLUMN_NAME_GEOMETRY, LogicalTypeId::Blob.into());
+    }
+
+    Ok(MifBindData {
+        sources,
+        column_specs,
+        coordinate_precision,
+        centroid,
+        geometry_format,
+        winding,
+        buffer_distance,
+        max_segment_length,
+        chunk_size,
+        distinct,
+        strict,
+        spatial_filter,
+        geometry_only,
+        include_mtime,
+        filename_relative_to,
+        geometry_last,
+        struct_attributes,
+        offset,
+        limit,
+    }
+    .into())
+}
+
+// ==================== //
+//     Shapefile        //
+// ==================== //
+
+// If `dbf_only` is true, the files are standalone .dbf files, which have no geometry.
+fn bind_shapefile(
+    bind: &BindInfo,
+    paths: Vec<PathBuf>,
+    dbf_only: bool,
+) -> Result<StReadMultiBindData, Box<dyn std::error::Error>> {
+    let encoding_option = bind.get_named_parameter("encoding").map(|v| v.to_string());
+    let include_encoding = bind
+        .get_named_parameter("include_encoding")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false);
+    let include_metadata = bind
+        .get_named_parameter("include_metadata")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false);
+    let include_deleted = bind
+        .get_named_parameter("include_deleted")
+        .map(|v| v.to_string() == "true")
+        .unwrap_or(false);
+    // The column whose bytes before decoding are read as `.raw_bytes` column, to
+    // diagnose the encoding issues
+    let raw_bytes_for = bind
+        .get_named_parameter("raw_bytes_for")
+        .map(|v| v.to_string());
+    // Some exporters store booleans as 0/1 of Numeric fields
+    let boolean_columns: Vec<String> = bind
+        .get_named_parameter("boolean_columns")
+        .map(|v| parse_list_value(&v.to_string()))
+        .unwrap_or_default();
+    let coordinate_precision = get_coordinate_precision(bind)?;
+    let centroid = get_centroid(bind);
+    let geometry_format = get_geometry_format(bind)?;
+    let winding = get_winding(bind)?;
+    let buffer_distance = get_buffer_distance(bind)?;
+    let max_segment_length = get_max_segment_length(bind)?;
+    let (geometry_type, on_geometry_type_mismatch) = get_geometry_type(bind)?;
+    let chunk_size = get_chunk_size(bind)?;
+    let distinct = get_distinct(bind);
+    let strict = get_strict(bind);
+    let spatial_filter = get_spatial_filter(bind)?;
+    let force_2d = get_force_2d(bind);
+    let schema_from_first = get_schema_from_first(bind);
+    let mut schema_validator = get_schema_validator(bind);
+    let geometry_only = get_geometry_only(bind);
+    let geometry_last = get_geometry_last(bind)?;
+    let struct_attributes = get_struct_attributes(bind);
+    let (offset, limit) = get_offset_limit(bind)?;
+    let include_mtime = get_include_mtime(bind) && !geometry_only;
+    let filename_relative_to = get_filename_relative_to(bind);
+    let column_filter = get_column_filter(bind);
+
+    if dbf_only && geometry_only {
+        return Err("'geometry_only' cannot be used for .dbf files".into());
+    }
+
+    let specified_encoding = match encoding_option {
+        Some(label) => Some(EncodingOption::from_label(&label)?),
+        None => None,
+    };
+
+    let mut sources: Vec<ShapefileDataSource> = Vec::new();
+    let mut column_specs: Option<Vec<ColumnSpec>> = None;
+
+    let include_metadata = include_metadata && !geometry_only;
+
+    for path in paths {
+        let mut source = log_parse(&path, || {
+            if dbf_only {
+                ShapefileDataSource::from_dbf(&path, specified_encoding.as_ref())
+            } else {
+                ShapefileDataSource::new(&path, specified_encoding.as_ref())
+            }
+        })?;
+        let column_specs_local = source.column_specs.clone();
+
+        if include_metadata {
+            source.metadata = Metadata::read(source.shp_path());
+        }
+
+        if let (Some(name), false) = (&raw_bytes_for, geometry_only) {
+            source.set_raw_bytes_field(name)?;
+        }
+
+        // Skip the records marked as deleted, as most GIS tools do.
+        if !include_deleted {
+            source.skip_deleted();
+        }
+
+        if let Some(existing_specs) = &column_specs {
+            if !schema_from_first {
+                schema_validator.validate(existing_specs, &column_specs_local, &path)?;
+            }
+        } else {
+            let _ = column_specs.insert(column_specs_local);
+        }
+
+        if include_mtime {
+            source.mtime = file_mtime(&path);
+        }
+
+        sources.push(source);
+    }
+    schema_validator.finish()?;
+
+    let mut column_specs = if geometry_only {
+        Vec::new()
+    } else {
+        column_specs.unwrap()
+    };
+    column_specs.retain(|s| column_filter.keeps(&s.name));
+    if !geometry_only {
+        column_filter.validate(&column_specs)?;
+    }
+
+    // The 0/1 values of these columns are read as booleans in func()
+    for name in &boolean_columns {
+        match column_specs.iter_mut().find(|s| s.name == *name) {
+            Some(spec) if matches!(spec.column_type, ColumnType::Integer | ColumnType::Double) => {
+                spec.column_type = ColumnType::Boolean
+            }
+            Some(spec) if spec.column_type == ColumnType::Boolean => {}
+            Some(spec) => warn!(
+                "Column '{name}' in 'boolean_columns' is ignored because it's {:?}, not a numeric field",
+                spec.column_type
+            ),
+            None if !geometry_only => {
+                warn!("Column '{name}' in 'boolean_columns' doesn't exist")
+            }
+            None => {}
+        }
+    }
+
+    // standalone .dbf files don't have the geometry column
+    let geometry_last = geometry_last && !dbf_only;
+    if !dbf_only && !geometry_last {
+        bind.add_result_column(COLUMN_NAME_GEOMETRY, LogicalTypeId::Blob.into());
+    }
+    add_attribute_columns(bind, column_specs.iter(), struct_attributes);
+
+    // encoding column to track the encoding used for the attributes
+    let include_encoding = include_encoding && !geometry_only;
+
+    if !geometry_only {
+        bind.add_result_column(COLUMN_NAME_FILENAME, LogicalTypeId::Varchar.into());
+    }
+
+    if include_encoding {
+        bind.add_result_column(COLUMN_NAME_ENCODING, LogicalTypeId::Varchar.into());
+    }
+
+    // title and abstract columns from the metadata sidecar file
+    if include_metadata {
+        bind.add_result_column(COLUMN_NAME_TITLE, LogicalTypeId::Varchar.into());
+        bind.add_result_column(COLUMN_NAME_ABSTRACT, LogicalTypeId::Varchar.into());
+    }
+
+    // Deleted records are included only when they are requested, so the column is
+    // added only in that case.
+    let include_deleted_column = include_deleted && !geometry_only;
+    if include_deleted_column {
+        bind.add_result_column(COLUMN_NAME_IS_DELETED, LogicalTypeId::Boolean.into());
+    }
+
+    let include_raw_bytes = raw_bytes_for.is_some() && !geometry_only;
+    if include_raw_bytes {
+        bind.add_result_column(COLUMN_NAME_RAW_BYTES, LogicalTypeId::Blob.into());
+    }
 
-const COLUMN_NAME_FILENAME: &str = ".filename";
-const COLUMN_NAME_LAYER: &str = ".layer";
+    if include_mtime {
+        bind.add_result_column(COLUMN_NAME_MTIME, LogicalTypeId::Timestamp.into());
+    }
+
+    if geometry_last {
+        bind.add_result_column(COLUMN_NAME_GEOMETRY, LogicalTypeId::Blob.into());
+    }
 
-struct StReadMultiVTab;
+    Ok(ShapefileBindData {
+        sources,
+        column_specs,
+        include_encoding,
+        include_metadata,
+        include_deleted: include_deleted_column,
+        include_raw_bytes,
+        has_geometry: !dbf_only,
+        prefetch: get_prefetch(bind),
+        coordinate_precision,
+        centroid,
+        geometry_format,
+        winding,
+        buffer_distance,
+        max_segment_length,
+        geometry_type,
+        on_geometry_type_mismatch,
+        chunk_size,
+        distinct,
+        strict,
+        spatial_filter,
+        force_2d,
+        geometry_only,
+        include_mtime,
+        filename_relative_to,
+        geometry_last,
+        struct_attributes,
+        offset,
+        limit,
+    }
+    .into())
+}
 
-impl VTab for StReadMultiVTab {
+impl<F: FormatSelector> VTab for StReadMultiVTab<F> {
     type InitData = StReadMultiInitData;
     type BindData = StReadMultiBindData;
 
     fn bind(bind: &BindInfo) -> Result<Self::BindData, Box<dyn std::error::Error>> {
+        let bind_warnings = BindWarnings::start();
+        validate_parameters(bind)?;
+
         let path_pattern = bind.get_parameter(0).to_string();
-        let expanded_pattern = expand_tilde(&path_pattern);
-        let paths: Vec<PathBuf> = glob(&expanded_pattern)?.collect::<Result<_, _>>()?;
-        let encoding_option = bind.get_named_parameter("encoding").map(|v| v.to_string());
 
-        if paths.is_empty() {
-            return Err(format!("'{path_pattern}' doesn't match to any file").into());
-        }
+        // `@<file>` reads the paths and the glob patterns listed in the file
+        let patterns: Vec<String> = match path_pattern.strip_prefix('@') {
+            Some(manifest) => read_manifest(expand_tilde(manifest))?,
+            None => vec![expand_tilde(&path_pattern)],
+        };
 
-        // ==================== //
-        //     GeoJSON          //
-        // ==================== //
+        // glob patterns to exclude from the matched paths
+        let exclude_patterns: Vec<glob::Pattern> = match bind.get_named_parameter("exclude") {
+            Some(v) => parse_list_value(&v.to_string())
+                .iter()
+                .map(|p| glob::Pattern::new(&expand_tilde(p)))
+                .collect::<Result<_, _>>()?,
+            None => vec![],
+        };
 
-        if paths.iter().all(is_geojson) {
-            if encoding_option.is_some() {
-                eprintln!("[WARN] Named parameter 'encoding' is ignored for GeoJSON input");
+        let max_depth = match bind.get_named_parameter("max_depth") {
+            Some(v) => {
+                let n = v.to_int64();
+                if n < 0 {
+                    return Err(format!("'max_depth' must not be negative: {n}").into());
+                }
+                if !patterns.iter().any(|p| p.contains("**")) {
+                    warn!("Named parameter 'max_depth' is ignored because the path doesn't contain '**'");
+                }
+                Some(n as usize)
             }
+            None => None,
+        };
 
-            let mut sources: Vec<GeoJsonDataSource> = Vec::new();
-            let mut column_specs: Option<Vec<ColumnSpec>> = None;
-
-            for path in paths {
-                let (mut data_sources, column_specs_local) =
-                    GeoJsonDataSource::parse_and_split(&path)?;
-                sources.append(&mut data_sources);
-
-                if let Some(existing_specs) = &column_specs {
-                    // check if the schema matches
-                    validate_schema(existing_specs, &column_specs_local, &path)?;
-                } else {
-                    // if it's the first file, use the spec as the base.
-                    let _ = column_specs.insert(column_specs_local);
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for pattern in &patterns {
+            let matched = if s3::is_s3_url(pattern) {
+                s3::expand_s3_url(pattern)?
+                    .into_iter()
+                    .map(PathBuf::from)
+                    .collect()
+            } else {
+                expand_glob(pattern, max_depth)?
+            };
+            log::debug!("'{pattern}' matched {} files", matched.len());
+            for path in matched {
+                // a file can match multiple patterns in the manifest
+                if !exclude_patterns.iter().any(|p| p.matches_path(&path)) && !paths.contains(&path)
+                {
+                    paths.push(path);
                 }
             }
+        }
 
-            let column_specs = column_specs.unwrap();
-
-            bind.add_result_column("geometry", LogicalTypeId::Blob.into());
-            for spec in column_specs.iter() {
-                bind.add_result_column(&spec.name, spec.column_type.into());
+        if paths.is_empty() {
+            // Distinguish an unreachable network share from a missing file
+            for root in patterns.iter().filter_map(|p| network_share_root(p)) {
+                if !Path::new(&root).exists() {
+                    return Err(format!(
+                        "Network path '{root}' is unreachable. Check that the share is mounted or accessible with the current credentials: '{path_pattern}'"
+                    )
+                    .into());
+                }
             }
+            return Err(format!("'{path_pattern}' doesn't match to any file").into());
+        }
 
-            // filename column to track source file
-            bind.add_result_column(COLUMN_NAME_FILENAME, LogicalTypeId::Varchar.into());
-
-            return Ok(GeoJsonBindData {
-                sources,
-                column_specs,
+        // Skip the files not modified since the last run of an incremental pipeline.
+        // The timestamp without offset is assumed to be in UTC.
+        if let Some(v) = bind.get_named_parameter("modified_after") {
+            let s = v.to_string();
+            let cutoff = gpkg::parse_datetime_to_utc_micros(&s).ok_or_else(|| {
+                format!("'modified_after' must be a timestamp (e.g. '2024-01-15 10:30:00'): {s}")
+            })?;
+            let n_paths = paths.len();
+            paths = filter_modified_after(paths, cutoff);
+            log::debug!(
+                "{} files are skipped by 'modified_after'",
+                n_paths - paths.len()
+            );
+            if paths.is_empty() {
+                return Err(
+                    format!("No files matching '{path_pattern}' are modified after {s}").into(),
+                );
             }
-            .into());
         }
 
-        // ==================== //
-        //     Gpkg             //
-        // ==================== //
-
-        if paths.iter().all(is_gpkg) {
-            if encoding_option.is_some() {
-                eprintln!("[WARN] Named parameter 'encoding' is ignored for GeoPackage input");
+        // `format` option skips the detection by the extension and the content
+        let format_option = bind.get_named_parameter("format").map(|v| v.to_string());
+        let format = match (F::FORMAT, &format_option) {
+            (Some(format), Some(_)) => {
+                warn!("Named parameter 'format' is ignored because the function reads a specific format");
+                format
             }
+            (Some(format), None) => format,
+            (None, Some(format_option)) => format_option.parse()?,
+            (None, None) => detect_format(&paths)?,
+        };
+        log::info!("Reading {} files as {format:?}", paths.len());
 
-            // Check if user specified a layer parameter
-            let layer_name = bind.get_named_parameter("layer").map(|v| v.to_string());
-
-            let mut sources: Vec<GpkgDataSource> = Vec::new();
-            let mut column_specs: Option<Vec<ColumnSpec>> = None;
-
-            for path in paths {
-                let gpkg = Gpkg::new(&path, layer_name.clone())?;
+        if !matches!(format, Format::GeoJson | Format::EsriJson)
+            && paths.iter().any(|p| s3::is_s3_url(&p.to_string_lossy()))
+        {
+            return Err("Only GeoJSON and EsriJSON files can be read from S3 for now".into());
+        }
 
-                for source in gpkg.list_data_sources()? {
-                    if let Some(existing_specs) = &column_specs {
-                        // check if the schema matches
-                        validate_schema(existing_specs, &source.column_specs, &path)?;
-                    } else {
-                        // if it's the first file, use the spec as the base.
-                        let _ = column_specs.insert(source.column_specs.clone());
-                    }
-                    sources.push(source);
-                }
+        if !matches!(
+            format,
+            Format::GeoJson | Format::EsriJson | Format::Shapefile | Format::Dbf
+        ) && bind.get_named_parameter("boolean_columns").is_some()
+        {
+            warn!(
+                "Named parameter 'boolean_columns' is only supported for GeoJSON, EsriJSON, Shapefile, and DBF input"
+            );
+        }
+        for name in [
+            "coerce_numeric_strings",
+            "numbers_as_double",
+            "on_type_mismatch",
+        ] {
+            if !matches!(format, Format::GeoJson | Format::EsriJson)
+                && bind.get_named_parameter(name).is_some()
+            {
+                warn!("Named parameter '{name}' is only supported for GeoJSON and EsriJSON input");
             }
-
-            let column_specs = column_specs.ok_or("No layers are found")?;
-
-            for spec in column_specs.iter() {
-                bind.add_result_column(&spec.name, spec.column_type.into());
+        }
+        for name in ["geometry_properties", "geometry_member", "json_pointer"] {
+            if format != Format::GeoJson && bind.get_named_parameter(name).is_some() {
+                warn!("Named parameter '{name}' is only supported for GeoJSON input");
             }
-
-            // filename and layer column to track source
-            bind.add_result_column(COLUMN_NAME_FILENAME, LogicalTypeId::Varchar.into());
-            bind.add_result_column(COLUMN_NAME_LAYER, LogicalTypeId::Varchar.into());
-
-            return Ok(GpkgBindData {
-                sources,
-                column_specs,
+        }
+        for name in ["geometry_type", "on_geometry_type_mismatch"] {
+            if !matches!(
+                format,
+                Format::GeoJson | Format::EsriJson | Format::Shapefile
+            ) && bind.get_named_parameter(name).is_some()
+            {
+                warn!("Named parameter '{name}' is only supported for GeoJSON, EsriJSON, and Shapefile input");
             }
-            .into());
         }
-
-        // ==================== //
-        //     Shapefile        //
-        // ==================== //
-
-        if paths.iter().all(is_shp) {
-            let specified_encoding = match encoding_option {
-                Some(label) => Some(
-                    ::shapefile::dbase::encoding::DynEncoding::from_name(&label).ok_or_else(
-                        || format!("Unknown encoding label in 'encoding' option: {label}"),
-                    )?,
-                ),
-                None => None,
-            };
-
-            let mut sources: Vec<ShapefileDataSource> = Vec::new();
-            let mut column_specs: Option<Vec<ColumnSpec>> = None;
-
-            for path in paths {
-                let source = ShapefileDataSource::new(&path, specified_encoding.clone())?;
-                let column_specs_local = source.column_specs.clone();
-
-                if let Some(existing_specs) = &column_specs {
-                    validate_schema(existing_specs, &column_specs_local, &path)?;
-                } else {
-                    let _ = column_specs.insert(column_specs_local);
-                }
-
-                sources.push(source);
+        if format == Format::Csv
+            && bind
+                .get_named_parameter("densify_max_segment_length")
+                .is_some()
+        {
+            warn!("Named parameter 'densify_max_segment_length' is not supported for CSV input");
+        }
+        if format == Format::Csv && bind.get_named_parameter("buffer_distance").is_some() {
+            warn!("Named parameter 'buffer_distance' is not supported for CSV input");
+        }
+        if !matches!(format, Format::GeoJson | Format::EsriJson | Format::Csv)
+            && bind.get_named_parameter("coordinate_order").is_some()
+        {
+            warn!(
+                "Named parameter 'coordinate_order' is only supported for GeoJSON, EsriJSON, and CSV input"
+            );
+        }
+        for name in [
+            "union_by_name",
+            "bbox",
+            "include_layer_metadata",
+            "geometry_column",
+        ] {
+            if format != Format::Gpkg && bind.get_named_parameter(name).is_some() {
+                warn!("Named parameter '{name}' is only supported for GeoPackage input");
             }
-
-            let column_specs = column_specs.unwrap();
-
-            bind.add_result_column("geometry", LogicalTypeId::Blob.into());
-            for spec in column_specs.iter() {
-                bind.add_result_column(&spec.name, spec.column_type.into());
+        }
+        for name in ["include_metadata", "include_deleted", "raw_bytes_for"] {
+            if !matches!(format, Format::Shapefile | Format::Dbf)
+                && bind.get_named_parameter(name).is_some()
+            {
+                warn!("Named parameter '{name}' is only supported for Shapefile input");
+            }
+        }
+        for name in [
+            "latitude_column",
+            "longitude_column",
+            "h3_column",
+            "geohash_column",
+        ] {
+            if format != Format::Csv && bind.get_named_parameter(name).is_some() {
+                warn!("Named parameter '{name}' is only supported for CSV input");
             }
+        }
 
-            bind.add_result_column(COLUMN_NAME_FILENAME, LogicalTypeId::Varchar.into());
+        let dry_run = bind
+            .get_named_parameter("dry_run")
+            .map(|v| v.to_string() == "true")
+            .unwrap_or(false);
+
+        let bind_data = match format {
+            Format::GeoJson => bind_geojson(bind, paths, false),
+            Format::EsriJson => bind_geojson(bind, paths, true),
+            Format::Gpkg => bind_gpkg(bind, paths),
+            Format::Shapefile => bind_shapefile(bind, paths, false),
+            Format::Dbf => bind_shapefile(bind, paths, true),
+            Format::Csv => bind_csv(bind, paths),
+            Format::Mif => bind_mif(bind, paths),
+        };
+        // The files are not checked in advance when the format is forced, so tell
+        // the user the reason of the failure.
+        let mut bind_data = match (bind_data, &format_option) {
+            (Err(e), Some(format_option)) if F::FORMAT.is_none() => {
+                return Err(format!("Cannot read the files as '{format_option}': {e}").into());
+            }
+            (bind_data, _) => bind_data?,
+        };
 
-            return Ok(ShapefileBindData {
-                sources,
-                column_specs,
+        // In strict mode, anything that would be a warning is an error
+        if get_strict(bind) {
+            if let Some(warning) = bind_warnings.first() {
+                return Err(format!("{warning} (strict mode)").into());
             }
-            .into());
         }
 
-        Err("All files must have extension '.geojson', '.gpkg', or '.shp'".into())
+        log::info!(
+            "Found {} rows in {} data sources",
+            (0..bind_data.n_sources())
+                .map(|i| bind_data.n_rows(i))
+                .sum::<usize>(),
+            bind_data.n_sources()
+        );
+
+        // All the files are already parsed and validated at this point.
+        if dry_run {
+            bind_data.discard_sources();
+        }
+
+        Ok(bind_data)
     }
 
-    fn init(_: &InitInfo) -> Result<Self::InitData, Box<dyn std::error::Error>> {
-        Ok(StReadMultiInitData {
-            cursor: Arc::new(Mutex::new(Cursor::new())),
-        })
+    fn init(init: &InitInfo) -> Result<Self::InitData, Box<dyn std::error::Error>> {
+        // Each thread claims a chunk of rows at a time, so the scan can be parallelized
+        // regardless of the number of data sources. DuckDB caps this to the number of
+        // threads it's configured with.
+        let max_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let bind_data = unsafe { &*init.get_bind_data::<StReadMultiBindData>() };
+        let (init_data, max_threads) = StReadMultiInitData::new_for(bind_data, max_threads);
+        init.set_max_threads(max_threads as _);
+
+        Ok(init_data)
     }
 
     fn func(
@@ -209,325 +2094,1102 @@ impl VTab for StReadMultiVTab {
         let init_data = func.get_init_data();
         let bind_data = func.get_bind_data();
 
-        let mut cursor = match init_data.cursor.lock() {
-            Ok(cursor) => cursor,
-            Err(_) => return Err("Failed to acquire the lock of the cursor".into()),
-        };
+        // The rows of each chunk are written after the `n_filled` rows already in the
+        // output. An empty output tells DuckDB it's over.
+        let n_filled = init_data.fill_output(bind_data, |chunk, mut n_filled| {
+            let n_columns = output.num_columns();
+            let geometry_last = bind_data.geometry_last();
+            let col = |i: usize| column_index(i, n_columns, geometry_last);
+
+            match bind_data {
+                // ==================== //
+                //     GeoJSON          //
+                // ==================== //
+                StReadMultiBindData::GeoJson(bind_data_inner) => {
+                    let mut geom_vector = output.flat_vector(col(0));
+                    let n_props = bind_data_inner.column_specs.len();
+                    let n_foreign_members = bind_data_inner.foreign_member_specs.len();
+                    let struct_attributes = bind_data_inner.struct_attributes;
+                    let mut property_vectors = attribute_vectors(
+                        output,
+                        1,
+                        n_props + n_foreign_members,
+                        struct_attributes,
+                        col,
+                    );
+                    let mut foreign_member_vectors = property_vectors.split_off(n_props);
+                    let n_attr_columns =
+                        n_attribute_columns(n_props + n_foreign_members, struct_attributes);
+                    let filename_vector = if bind_data_inner.geometry_only {
+                        None
+                    } else {
+                        Some(output.flat_vector(col(n_attr_columns + 1)))
+                    };
+                    let crs_vector = if bind_data_inner.include_crs {
+                        Some(output.flat_vector(col(n_attr_columns + 2)))
+                    } else {
+                        None
+                    };
 
-        match bind_data {
-            // ==================== //
-            //     GeoJSON          //
-            // ==================== //
-            StReadMultiBindData::GeoJson(bind_data_inner) => {
-                // If there's no remaining data source, tell DuckDB it's over.
-                if cursor.source_idx >= bind_data_inner.sources.len() {
-                    output.set_len(0);
-                    return Ok(());
-                }
+                    let mut mtime_vector = if bind_data_inner.include_mtime {
+                        Some(output.flat_vector(col(n_columns - 1)))
+                    } else {
+                        None
+                    };
+
+                    let mut row_idx = n_filled;
+                    let mut wkb_converter = WkbConverter::new(
+                        bind_data_inner.coordinate_order,
+                        bind_data_inner.coordinate_precision,
+                        bind_data_inner.centroid,
+                        bind_data_inner.buffer_distance,
+                        bind_data_inner.max_segment_length,
+                        bind_data_inner.winding,
+                        bind_data_inner.collection_mode,
+                    );
+                    let source = &bind_data_inner.sources[chunk.source_idx];
+                    let on_type_mismatch = bind_data_inner.on_type_mismatch;
+                    let filename = relative_filename(
+                        &source.filename,
+                        bind_data_inner.filename_relative_to.as_deref(),
+                    );
+                    let property_layout = &bind_data_inner.property_layouts[chunk.source_idx];
+                    let mut values = vec![None; bind_data_inner.column_specs.len()];
+
+                    for (feature_idx, f) in chunk.range().zip(&source.features[chunk.range()]) {
+                        if let Some(filter) = &bind_data_inner.spatial_filter {
+                            if !feature_intersects(f, filter, bind_data_inner.coordinate_order) {
+                                continue;
+                            }
+                        }
+                        let wkb_data = coerce_geometry(
+                            wkb_converter.convert(f)?,
+                            bind_data_inner.geometry_type,
+                            bind_data_inner.on_geometry_type_mismatch,
+                            &source.filename,
+                        )?;
+                        if bind_data_inner.distinct {
+                            let mut hasher = DefaultHasher::new();
+                            wkb_data.hash(&mut hasher);
+                            for spec in &bind_data_inner.column_specs {
+                                let val = f.properties.as_ref().and_then(|p| p.get(&spec.name));
+                                val.map(|v| v.to_string()).hash(&mut hasher);
+                            }
+                            for spec in &bind_data_inner.foreign_member_specs {
+                                let val =
+                                    f.foreign_members.as_ref().and_then(|m| m.get(&spec.name));
+                                val.map(|v| v.to_string()).hash(&mut hasher);
+                            }
+                            if init_data.is_duplicate(hasher.finish()) {
+                                continue;
+                            }
+                        }
+                        if !init_data.is_in_page(bind_data) {
+                            continue;
+                        }
+                        match wkb_data {
+                            Some(wkb) => insert_geometry(
+                                &geom_vector,
+                                row_idx,
+                                &wkb,
+                                bind_data_inner.geometry_format,
+                                bind_data_inner.strict,
+                            )
+                            .map_err(|e| -> Box<dyn Error> { e })?,
+                            None => set_null_geometry(
+                                &mut geom_vector,
+                                row_idx,
+                                bind_data_inner.strict,
+                                &source.filename,
+                            )
+                            .map_err(|e| -> Box<dyn Error> { e })?,
+                        }
+                        if let Some(filename_vector) = &filename_vector {
+                            filename_vector.insert(row_idx, filename.as_ref());
+                        }
+                        if let Some(mtime_vector) = &mut mtime_vector {
+                            write_timestamp(mtime_vector, row_idx, source.mtime);
+                        }
+                        // If the file doesn't declare any CRS, it's the default one
+                        if let Some(crs_vector) = &crs_vector {
+                            crs_vector
+                                .insert(row_idx, source.crs.as_deref().unwrap_or("OGC:CRS84"));
+                        }
+
+                        if let Some(properties) = &f.properties {
+                            property_layout.read(
+                                properties,
+                                &bind_data_inner.column_specs,
+                                &mut values,
+                            );
+                            for (prop_idx, (spec, &val)) in
+                                bind_data_inner.column_specs.iter().zip(&values).enumerate()
+                            {
+                                if !write_geojson_value(
+                                    &mut property_vectors[prop_idx],
+                                    row_idx,
+                                    spec.column_type,
+                                    val,
+                                    bind_data_inner,
+                                ) && on_type_mismatch == OnTypeMismatch::Error
+                                {
+                                    return Err(type_mismatch_error(
+                                        spec,
+                                        val,
+                                        feature_idx,
+                                        &source.filename,
+                                    ));
+                                }
+                            }
+                        } else {
+                            // The vector might contain the values of the previous chunk, so
+                            // this needs to be set explicitly.
+                            for property_vector in property_vectors.iter_mut() {
+                                property_vector.set_null(row_idx);
+                            }
+                        }
 
-                let geom_vector = output.flat_vector(0);
-                let n_props = bind_data_inner.column_specs.len();
-                let mut property_vectors: Vec<FlatVector> =
-                    (0..n_props).map(|i| output.flat_vector(i + 1)).collect();
-                let filename_vector = output.flat_vector(n_props + 1);
+                        for (i, spec) in bind_data_inner.foreign_member_specs.iter().enumerate() {
+                            let val = f
+                                .foreign_members
+                                .as_ref()
+                                .and_then(|members| members.get(&spec.name));
+                            if !write_geojson_value(
+                                &mut foreign_member_vectors[i],
+                                row_idx,
+                                spec.column_type,
+                                val,
+                                bind_data_inner,
+                            ) && on_type_mismatch == OnTypeMismatch::Error
+                            {
+                                return Err(type_mismatch_error(
+                                    spec,
+                                    val,
+                                    feature_idx,
+                                    &source.filename,
+                                ));
+                            }
+                        }
 
-                let mut row_idx: usize = 0;
-                let mut wkb_converter = WkbConverter::new();
-                let source = &bind_data_inner.sources[cursor.source_idx];
+                        row_idx += 1;
+                    }
 
-                let range_end = std::cmp::min(cursor.offset + VECTOR_SIZE, source.features.len());
-                let last = range_end >= source.features.len();
-                let range = cursor.offset..range_end;
+                    n_filled = row_idx;
+                }
 
-                for f in &source.features[range] {
-                    let wkb_data = wkb_converter.convert(f)?;
-                    geom_vector.insert(row_idx, wkb_data);
-                    filename_vector.insert(row_idx, source.filename.as_str());
+                // ==================== //
+                //     Gpkg             //
+                // ==================== //
+                StReadMultiBindData::Gpkg(bind_data_inner) => {
+                    let n_props = bind_data_inner.column_specs.len();
+                    // The geometry column is not an attribute, so it's never bundled
+                    // into the STRUCT column.
+                    let n_geom = bind_data_inner
+                        .column_specs
+                        .first()
+                        .is_some_and(|s| s.column_type == ColumnType::Geometry)
+                        as usize;
+                    let struct_attributes = bind_data_inner.struct_attributes;
+                    let mut property_vectors: Vec<FlatVector> =
+                        (0..n_geom).map(|i| output.flat_vector(col(i))).collect();
+                    property_vectors.extend(attribute_vectors(
+                        output,
+                        n_geom,
+                        n_props - n_geom,
+                        struct_attributes,
+                        col,
+                    ));
+                    let n_prop_columns =
+                        n_geom + n_attribute_columns(n_props - n_geom, struct_attributes);
+
+                    let source_vectors = if bind_data_inner.geometry_only {
+                        None
+                    } else {
+                        Some((
+                            output.flat_vector(col(n_prop_columns)),
+                            output.flat_vector(col(n_prop_columns + 1)),
+                        ))
+                    };
+
+                    // .last_change and the extent
+                    let mut layer_metadata_vectors: Vec<FlatVector> =
+                        if bind_data_inner.include_layer_metadata {
+                            (0..5)
+                                .map(|i| output.flat_vector(col(n_prop_columns + 2 + i)))
+                                .collect()
+                        } else {
+                            vec![]
+                        };
+
+                    let mut mtime_vector = if bind_data_inner.include_mtime {
+                        Some(output.flat_vector(col(n_columns - 1)))
+                    } else {
+                        None
+                    };
+
+                    let source = &bind_data_inner.sources[chunk.source_idx];
+                    let filename = relative_filename(
+                        &source.path,
+                        bind_data_inner.filename_relative_to.as_deref(),
+                    );
+                    let layer_label = if bind_data_inner.qualify_layer_with_filename {
+                        Cow::Owned(format!("{filename}:{}", source.layer_name))
+                    } else {
+                        Cow::Borrowed(source.layer_name.as_str())
+                    };
+                    let mut conn = init_data.take_gpkg_connection(
+                        &source.path,
+                        bind_data_inner.max_open_files,
+                        bind_data_inner.immutable,
+                    )?;
+
+                    // The columns that don't exist in this data source (only when
+                    // `union_by_name` is specified)
+                    let missing_columns: Vec<usize> = (0..n_props)
+                        .filter(|i| !source.column_indices.contains(i))
+                        .collect();
+
+                    let result = conn.fetch_rows(&source.sql, chunk.range(), |row, i: usize| {
+                        let row_idx = n_filled + i;
+                        // the geometry column comes first
+                        if let Some(bbox) = &source.bbox {
+                            let encoding = bind_data_inner.geometry_column_encoding;
+                            if !geometry_intersects_bbox(row.get_ref(0)?, encoding, bbox) {
+                                return Ok(false);
+                            }
+                        }
+                        if let Some(filter) = &bind_data_inner.spatial_filter {
+                            let encoding = bind_data_inner.geometry_column_encoding;
+                            if !geometry_intersects_filter(row.get_ref(0)?, encoding, filter) {
+                                return Ok(false);
+                            }
+                        }
 
-                    if let Some(properties) = &f.properties {
-                        for (prop_idx, spec) in bind_data_inner.column_specs.iter().enumerate() {
-                            let val = properties.get(&spec.name);
-
-                            match val {
-                                Some(v) if !v.is_null() => {
-                                    match spec.column_type {
-                                        // Varchar needs insert()
-                                        ColumnType::Varchar => {
-                                            property_vectors[prop_idx]
-                                                .insert(row_idx, v.as_str().unwrap());
-                                        }
-                                        ColumnType::Boolean => unsafe {
-                                            property_vectors[prop_idx].as_mut_slice()[row_idx] =
-                                                v.as_bool().unwrap();
-                                        },
-                                        ColumnType::Double => unsafe {
-                                            property_vectors[prop_idx].as_mut_slice()[row_idx] =
-                                                v.as_f64().unwrap();
-                                        },
-                                        // JSON doesn't have integer type.
-                                        _ => unreachable!(),
+                        if bind_data_inner.distinct {
+                            // hash the values in the order of the output columns
+                            let mut hasher = DefaultHasher::new();
+                            for i in 0..n_props {
+                                match source.column_indices.iter().position(|&c| c == i) {
+                                    Some(col_idx) => {
+                                        hash_value_ref(row.get_ref(col_idx)?, &mut hasher)
+                                    }
+                                    None => {
+                                        hash_value_ref(rusqlite::types::ValueRef::Null, &mut hasher)
                                     }
                                 }
-                                _ => {
-                                    // Handle NULL or missing values
-                                    property_vectors[prop_idx].set_null(row_idx);
-                                }
+                            }
+                            if init_data.is_duplicate(hasher.finish()) {
+                                return Ok(false);
                             }
                         }
-                    }
-
-                    row_idx += 1;
-                }
+                        if !init_data.is_in_page(bind_data) {
+                            return Ok(false);
+                        }
 
-                if last {
-                    cursor.source_idx += 1;
-                    cursor.offset = 0;
-                } else {
-                    cursor.offset += VECTOR_SIZE;
-                }
+                        // Insert filename
+                        if let Some((filename_vector, layer_name_vector)) = &source_vectors {
+                            filename_vector.insert(row_idx, filename.as_ref());
+                            layer_name_vector.insert(row_idx, layer_label.as_ref());
+                        }
+                        if let Some((last_change_vector, extent_vectors)) =
+                            layer_metadata_vectors.split_first_mut()
+                        {
+                            let contents = &source.contents;
+                            write_timestamp(last_change_vector, row_idx, contents.last_change);
+                            let extent = [
+                                contents.min_x,
+                                contents.min_y,
+                                contents.max_x,
+                                contents.max_y,
+                            ];
+                            for (vector, v) in extent_vectors.iter_mut().zip(extent) {
+                                match v {
+                                    Some(v) => unsafe { vector.as_mut_slice()[row_idx] = v },
+                                    None => vector.set_null(row_idx),
+                                }
+                            }
+                        }
+                        if let Some(mtime_vector) = &mut mtime_vector {
+                            write_timestamp(mtime_vector, row_idx, source.mtime);
+                        }
 
-                output.set_len(row_idx);
-                return Ok(());
-            }
+                        for &i in &missing_columns {
+                            property_vectors[i].set_null(row_idx);
+                        }
 
-            // ==================== //
-            //     Gpkg             //
-            // ==================== //
-            StReadMultiBindData::Gpkg(bind_data_inner) => {
-                // If there's no remaining data source, tell DuckDB it's over.
-                if cursor.source_idx >= bind_data_inner.sources.len() {
-                    output.set_len(0);
-                    return Ok(());
-                }
+                        for (col_idx, spec) in source.column_specs.iter().enumerate() {
+                            let vector = &mut property_vectors[source.column_indices[col_idx]];
 
-                let n_props = bind_data_inner.column_specs.len();
-                let mut property_vectors: Vec<FlatVector> =
-                    (0..n_props).map(|i| output.flat_vector(i)).collect();
-
-                let filename_vector = output.flat_vector(n_props);
-                let layer_name_vector = output.flat_vector(n_props + 1);
-
-                // Note: This for loop is a bit tricky. This is necessary to let this function
-                // return non-empty result, otherwise DuckDB would assume the query is done.
-                for source in &bind_data_inner.sources[cursor.source_idx..] {
-                    let mut conn = source.gpkg.conn.lock().unwrap();
-
-                    let row_count =
-                        conn.fetch_rows(&source.sql, cursor.offset, |row, row_idx: usize| {
-                            // Insert filename
-                            filename_vector.insert(row_idx, source.gpkg.path.as_str());
-                            layer_name_vector.insert(row_idx, source.layer_name.as_str());
-
-                            for (col_idx, spec) in source.column_specs.iter().enumerate() {
-                                match &spec.column_type {
-                                    ColumnType::Integer => {
-                                        let val: Option<i64> = row.get(col_idx)?;
-                                        match val {
-                                            Some(v) => unsafe {
-                                                property_vectors[col_idx].as_mut_slice()[row_idx] =
-                                                    v as i32
-                                            },
-                                            None => property_vectors[col_idx].set_null(row_idx),
-                                        }
+                            match &spec.column_type {
+                                ColumnType::Integer => {
+                                    let val: Option<i64> = row.get(col_idx)?;
+                                    match val {
+                                        Some(v) => unsafe {
+                                            vector.as_mut_slice()[row_idx] = v as i32
+                                        },
+                                        None => vector.set_null(row_idx),
                                     }
-                                    ColumnType::Double => {
-                                        let val: Option<f64> = row.get(col_idx)?;
-                                        match val {
-                                            Some(v) => unsafe {
-                                                property_vectors[col_idx].as_mut_slice()[row_idx] =
-                                                    v
-                                            },
-                                            None => property_vectors[col_idx].set_null(row_idx),
-                                        }
+                                }
+                                ColumnType::BigInt => {
+                                    let val: Option<i64> = row.get(col_idx)?;
+                                    match val {
+                                        Some(v) => unsafe { vector.as_mut_slice()[row_idx] = v },
+                                        None => vector.set_null(row_idx),
                                     }
-                                    ColumnType::Varchar => {
-                                        let val: Option<String> = row.get(col_idx)?;
-                                        match val {
-                                            Some(v) => property_vectors[col_idx]
-                                                .insert(row_idx, v.as_str()),
-                                            None => property_vectors[col_idx].set_null(row_idx),
-                                        }
+                                }
+                                ColumnType::Double => {
+                                    let val: Option<f64> = row.get(col_idx)?;
+                                    match val {
+                                        Some(v) => unsafe { vector.as_mut_slice()[row_idx] = v },
+                                        None => vector.set_null(row_idx),
                                     }
-                                    ColumnType::Boolean => {
-                                        let val: Option<bool> = row.get(col_idx)?;
-                                        match val {
-                                            Some(v) => unsafe {
-                                                property_vectors[col_idx].as_mut_slice()[row_idx] =
-                                                    v
-                                            },
-                                            None => property_vectors[col_idx].set_null(row_idx),
-                                        }
+                                }
+                                // GeoPackage doesn't produce REAL columns (FLOAT and REAL are both
+                                // 8-byte), but handle it for completeness.
+                                ColumnType::Real => {
+                                    let val: Option<f64> = row.get(col_idx)?;
+                                    match val {
+                                        Some(v) => unsafe {
+                                            vector.as_mut_slice()[row_idx] = v as f32
+                                        },
+                                        None => vector.set_null(row_idx),
+                                    }
+                                }
+                                ColumnType::Varchar => {
+                                    let val: Option<String> = row.get(col_idx)?;
+                                    match val {
+                                        Some(v) => vector.insert(row_idx, v.as_str()),
+                                        None => vector.set_null(row_idx),
+                                    }
+                                }
+                                ColumnType::Boolean => {
+                                    let val: Option<bool> = row.get(col_idx)?;
+                                    match val {
+                                        Some(v) => unsafe { vector.as_mut_slice()[row_idx] = v },
+                                        None => vector.set_null(row_idx),
+                                    }
+                                }
+                                ColumnType::Blob => {
+                                    let val: Option<Vec<u8>> = row.get(col_idx)?;
+                                    match val {
+                                        Some(v) => vector.insert(row_idx, &v),
+                                        None => vector.set_null(row_idx),
                                     }
-                                    ColumnType::Blob => {
-                                        let val: Option<Vec<u8>> = row.get(col_idx)?;
-                                        match val {
-                                            Some(v) => {
-                                                property_vectors[col_idx].insert(row_idx, &v)
+                                }
+                                ColumnType::Geometry => {
+                                    let encoding = bind_data_inner.geometry_column_encoding;
+                                    // GeoJSON is converted to WKB first so that both the
+                                    // encodings are processed in the same way.
+                                    let (val, sql_type) = match encoding {
+                                        GeometryColumnEncoding::Gpkg
+                                        | GeometryColumnEncoding::Auto => (
+                                            row.get::<_, Option<Vec<u8>>>(col_idx)?,
+                                            rusqlite::types::Type::Blob,
+                                        ),
+                                        GeometryColumnEncoding::GeoJson => {
+                                            let val: Option<String> = row.get(col_idx)?;
+                                            let wkb = val
+                                                .map(|v| {
+                                                    geojson_geometry_to_wkb(
+                                                        &v, None, None, None, None,
+                                                    )
+                                                })
+                                                .transpose()
+                                                .map_err(|e| {
+                                                    rusqlite::Error::FromSqlConversionFailure(
+                                                        col_idx,
+                                                        rusqlite::types::Type::Text,
+                                                        e,
+                                                    )
+                                                })?;
+                                            (wkb, rusqlite::types::Type::Text)
+                                        }
+                                    };
+                                    let to_sql_error = |e: Box<dyn Error + Send + Sync>| {
+                                        rusqlite::Error::FromSqlConversionFailure(
+                                            col_idx,
+                                            sql_type.clone(),
+                                            e,
+                                        )
+                                    };
+
+                                    let raw_wkb: Option<Cow<[u8]>> = match &val {
+                                        Some(v) if encoding == GeometryColumnEncoding::Gpkg => {
+                                            Some(Cow::Borrowed(gpkg_geometry_to_wkb(v)))
+                                        }
+                                        Some(v) if encoding == GeometryColumnEncoding::Auto => {
+                                            Some(blob_to_wkb(v).map_err(&to_sql_error)?)
+                                        }
+                                        Some(v) => Some(Cow::Borrowed(v.as_slice())),
+                                        None => None,
+                                    };
+                                    let wkb = match &raw_wkb {
+                                        Some(wkb) => apply_collection_mode_wkb(
+                                            wkb,
+                                            bind_data_inner.collection_mode,
+                                        )
+                                        .map_err(&to_sql_error)?,
+                                        None => None,
+                                    };
+
+                                    match (wkb, bind_data_inner.coordinate_precision) {
+                                        (Some(wkb), precision) if bind_data_inner.centroid => {
+                                            match centroid_wkb(&wkb, precision)
+                                                .map_err(&to_sql_error)?
+                                            {
+                                                Some(wkb) => insert_geometry(
+                                                    vector,
+                                                    row_idx,
+                                                    &wkb,
+                                                    bind_data_inner.geometry_format,
+                                                    bind_data_inner.strict,
+                                                )
+                                                .map_err(&to_sql_error)?,
+                                                None => set_null_geometry(
+                                                    vector,
+                                                    row_idx,
+                                                    bind_data_inner.strict,
+                                                    &source.path,
+                                                )
+                                                .map_err(&to_sql_error)?,
                                             }
-                                            None => property_vectors[col_idx].set_null(row_idx),
                                         }
-                                    }
-                                    ColumnType::Geometry => {
-                                        let val: Option<Vec<u8>> = row.get(col_idx)?;
-                                        match val {
-                                            Some(v) => property_vectors[col_idx]
-                                                .insert(row_idx, gpkg_geometry_to_wkb(&v)),
-                                            None => property_vectors[col_idx].set_null(row_idx),
+                                        (Some(wkb), None)
+                                            if !bind_data_inner.force_2d
+                                                && bind_data_inner.winding.is_none()
+                                                && bind_data_inner.buffer_distance.is_none()
+                                                && bind_data_inner.max_segment_length.is_none() =>
+                                        {
+                                            insert_geometry(
+                                                vector,
+                                                row_idx,
+                                                &wkb,
+                                                bind_data_inner.geometry_format,
+                                                bind_data_inner.strict,
+                                            )
+                                            .map_err(&to_sql_error)?
                                         }
+                                        (Some(wkb), precision) => {
+                                            let wkb = rewrite_wkb(
+                                                &wkb,
+                                                bind_data_inner.buffer_distance,
+                                                bind_data_inner.max_segment_length,
+                                                precision,
+                                                bind_data_inner.winding,
+                                            )
+                                            .map_err(&to_sql_error)?;
+                                            insert_geometry(
+                                                vector,
+                                                row_idx,
+                                                &wkb,
+                                                bind_data_inner.geometry_format,
+                                                bind_data_inner.strict,
+                                            )
+                                            .map_err(&to_sql_error)?
+                                        }
+                                        (None, _) => set_null_geometry(
+                                            vector,
+                                            row_idx,
+                                            bind_data_inner.strict,
+                                            &source.path,
+                                        )
+                                        .map_err(&to_sql_error)?,
                                     }
-                                    ColumnType::Date => {
-                                        let val: Option<String> = row.get(col_idx)?;
-                                        match val {
-                                            Some(v) => unsafe {
-                                                property_vectors[col_idx]
-                                                    .as_mut_slice::<duckdb_date>()[row_idx] =
-                                                    duckdb_date {
-                                                        days: gpkg::parse_date_to_unix_days(&v),
-                                                    };
+                                }
+                                ColumnType::Date => {
+                                    let val: Option<String> = row.get(col_idx)?;
+                                    match val {
+                                        Some(v) => match gpkg::parse_date_to_unix_days(&v) {
+                                            Some(days) => unsafe {
+                                                vector.as_mut_slice::<duckdb_date>()[row_idx] =
+                                                    duckdb_date { days };
                                             },
-                                            None => property_vectors[col_idx].set_null(row_idx),
-                                        }
+                                            None => set_null_unparsable(
+                                                vector,
+                                                row_idx,
+                                                bind_data_inner.strict,
+                                                spec,
+                                                &v,
+                                                &source.path,
+                                            )
+                                            .map_err(|e| {
+                                                rusqlite::Error::FromSqlConversionFailure(
+                                                    col_idx,
+                                                    rusqlite::types::Type::Text,
+                                                    e,
+                                                )
+                                            })?,
+                                        },
+                                        None => vector.set_null(row_idx),
                                     }
-                                    ColumnType::Timestamp => {
-                                        let val: Option<String> = row.get(col_idx)?;
-                                        match val {
-                                            Some(v) => unsafe {
-                                                property_vectors[col_idx]
-                                                    .as_mut_slice::<duckdb_timestamp>()[row_idx] =
-                                                    duckdb_timestamp {
-                                                        micros: gpkg::parse_datetime_to_unix_micros(
-                                                            &v,
-                                                        ),
-                                                    };
+                                }
+                                // Note: DATETIME is always TimestampTz for GeoPackage, but the
+                                // values are in UTC anyway, so both can be handled in the same way.
+                                ColumnType::Timestamp | ColumnType::TimestampTz => {
+                                    let val: Option<String> = row.get(col_idx)?;
+                                    match val {
+                                        Some(v) => match gpkg::parse_datetime_to_utc_micros(&v) {
+                                            Some(micros) => unsafe {
+                                                vector.as_mut_slice::<duckdb_timestamp>()
+                                                    [row_idx] = duckdb_timestamp { micros };
                                             },
-                                            None => property_vectors[col_idx].set_null(row_idx),
-                                        }
+                                            None => set_null_unparsable(
+                                                vector,
+                                                row_idx,
+                                                bind_data_inner.strict,
+                                                spec,
+                                                &v,
+                                                &source.path,
+                                            )
+                                            .map_err(|e| {
+                                                rusqlite::Error::FromSqlConversionFailure(
+                                                    col_idx,
+                                                    rusqlite::types::Type::Text,
+                                                    e,
+                                                )
+                                            })?,
+                                        },
+                                        None => vector.set_null(row_idx),
                                     }
                                 }
                             }
+                        }
 
-                            Ok(())
-                        })?;
+                        Ok(true)
+                    });
 
-                    match row_count {
-                        // This is a special case. While we want to just return the result,
-                        // to DuckDB, 0-row result means it's finished. But, we don't want
-                        // to finish as long as there's any remaining data sources.
-                        0 => {
-                            cursor.source_idx += 1;
-                            cursor.offset = 0;
+                    // put back the connection before propagating the error
+                    init_data.put_back_gpkg_connection(conn);
+                    n_filled += result?;
+                }
+
+                // ==================== //
+                //     Shapefile        //
+                // ==================== //
+                StReadMultiBindData::Shapefile(bind_data_inner) => {
+                    // standalone .dbf files don't have the geometry column
+                    let (mut geom_vector, col_offset) = if bind_data_inner.has_geometry {
+                        (Some(output.flat_vector(col(0))), 1)
+                    } else {
+                        (None, 0)
+                    };
+                    let n_props = bind_data_inner.column_specs.len();
+                    let struct_attributes = bind_data_inner.struct_attributes;
+                    let mut property_vectors =
+                        attribute_vectors(output, col_offset, n_props, struct_attributes, col);
+                    let n_attr_columns = n_attribute_columns(n_props, struct_attributes);
+                    let filename_vector = if bind_data_inner.geometry_only {
+                        None
+                    } else {
+                        Some(output.flat_vector(col(n_attr_columns + col_offset)))
+                    };
+                    let mut encoding_vector = if bind_data_inner.include_encoding {
+                        Some(output.flat_vector(col(n_attr_columns + col_offset + 1)))
+                    } else {
+                        None
+                    };
+                    let mut metadata_vectors = if bind_data_inner.include_metadata {
+                        let i = n_attr_columns
+                            + col_offset
+                            + 1
+                            + bind_data_inner.include_encoding as usize;
+                        Some((output.flat_vector(col(i)), output.flat_vector(col(i + 1))))
+                    } else {
+                        None
+                    };
+                    let mut is_deleted_vector = if bind_data_inner.include_deleted {
+                        let i = n_attr_columns
+                            + col_offset
+                            + 1
+                            + bind_data_inner.include_encoding as usize
+                            + 2 * bind_data_inner.include_metadata as usize;
+                        Some(output.flat_vector(col(i)))
+                    } else {
+                        None
+                    };
+                    let raw_bytes_vector = if bind_data_inner.include_raw_bytes {
+                        let i = n_attr_columns
+                            + col_offset
+                            + 1
+                            + bind_data_inner.include_encoding as usize
+                            + 2 * bind_data_inner.include_metadata as usize
+                            + bind_data_inner.include_deleted as usize;
+                        Some(output.flat_vector(col(i)))
+                    } else {
+                        None
+                    };
 
-                            if cursor.source_idx < bind_data_inner.sources.len() {
+                    let mut mtime_vector = if bind_data_inner.include_mtime {
+                        Some(output.flat_vector(col(n_columns - 1)))
+                    } else {
+                        None
+                    };
+
+                    let mut row_idx = n_filled;
+                    let source = &bind_data_inner.sources[chunk.source_idx];
+                    let filename = relative_filename(
+                        &source.filename,
+                        bind_data_inner.filename_relative_to.as_deref(),
+                    );
+                    let rows = source.read_rows(chunk.range())?;
+
+                    for row in &rows {
+                        if let Some(filter) = &bind_data_inner.spatial_filter {
+                            if !row
+                                .geometry
+                                .as_deref()
+                                .is_some_and(|w| filter.intersects_wkb(w))
+                            {
+                                continue;
+                            }
+                        }
+                        if bind_data_inner.distinct {
+                            let mut hasher = DefaultHasher::new();
+                            row.geometry.hash(&mut hasher);
+                            for spec in &bind_data_inner.column_specs {
+                                // FieldValue doesn't implement Hash
+                                format!("{:?}", row.record.get(&spec.name)).hash(&mut hasher);
+                            }
+                            if init_data.is_duplicate(hasher.finish()) {
                                 continue;
                             }
                         }
-                        // return the current result and proceed to the next data source
-                        1..VECTOR_SIZE => {
-                            cursor.source_idx += 1;
-                            cursor.offset = 0;
+                        if !init_data.is_in_page(bind_data) {
+                            continue;
+                        }
+
+                        if let Some(geom_vector) = &mut geom_vector {
+                            let geometry = coerce_geometry(
+                                row.geometry.as_deref(),
+                                bind_data_inner.geometry_type,
+                                bind_data_inner.on_geometry_type_mismatch,
+                                &source.filename,
+                            )?;
+                            match (geometry.as_deref(), bind_data_inner.coordinate_precision) {
+                                (Some(wkb_data), precision) if bind_data_inner.centroid => {
+                                    match centroid_wkb(wkb_data, precision)
+                                        .map_err(|e| -> Box<dyn Error> { e })?
+                                    {
+                                        Some(wkb) => insert_geometry(
+                                            geom_vector,
+                                            row_idx,
+                                            &wkb,
+                                            bind_data_inner.geometry_format,
+                                            bind_data_inner.strict,
+                                        )
+                                        .map_err(|e| -> Box<dyn Error> { e })?,
+                                        None => set_null_geometry(
+                                            &mut geom_vector,
+                                            row_idx,
+                                            bind_data_inner.strict,
+                                            &source.filename,
+                                        )
+                                        .map_err(|e| -> Box<dyn Error> { e })?,
+                                    }
+                                }
+                                (Some(wkb_data), None)
+                                    if !bind_data_inner.force_2d
+                                        && bind_data_inner.winding.is_none()
+                                        && bind_data_inner.buffer_distance.is_none()
+                                        && bind_data_inner.max_segment_length.is_none() =>
+                                {
+                                    insert_geometry(
+                                        geom_vector,
+                                        row_idx,
+                                        wkb_data,
+                                        bind_data_inner.geometry_format,
+                                        bind_data_inner.strict,
+                                    )
+                                    .map_err(|e| -> Box<dyn Error> { e })?
+                                }
+                                (Some(wkb_data), precision) => {
+                                    let wkb = rewrite_wkb(
+                                        wkb_data,
+                                        bind_data_inner.buffer_distance,
+                                        bind_data_inner.max_segment_length,
+                                        precision,
+                                        bind_data_inner.winding,
+                                    )
+                                    .map_err(|e| -> Box<dyn Error> { e })?;
+                                    insert_geometry(
+                                        geom_vector,
+                                        row_idx,
+                                        &wkb,
+                                        bind_data_inner.geometry_format,
+                                        bind_data_inner.strict,
+                                    )
+                                    .map_err(|e| -> Box<dyn Error> { e })?
+                                }
+                                (None, _) => set_null_geometry(
+                                    &mut geom_vector,
+                                    row_idx,
+                                    bind_data_inner.strict,
+                                    &source.filename,
+                                )
+                                .map_err(|e| -> Box<dyn Error> { e })?,
+                            }
+                        }
+                        if let Some(filename_vector) = &filename_vector {
+                            filename_vector.insert(row_idx, filename.as_ref());
+                        }
+                        if let Some(mtime_vector) = &mut mtime_vector {
+                            write_timestamp(mtime_vector, row_idx, source.mtime);
+                        }
+                        if let Some(encoding_vector) = &mut encoding_vector {
+                            match &source.encoding {
+                                Some(encoding) => {
+                                    encoding_vector.insert(row_idx, encoding.as_str())
+                                }
+                                None => encoding_vector.set_null(row_idx),
+                            }
+                        }
+                        if let Some((title_vector, abstract_vector)) = &mut metadata_vectors {
+                            let metadata = source.metadata.as_ref();
+                            match metadata.and_then(|m| m.title.as_deref()) {
+                                Some(title) => title_vector.insert(row_idx, title),
+                                None => title_vector.set_null(row_idx),
+                            }
+                            match metadata.and_then(|m| m.abstract_text.as_deref()) {
+                                Some(abstract_text) => {
+                                    abstract_vector.insert(row_idx, abstract_text)
+                                }
+                                None => abstract_vector.set_null(row_idx),
+                            }
+                        }
+                        if let Some(is_deleted_vector) = &mut is_deleted_vector {
+                            unsafe { is_deleted_vector.as_mut_slice()[row_idx] = row.is_deleted };
                         }
-                        // return the current result and continue on the current data source
-                        VECTOR_SIZE => {
-                            cursor.offset += row_count;
+                        if let Some(raw_bytes_vector) = &raw_bytes_vector {
+                            let raw_bytes = row.raw_bytes.as_deref().unwrap_or_default();
+                            raw_bytes_vector.insert(row_idx, raw_bytes);
+                        }
+
+                        for (prop_idx, spec) in bind_data_inner.column_specs.iter().enumerate() {
+                            let val = row.record.get(&spec.name);
+
+                            use ::shapefile::dbase::FieldValue;
+                            match (spec.column_type, val) {
+                                (ColumnType::Varchar, Some(FieldValue::Character(Some(v)))) => {
+                                    property_vectors[prop_idx].insert(row_idx, v.as_str());
+                                }
+                                (ColumnType::Varchar, Some(FieldValue::Memo(v))) => {
+                                    property_vectors[prop_idx].insert(row_idx, v.as_str());
+                                }
+                                (ColumnType::Boolean, Some(FieldValue::Logical(Some(v)))) => unsafe {
+                                    property_vectors[prop_idx].as_mut_slice()[row_idx] = *v;
+                                },
+                                // 0/1 of a numeric field (cf. `boolean_columns`). The
+                                // other values are NULL.
+                                (ColumnType::Boolean, Some(FieldValue::Numeric(Some(v))))
+                                    if *v == 0.0 || *v == 1.0 =>
+                                unsafe {
+                                    property_vectors[prop_idx].as_mut_slice()[row_idx] = *v == 1.0;
+                                },
+                                (ColumnType::Boolean, Some(FieldValue::Integer(v)))
+                                    if *v == 0 || *v == 1 =>
+                                unsafe {
+                                    property_vectors[prop_idx].as_mut_slice()[row_idx] = *v == 1;
+                                },
+                                (ColumnType::Integer, Some(FieldValue::Integer(v))) => unsafe {
+                                    property_vectors[prop_idx].as_mut_slice()[row_idx] = *v;
+                                },
+                                (ColumnType::Double, Some(FieldValue::Numeric(Some(v)))) => unsafe {
+                                    property_vectors[prop_idx].as_mut_slice()[row_idx] = *v;
+                                },
+                                (ColumnType::Real, Some(FieldValue::Float(Some(v)))) => unsafe {
+                                    property_vectors[prop_idx].as_mut_slice::<f32>()[row_idx] = *v;
+                                },
+                                (ColumnType::Double, Some(FieldValue::Currency(v)))
+                                | (ColumnType::Double, Some(FieldValue::Double(v))) => unsafe {
+                                    property_vectors[prop_idx].as_mut_slice()[row_idx] = *v;
+                                },
+                                (ColumnType::Date, Some(FieldValue::Date(Some(v)))) => unsafe {
+                                    property_vectors[prop_idx].as_mut_slice::<duckdb_date>()
+                                        [row_idx] = duckdb_date {
+                                        days: v.to_unix_days(),
+                                    };
+                                },
+                                (ColumnType::Timestamp, Some(FieldValue::DateTime(v))) => unsafe {
+                                    property_vectors[prop_idx].as_mut_slice::<duckdb_timestamp>()
+                                        [row_idx] = duckdb_timestamp {
+                                        micros: datetime_to_unix_micros(v),
+                                    };
+                                },
+                                _ => {
+                                    property_vectors[prop_idx].set_null(row_idx);
+                                }
+                            }
                         }
-                        _ => unreachable!(),
+
+                        row_idx += 1;
                     }
 
-                    // return result and break this loop.
-                    output.set_len(row_count);
-                    return Ok(());
+                    n_filled = row_idx;
                 }
-            }
 
-            // ==================== //
-            //     Shapefile        //
-            // ==================== //
-            StReadMultiBindData::Shapefile(bind_data_inner) => {
-                if cursor.source_idx >= bind_data_inner.sources.len() {
-                    output.set_len(0);
-                    return Ok(());
-                }
+                // ==================== //
+                //     CSV              //
+                // ==================== //
+                StReadMultiBindData::Csv(bind_data_inner) => {
+                    let mut geom_vector = output.flat_vector(col(0));
+                    let n_props = bind_data_inner.column_specs.len();
+                    let struct_attributes = bind_data_inner.struct_attributes;
+                    let mut property_vectors =
+                        attribute_vectors(output, 1, n_props, struct_attributes, col);
+                    let n_attr_columns = n_attribute_columns(n_props, struct_attributes);
+                    let filename_vector = if bind_data_inner.geometry_only {
+                        None
+                    } else {
+                        Some(output.flat_vector(col(n_attr_columns + 1)))
+                    };
+
+                    let mut mtime_vector = if bind_data_inner.include_mtime {
+                        Some(output.flat_vector(col(n_columns - 1)))
+                    } else {
+                        None
+                    };
+
+                    let mut row_idx = n_filled;
+                    let mut buffer: Vec<u8> = Vec::new();
+                    let source = &bind_data_inner.sources[chunk.source_idx];
+                    let filename = relative_filename(
+                        &source.filename,
+                        bind_data_inner.filename_relative_to.as_deref(),
+                    );
+
+                    for row in &source.rows[chunk.range()] {
+                        // A point is the centroid of itself, so this matters only for
+                        // the cells of H3 or geohash.
+                        let geometry = match &row.geometry {
+                            Some(g) if bind_data_inner.centroid => g.centroid().map(Into::into),
+                            g => g.clone(),
+                        };
+                        let wkb = match geometry {
+                            Some(mut geometry) => {
+                                if bind_data_inner.coordinate_order == CoordinateOrder::LatLon {
+                                    swap_xy(&mut geometry);
+                                }
+                                if let Some(precision) = bind_data_inner.coordinate_precision {
+                                    round_coordinates(&mut geometry, precision);
+                                }
+                                buffer.clear();
+                                wkb::writer::write_geometry(
+                                    &mut buffer,
+                                    &geometry,
+                                    &Default::default(),
+                                )
+                                .map_err(|e| e.to_string())?;
+                                Some(buffer.as_slice())
+                            }
+                            None => None,
+                        };
 
-                let mut geom_vector = output.flat_vector(0);
-                let n_props = bind_data_inner.column_specs.len();
-                let mut property_vectors: Vec<FlatVector> =
-                    (0..n_props).map(|i| output.flat_vector(i + 1)).collect();
-                let filename_vector = output.flat_vector(n_props + 1);
+                        if let Some(filter) = &bind_data_inner.spatial_filter {
+                            if !wkb.is_some_and(|w| filter.intersects_wkb(w)) {
+                                continue;
+                            }
+                        }
+
+                        if bind_data_inner.distinct {
+                            let mut hasher = DefaultHasher::new();
+                            wkb.hash(&mut hasher);
+                            if !bind_data_inner.geometry_only {
+                                row.values.hash(&mut hasher);
+                            }
+                            if init_data.is_duplicate(hasher.finish()) {
+                                continue;
+                            }
+                        }
+                        if !init_data.is_in_page(bind_data) {
+                            continue;
+                        }
 
-                let mut row_idx: usize = 0;
-                let source = &bind_data_inner.sources[cursor.source_idx];
+                        match wkb {
+                            Some(wkb) => insert_geometry(
+                                &geom_vector,
+                                row_idx,
+                                wkb,
+                                bind_data_inner.geometry_format,
+                                bind_data_inner.strict,
+                            )
+                            .map_err(|e| -> Box<dyn Error> { e })?,
+                            None => set_null_geometry(
+                                &mut geom_vector,
+                                row_idx,
+                                bind_data_inner.strict,
+                                &source.filename,
+                            )
+                            .map_err(|e| -> Box<dyn Error> { e })?,
+                        }
+                        if let Some(filename_vector) = &filename_vector {
+                            filename_vector.insert(row_idx, filename.as_ref());
+                        }
+                        if let Some(mtime_vector) = &mut mtime_vector {
+                            write_timestamp(mtime_vector, row_idx, source.mtime);
+                        }
 
-                let range_end = std::cmp::min(cursor.offset + VECTOR_SIZE, source.rows.len());
-                let last = range_end >= source.rows.len();
-                let range = cursor.offset..range_end;
+                        for (prop_idx, spec) in bind_data_inner.column_specs.iter().enumerate() {
+                            write_text_value(
+                                &mut property_vectors[prop_idx],
+                                row_idx,
+                                spec,
+                                row.values[prop_idx].as_deref(),
+                                bind_data_inner.strict,
+                                &source.filename,
+                            )
+                            .map_err(|e| -> Box<dyn Error> { e })?;
+                        }
 
-                for row in &source.rows[range] {
-                    match &row.geometry {
-                        Some(wkb_data) => geom_vector.insert(row_idx, wkb_data.as_slice()),
-                        None => geom_vector.set_null(row_idx),
+                        row_idx += 1;
                     }
-                    filename_vector.insert(row_idx, source.filename.as_str());
 
-                    for (prop_idx, spec) in bind_data_inner.column_specs.iter().enumerate() {
-                        let val = row.record.get(&spec.name);
+                    n_filled = row_idx;
+                }
+
+                // ==================== //
+                //     MapInfo          //
+                // ==================== //
+                StReadMultiBindData::Mif(bind_data_inner) => {
+                    let mut geom_vector = output.flat_vector(col(0));
+                    let n_props = bind_data_inner.column_specs.len();
+                    let struct_attributes = bind_data_inner.struct_attributes;
+                    let mut property_vectors =
+                        attribute_vectors(output, 1, n_props, struct_attributes, col);
+                    let n_attr_columns = n_attribute_columns(n_props, struct_attributes);
+                    let filename_vector = if bind_data_inner.geometry_only {
+                        None
+                    } else {
+                        Some(output.flat_vector(col(n_attr_columns + 1)))
+                    };
 
-                        use ::shapefile::dbase::FieldValue;
-                        match (spec.column_type, val) {
-                            (ColumnType::Varchar, Some(FieldValue::Character(Some(v)))) => {
-                                property_vectors[prop_idx].insert(row_idx, v.as_str());
+                    let mut mtime_vector = if bind_data_inner.include_mtime {
+                        Some(output.flat_vector(col(n_columns - 1)))
+                    } else {
+                        None
+                    };
+
+                    let mut row_idx = n_filled;
+                    let source = &bind_data_inner.sources[chunk.source_idx];
+                    let filename = relative_filename(
+                        &source.filename,
+                        bind_data_inner.filename_relative_to.as_deref(),
+                    );
+
+                    for row in &source.rows[chunk.range()] {
+                        if let Some(filter) = &bind_data_inner.spatial_filter {
+                            if !row
+                                .geometry
+                                .as_deref()
+                                .is_some_and(|w| filter.intersects_wkb(w))
+                            {
+                                continue;
                             }
-                            (ColumnType::Varchar, Some(FieldValue::Memo(v))) => {
-                                property_vectors[prop_idx].insert(row_idx, v.as_str());
+                        }
+                        if bind_data_inner.distinct {
+                            let mut hasher = DefaultHasher::new();
+                            row.geometry.hash(&mut hasher);
+                            if !bind_data_inner.geometry_only {
+                                row.values.hash(&mut hasher);
                             }
-                            (ColumnType::Boolean, Some(FieldValue::Logical(Some(v)))) => unsafe {
-                                property_vectors[prop_idx].as_mut_slice()[row_idx] = *v;
-                            },
-                            (ColumnType::Integer, Some(FieldValue::Integer(v))) => unsafe {
-                                property_vectors[prop_idx].as_mut_slice()[row_idx] = *v;
-                            },
-                            (ColumnType::Double, Some(FieldValue::Numeric(Some(v)))) => unsafe {
-                                property_vectors[prop_idx].as_mut_slice()[row_idx] = *v;
-                            },
-                            (ColumnType::Double, Some(FieldValue::Float(Some(v)))) => unsafe {
-                                property_vectors[prop_idx].as_mut_slice()[row_idx] = *v as f64;
-                            },
-                            (ColumnType::Double, Some(FieldValue::Currency(v)))
-                            | (ColumnType::Double, Some(FieldValue::Double(v))) => unsafe {
-                                property_vectors[prop_idx].as_mut_slice()[row_idx] = *v;
-                            },
-                            (ColumnType::Date, Some(FieldValue::Date(Some(v)))) => unsafe {
-                                property_vectors[prop_idx].as_mut_slice::<duckdb_date>()[row_idx] =
-                                    duckdb_date {
-                                        days: v.to_unix_days(),
-                                    };
-                            },
-                            (ColumnType::Timestamp, Some(FieldValue::DateTime(v))) => unsafe {
-                                property_vectors[prop_idx].as_mut_slice::<duckdb_timestamp>()
-                                    [row_idx] = duckdb_timestamp {
-                                    micros: v.to_unix_timestamp() * 1_000_000,
-                                };
-                            },
-                            _ => {
-                                property_vectors[prop_idx].set_null(row_idx);
+                            if init_data.is_duplicate(hasher.finish()) {
+                                continue;
                             }
                         }
-                    }
+                        if !init_data.is_in_page(bind_data) {
+                            continue;
+                        }
 
-                    row_idx += 1;
-                }
+                        match (&row.geometry, bind_data_inner.coordinate_precision) {
+                            (Some(wkb_data), precision) if bind_data_inner.centroid => {
+                                match centroid_wkb(wkb_data, precision)
+                                    .map_err(|e| -> Box<dyn Error> { e })?
+                                {
+                                    Some(wkb) => insert_geometry(
+                                        &geom_vector,
+                                        row_idx,
+                                        &wkb,
+                                        bind_data_inner.geometry_format,
+                                        bind_data_inner.strict,
+                                    )
+                                    .map_err(|e| -> Box<dyn Error> { e })?,
+                                    None => set_null_geometry(
+                                        &mut geom_vector,
+                                        row_idx,
+                                        bind_data_inner.strict,
+                                        &source.filename,
+                                    )
+                                    .map_err(|e| -> Box<dyn Error> { e })?,
+                                }
+                            }
+                            (Some(wkb_data), None)
+                                if bind_data_inner.winding.is_none()
+                                    && bind_data_inner.buffer_distance.is_none()
+                                    && bind_data_inner.max_segment_length.is_none() =>
+                            {
+                                insert_geometry(
+                                    &geom_vector,
+                                    row_idx,
+                                    wkb_data,
+                                    bind_data_inner.geometry_format,
+                                    bind_data_inner.strict,
+                                )
+                                .map_err(|e| -> Box<dyn Error> { e })?
+                            }
+                            (Some(wkb_data), precision) => {
+                                let wkb = rewrite_wkb(
+                                    wkb_data,
+                                    bind_data_inner.buffer_distance,
+                                    bind_data_inner.max_segment_length,
+                                    precision,
+                                    bind_data_inner.winding,
+                                )
+                                .map_err(|e| -> Box<dyn Error> { e })?;
+                                insert_geometry(
+                                    &geom_vector,
+                                    row_idx,
+                                    &wkb,
+                                    bind_data_inner.geometry_format,
+                                    bind_data_inner.strict,
+                                )
+                                .map_err(|e| -> Box<dyn Error> { e })?
+                            }
+                            (None, _) => set_null_geometry(
+                                &mut geom_vector,
+                                row_idx,
+                                bind_data_inner.strict,
+                                &source.filename,
+                            )
+                            .map_err(|e| -> Box<dyn Error> { e })?,
+                        }
+                        if let Some(filename_vector) = &filename_vector {
+                            filename_vector.insert(row_idx, filename.as_ref());
+                        }
+                        if let Some(mtime_vector) = &mut mtime_vector {
+                            write_timestamp(mtime_vector, row_idx, source.mtime);
+                        }
 
-                if last {
-                    cursor.source_idx += 1;
-                    cursor.offset = 0;
-                } else {
-                    cursor.offset += VECTOR_SIZE;
-                }
+                        for (prop_idx, spec) in bind_data_inner.column_specs.iter().enumerate() {
+                            write_text_value(
+                                &mut property_vectors[prop_idx],
+                                row_idx,
+                                spec,
+                                row.values[prop_idx].as_deref(),
+                                bind_data_inner.strict,
+                                &source.filename,
+                            )
+                            .map_err(|e| -> Box<dyn Error> { e })?;
+                        }
 
-                output.set_len(row_idx);
-                return Ok(());
+                        row_idx += 1;
+                    }
+
+                    n_filled = row_idx;
+                }
             }
-        }
 
+            Ok(n_filled)
+        })?;
+        output.set_len(n_filled);
         Ok(())
     }
 
@@ -539,6 +3201,113 @@ impl VTab for StReadMultiVTab {
         Some(vec![
             ("layer".into(), LogicalTypeId::Varchar.into()),
             ("encoding".into(), LogicalTypeId::Varchar.into()),
+            ("preserve_order".into(), LogicalTypeId::Boolean.into()),
+            ("include_encoding".into(), LogicalTypeId::Boolean.into()),
+            ("max_open_files".into(), LogicalTypeId::Integer.into()),
+            ("immutable".into(), LogicalTypeId::Boolean.into()),
+            ("wal_mode".into(), LogicalTypeId::Varchar.into()),
+            ("prefetch".into(), LogicalTypeId::Boolean.into()),
+            (
+                "qualify_layer_with_filename".into(),
+                LogicalTypeId::Boolean.into(),
+            ),
+            ("follow_relations".into(), LogicalTypeId::Boolean.into()),
+            ("geometry_column".into(), LogicalTypeId::Varchar.into()),
+            (
+                "enforce_geometry_type".into(),
+                LogicalTypeId::Varchar.into(),
+            ),
+            (
+                "geometry_column_encoding".into(),
+                LogicalTypeId::Varchar.into(),
+            ),
+            (
+                "exclude".into(),
+                LogicalTypeHandle::list(&LogicalTypeId::Varchar.into()),
+            ),
+            (
+                "include_foreign_members".into(),
+                LogicalTypeId::Boolean.into(),
+            ),
+            ("coordinate_order".into(), LogicalTypeId::Varchar.into()),
+            ("coordinate_precision".into(), LogicalTypeId::Integer.into()),
+            ("schema_from_first".into(), LogicalTypeId::Boolean.into()),
+            (
+                "report_all_mismatches".into(),
+                LogicalTypeId::Boolean.into(),
+            ),
+            ("dry_run".into(), LogicalTypeId::Boolean.into()),
+            ("union_by_name".into(), LogicalTypeId::Boolean.into()),
+            (
+                "include_layer_metadata".into(),
+                LogicalTypeId::Boolean.into(),
+            ),
+            ("geometry_only".into(), LogicalTypeId::Boolean.into()),
+            ("include_crs".into(), LogicalTypeId::Boolean.into()),
+            ("chunk_size".into(), LogicalTypeId::Integer.into()),
+            ("include_metadata".into(), LogicalTypeId::Boolean.into()),
+            ("force_2d".into(), LogicalTypeId::Boolean.into()),
+            ("latitude_column".into(), LogicalTypeId::Varchar.into()),
+            ("h3_column".into(), LogicalTypeId::Varchar.into()),
+            ("geohash_column".into(), LogicalTypeId::Varchar.into()),
+            ("longitude_column".into(), LogicalTypeId::Varchar.into()),
+            ("distinct".into(), LogicalTypeId::Boolean.into()),
+            ("strict".into(), LogicalTypeId::Boolean.into()),
+            ("filter_wkt".into(), LogicalTypeId::Varchar.into()),
+            ("include_deleted".into(), LogicalTypeId::Boolean.into()),
+            ("raw_bytes_for".into(), LogicalTypeId::Varchar.into()),
+            (
+                "boolean_columns".into(),
+                LogicalTypeHandle::list(&LogicalTypeId::Varchar.into()),
+            ),
+            (
+                "geometry_properties".into(),
+                LogicalTypeHandle::list(&LogicalTypeId::Varchar.into()),
+            ),
+            ("geometry_member".into(), LogicalTypeId::Varchar.into()),
+            ("json_pointer".into(), LogicalTypeId::Varchar.into()),
+            ("max_depth".into(), LogicalTypeId::Integer.into()),
+            ("include_mtime".into(), LogicalTypeId::Boolean.into()),
+            ("filename_relative_to".into(), LogicalTypeId::Varchar.into()),
+            ("modified_after".into(), LogicalTypeId::Varchar.into()),
+            (
+                "coerce_numeric_strings".into(),
+                LogicalTypeId::Boolean.into(),
+            ),
+            ("numbers_as_double".into(), LogicalTypeId::Boolean.into()),
+            ("geometry_position".into(), LogicalTypeId::Varchar.into()),
+            ("on_type_mismatch".into(), LogicalTypeId::Varchar.into()),
+            ("format".into(), LogicalTypeId::Varchar.into()),
+            ("offset".into(), LogicalTypeId::Bigint.into()),
+            ("struct_attributes".into(), LogicalTypeId::Boolean.into()),
+            ("centroid".into(), LogicalTypeId::Boolean.into()),
+            ("geometry_format".into(), LogicalTypeId::Varchar.into()),
+            ("twkb_precision".into(), LogicalTypeId::Integer.into()),
+            ("winding".into(), LogicalTypeId::Varchar.into()),
+            ("buffer_distance".into(), LogicalTypeId::Double.into()),
+            (
+                "densify_max_segment_length".into(),
+                LogicalTypeId::Double.into(),
+            ),
+            ("collection_mode".into(), LogicalTypeId::Varchar.into()),
+            ("geometry_type".into(), LogicalTypeId::Varchar.into()),
+            (
+                "on_geometry_type_mismatch".into(),
+                LogicalTypeId::Varchar.into(),
+            ),
+            (
+                "include_columns".into(),
+                LogicalTypeHandle::list(&LogicalTypeId::Varchar.into()),
+            ),
+            (
+                "exclude_columns".into(),
+                LogicalTypeHandle::list(&LogicalTypeId::Varchar.into()),
+            ),
+            (
+                "bbox".into(),
+                LogicalTypeHandle::list(&LogicalTypeId::Double.into()),
+            ),
+            ("limit".into(), LogicalTypeId::Bigint.into()),
         ])
     }
 }
@@ -547,7 +3316,32 @@ const EXTENSION_NAME: &str = env!("CARGO_PKG_NAME");
 
 #[duckdb_entrypoint_c_api()]
 pub unsafe fn extension_entrypoint(con: Connection) -> Result<(), Box<dyn Error>> {
-    con.register_table_function::<StReadMultiVTab>(EXTENSION_NAME)
+    con.register_table_function::<StReadMultiVTab<AutoDetect>>(EXTENSION_NAME)
         .expect("Failed to register StReadMulti table function");
+    con.register_table_function::<StReadMultiVTab<ForceGeoJson>>(FUNCTION_NAME_GEOJSON)
+        .expect("Failed to register StReadGeoJson table function");
+    con.register_table_function::<StReadMultiVTab<ForceGpkg>>(FUNCTION_NAME_GPKG)
+        .expect("Failed to register StReadGpkg table function");
+    con.register_table_function::<StReadMultiVTab<ForceShapefile>>(FUNCTION_NAME_SHAPEFILE)
+        .expect("Failed to register StReadShp table function");
+    con.register_table_function::<StReadMultiWarningsVTab>(FUNCTION_NAME_WARNINGS)
+        .expect("Failed to register StReadMultiWarnings table function");
+
+    // The connection is held by the functions, which are in turn held by the
+    // database, so the database is kept alive until the process exits even after
+    // all the other connections are closed. The C API doesn't provide a way to get a
+    // connection from the ClientContext of the query, so this can't be avoided as
+    // long as the functions run a query by themselves.
+    let writer_conn: WriterConnection = Arc::new(Mutex::new(con.try_clone()?));
+    con.register_table_function_with_extra_info::<StWriteGeoJsonVTab, _>(
+        FUNCTION_NAME_WRITE_GEOJSON,
+        &writer_conn,
+    )
+    .expect("Failed to register StWriteGeoJson table function");
+    con.register_table_function_with_extra_info::<StReadMultiSelftestVTab, _>(
+        FUNCTION_NAME_SELFTEST,
+        &writer_conn,
+    )
+    .expect("Failed to register StReadMultiSelftest table function");
     Ok(())
 }
@@ -0,0 +1,470 @@
+use std::path::Path;
+
+use geo_types::{
+    Coord, Geometry, LineString, MultiLineString, MultiPoint, MultiPolygon, Point, Polygon,
+};
+
+use crate::{
+    diagnostics::warn,
+    types::{ColumnSpec, ColumnType},
+};
+
+// The keywords that start a graphic object in the DATA section of a MIF file.
+// The other lines (e.g. "Pen (1,2,0)") are the continuation of the previous one.
+//
+// cf. https://github.com/OSGeo/gdal/blob/master/ogr/ogrsf_frmts/mitab/mitab_miffile.cpp
+const OBJECT_KEYWORDS: &[&str] = &[
+    "none",
+    "point",
+    "line",
+    "pline",
+    "region",
+    "multipoint",
+    "rect",
+    "roundrect",
+    "ellipse",
+    "arc",
+    "text",
+    "collection",
+];
+
+#[repr(C)]
+pub struct MifRow {
+    pub geometry: Option<Vec<u8>>,
+    // The raw values in the MID file. An empty field is None.
+    pub values: Vec<Option<String>>,
+}
+
+#[repr(C)]
+pub struct MifDataSource {
+    pub rows: Vec<MifRow>,
+    pub filename: String,
+    pub column_specs: Vec<ColumnSpec>,
+    // The modification time of the file (only when `include_mtime` is specified)
+    pub mtime: Option<i64>,
+}
+
+struct MifHeader {
+    encoding: &'static encoding_rs::Encoding,
+    delimiter: u8,
+    column_specs: Vec<ColumnSpec>,
+}
+
+impl MifDataSource {
+    // Read a MIF file and the corresponding MID file. The MID file is optional when
+    // there are no attribute columns.
+    pub(crate) fn new<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let filename = path.to_string_lossy().into_owned();
+
+        let mif_bytes = std::fs::read(path)?;
+        // The header is ASCII, so it's safe to read it before knowing the charset.
+        let header = parse_header(&String::from_utf8_lossy(&mif_bytes))?;
+        let (mif, _, _) = header.encoding.decode(&mif_bytes);
+
+        let data = match find_data_section(&mif) {
+            Some(data) => data,
+            None => {
+                return Err(format!("No DATA section in {}", filename.replace('\\', "/")).into())
+            }
+        };
+        let geometries = split_objects(data)
+            .into_iter()
+            .map(|tokens| parse_object(&tokens))
+            .collect::<Result<Vec<Option<Geometry>>, _>>()?;
+
+        let mid_path = ["mid", "MID"]
+            .iter()
+            .map(|ext| path.with_extension(ext))
+            .find(|p| p.exists());
+        let records: Vec<Vec<Option<String>>> = match mid_path {
+            Some(mid_path) => {
+                let mid_bytes = std::fs::read(mid_path)?;
+                let (mid, _, _) = header.encoding.decode(&mid_bytes);
+                let mut reader = ::csv::ReaderBuilder::new()
+                    .has_headers(false)
+                    .delimiter(header.delimiter)
+                    .flexible(true)
+                    .from_reader(mid.as_bytes());
+                reader
+                    .records()
+                    .map(|r| {
+                        r.map(|r| {
+                            (0..header.column_specs.len())
+                                .map(|i| r.get(i).map(|v| v.trim()).filter(|v| !v.is_empty()))
+                                .map(|v| v.map(String::from))
+                                .collect()
+                        })
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+            None if header.column_specs.is_empty() => vec![Vec::new(); geometries.len()],
+            None => {
+                return Err(
+                    format!("MID file is not found for {}", filename.replace('\\', "/")).into(),
+                )
+            }
+        };
+
+        if records.len() != geometries.len() {
+            return Err(format!(
+                "The number of objects in MIF ({}) doesn't match the number of records in MID ({}): {}",
+                geometries.len(),
+                records.len(),
+                filename.replace('\\', "/")
+            )
+            .into());
+        }
+
+        let rows = geometries
+            .into_iter()
+            .zip(records)
+            .map(|(geometry, values)| {
+                let geometry = match geometry {
+                    Some(geometry) => {
+                        let mut wkb = Vec::new();
+                        wkb::writer::write_geometry(&mut wkb, &geometry, &Default::default())
+                            .map_err(|e| e.to_string())?;
+                        Some(wkb)
+                    }
+                    None => None,
+                };
+                Ok(MifRow { geometry, values })
+            })
+            .collect::<Result<Vec<MifRow>, Box<dyn std::error::Error>>>()?;
+
+        Ok(Self {
+            rows,
+            filename,
+            column_specs: header.column_specs,
+            mtime: None,
+        })
+    }
+}
+
+fn parse_header(mif: &str) -> Result<MifHeader, Box<dyn std::error::Error>> {
+    let mut encoding = encoding_rs::WINDOWS_1252;
+    // TAB is the default delimiter
+    let mut delimiter = b'\t';
+    let mut column_specs: Vec<ColumnSpec> = Vec::new();
+
+    let mut lines = mif.lines().map(str::trim).filter(|l| !l.is_empty());
+    while let Some(line) = lines.next() {
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match keyword.to_lowercase().as_str() {
+            "charset" => encoding = charset_to_encoding(rest.trim().trim_matches('"')),
+            "delimiter" => {
+                let d = rest.trim().trim_matches('"');
+                delimiter = *d
+                    .as_bytes()
+                    .first()
+                    .ok_or("Empty delimiter in MIF header")?;
+            }
+            "columns" => {
+                let n: usize = rest.trim().parse()?;
+                for _ in 0..n {
+                    let line = lines
+                        .next()
+                        .ok_or("Unexpected end of the column definitions")?;
+                    let (name, column_type) = line
+                        .split_once(char::is_whitespace)
+                        .ok_or_else(|| format!("Invalid column definition: {line}"))?;
+                    column_specs.push(ColumnSpec {
+                        name: name.to_string(),
+                        column_type: mif_type_to_column_type(column_type.trim()),
+                    });
+                }
+            }
+            "data" => break,
+            _ => {}
+        }
+    }
+
+    Ok(MifHeader {
+        encoding,
+        delimiter,
+        column_specs,
+    })
+}
+
+// cf. https://github.com/OSGeo/gdal/blob/master/ogr/ogrsf_frmts/mitab/mitab_utils.cpp
+fn charset_to_encoding(charset: &str) -> &'static encoding_rs::Encoding {
+    let label = match charset.to_lowercase().as_str() {
+        "windowslatin2" => "windows-1250",
+        "windowscyrillic" => "windows-1251",
+        "windowsgreek" => "windows-1253",
+        "windowsturkish" => "windows-1254",
+        "windowshebrew" => "windows-1255",
+        "windowsarabic" => "windows-1256",
+        "windowsbalticrim" => "windows-1257",
+        "windowsvietnamese" => "windows-1258",
+        "windowsthai" => "windows-874",
+        "windowsjapanese" => "Shift_JIS",
+        "windowssimpchinese" => "GBK",
+        "windowstradchinese" => "Big5",
+        "windowskorean" => "EUC-KR",
+        "utf-8" => "UTF-8",
+        _ => "windows-1252",
+    };
+    encoding_rs::Encoding::for_label(label.as_bytes()).unwrap_or(encoding_rs::WINDOWS_1252)
+}
+
+fn mif_type_to_column_type(mif_type: &str) -> ColumnType {
+    let mif_type = mif_type.to_lowercase();
+    let name = mif_type.split('(').next().unwrap_or_default().trim();
+    match name {
+        "integer" | "smallint" => ColumnType::Integer,
+        // There's no 64-bit integer type in ColumnType
+        "largeint" | "float" | "decimal" => ColumnType::Double,
+        "logical" => ColumnType::Boolean,
+        "date" => ColumnType::Date,
+        "datetime" => ColumnType::Timestamp,
+        // char, time, and the others
+        _ => ColumnType::Varchar,
+    }
+}
+
+// MID stores DATE as "YYYYMMDD". Returns the days since Unix epoch.
+pub(crate) fn parse_mid_date(s: &str) -> Option<i32> {
+    let date = chrono::NaiveDate::parse_from_str(s, "%Y%m%d").ok()?;
+    Some((date - chrono::NaiveDate::default()).num_days() as i32)
+}
+
+// MID stores DATETIME as "YYYYMMDDhhmmssmmm". Returns the microseconds since Unix
+// epoch.
+pub(crate) fn parse_mid_datetime(s: &str) -> Option<i64> {
+    let dt = chrono::NaiveDateTime::parse_from_str(s, "%Y%m%d%H%M%S%3f").ok()?;
+    Some(dt.and_utc().timestamp_micros())
+}
+
+fn find_data_section(mif: &str) -> Option<&str> {
+    let mut offset = 0;
+    for line in mif.split_inclusive('\n') {
+        offset += line.len();
+        if line.trim().eq_ignore_ascii_case("data") {
+            return Some(&mif[offset..]);
+        }
+    }
+    None
+}
+
+// Split the DATA section into the tokens of each object.
+fn split_objects(data: &str) -> Vec<Vec<&str>> {
+    let mut objects: Vec<Vec<&str>> = Vec::new();
+    for line in data.lines() {
+        let mut tokens = line.split_whitespace().peekable();
+        let Some(first) = tokens.peek() else {
+            continue;
+        };
+
+        if OBJECT_KEYWORDS.contains(&first.to_lowercase().as_str()) {
+            objects.push(Vec::new());
+        }
+        if let Some(object) = objects.last_mut() {
+            object.extend(tokens);
+        }
+    }
+    objects
+}
+
+struct Tokens<'a, 'b> {
+    tokens: std::slice::Iter<'b, &'a str>,
+}
+
+impl Tokens<'_, '_> {
+    fn number(&mut self) -> Result<f64, Box<dyn std::error::Error>> {
+        let token = self.tokens.next().ok_or("Unexpected end of MIF object")?;
+        Ok(token.parse()?)
+    }
+
+    fn count(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
+        let token = self.tokens.next().ok_or("Unexpected end of MIF object")?;
+        Ok(token.parse()?)
+    }
+
+    fn coord(&mut self) -> Result<Coord, Box<dyn std::error::Error>> {
+        Ok(Coord {
+            x: self.number()?,
+            y: self.number()?,
+        })
+    }
+
+    fn coords(&mut self) -> Result<Vec<Coord>, Box<dyn std::error::Error>> {
+        let n = self.count()?;
+        (0..n).map(|_| self.coord()).collect()
+    }
+}
+
+// Parse the tokens of an object. Only point, line, polyline, region, and multipoint
+// are supported. The other objects are read as NULL.
+fn parse_object(tokens: &[&str]) -> Result<Option<Geometry>, Box<dyn std::error::Error>> {
+    let keyword = tokens[0].to_lowercase();
+    let mut t = Tokens {
+        tokens: tokens[1..].iter(),
+    };
+
+    let geometry: Geometry = match keyword.as_str() {
+        "none" => return Ok(None),
+        "point" => Point(t.coord()?).into(),
+        "line" => LineString::new(vec![t.coord()?, t.coord()?]).into(),
+        "pline" => {
+            if tokens
+                .get(1)
+                .is_some_and(|s| s.eq_ignore_ascii_case("multiple"))
+            {
+                t.tokens.next();
+                let n_sections = t.count()?;
+                let lines = (0..n_sections)
+                    .map(|_| t.coords().map(LineString::new))
+                    .collect::<Result<Vec<_>, _>>()?;
+                MultiLineString::new(lines).into()
+            } else {
+                LineString::new(t.coords()?).into()
+            }
+        }
+        "region" => {
+            let n_rings = t.count()?;
+            let rings = (0..n_rings)
+                .map(|_| t.coords().map(LineString::new))
+                .collect::<Result<Vec<_>, _>>()?;
+            region_to_geometry(rings)
+        }
+        "multipoint" => MultiPoint::new(t.coords()?.into_iter().map(Point).collect()).into(),
+        "collection" => return Err("Collection objects in MIF are not supported".into()),
+        _ => {
+            warn!("Unsupported MIF object '{}' is read as NULL", tokens[0]);
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(geometry))
+}
+
+// A region is a set of rings, which doesn't tell which ring is a hole. Here, a ring
+// inside another (non-hole) ring is considered as a hole of it.
+fn region_to_geometry(rings: Vec<LineString>) -> Geometry {
+    let mut polygons: Vec<(LineString, Vec<LineString>)> = Vec::new();
+    // A ring without vertices is skipped
+    for mut ring in rings.into_iter().filter(|r| !r.0.is_empty()) {
+        ring.close();
+        let first = ring.0[0];
+        match polygons
+            .iter_mut()
+            .find(|(exterior, _)| contains_coord(exterior, first))
+        {
+            Some((_, interiors)) => interiors.push(ring),
+            None => polygons.push((ring, Vec::new())),
+        }
+    }
+
+    let mut polygons: Vec<Polygon> = polygons
+        .into_iter()
+        .map(|(exterior, interiors)| Polygon::new(exterior, interiors))
+        .collect();
+    if polygons.len() == 1 {
+        polygons.remove(0).into()
+    } else {
+        MultiPolygon::new(polygons).into()
+    }
+}
+
+// Ray casting
+fn contains_coord(ring: &LineString, c: Coord) -> bool {
+    let mut inside = false;
+    for line in ring.lines() {
+        let (a, b) = (line.start, line.end);
+        if (a.y > c.y) != (b.y > c.y) && c.x < (b.x - a.x) * (c.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::ColumnType;
+
+    #[test]
+    fn test_mif() -> Result<(), Box<dyn std::error::Error>> {
+        let source = super::MifDataSource::new("./test/data/mapinfo/features.mif")?;
+
+        let names: Vec<&str> = source
+            .column_specs
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["name", "val", "day"]);
+        assert_eq!(source.column_specs[0].column_type, ColumnType::Varchar);
+        assert_eq!(source.column_specs[1].column_type, ColumnType::Double);
+        assert_eq!(source.column_specs[2].column_type, ColumnType::Date);
+
+        assert_eq!(source.rows.len(), 5);
+        assert_eq!(source.rows[0].values[0].as_deref(), Some("café"));
+        assert_eq!(source.rows[4].geometry, None);
+        assert_eq!(source.rows[4].values[1], None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_mid_date() {
+        assert_eq!(super::parse_mid_date("19700102"), Some(1));
+        assert_eq!(super::parse_mid_date("20240101"), Some(19723));
+        assert_eq!(super::parse_mid_date("2024"), None);
+        assert_eq!(
+            super::parse_mid_datetime("19700101000001500"),
+            Some(1_500_000)
+        );
+    }
+
+    #[test]
+    fn test_parse_object() -> Result<(), Box<dyn std::error::Error>> {
+        use geo_types::{line_string, point, polygon, Geometry};
+
+        let parse = |s: &str| {
+            let objects = super::split_objects(s);
+            assert_eq!(objects.len(), 1);
+            super::parse_object(&objects[0])
+        };
+
+        assert_eq!(
+            parse("Point 1 2\n    Symbol (35,0,12)")?,
+            Some(Geometry::Point(point!(x: 1.0, y: 2.0)))
+        );
+        assert_eq!(
+            parse("Pline 3\n0 0\n1 1\n2 0\n    Pen (1,2,0)")?,
+            Some(Geometry::LineString(
+                line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 1.0), (x: 2.0, y: 0.0)]
+            ))
+        );
+
+        // the second ring is a hole of the first one
+        let region = "Region 2\n  4\n0 0\n10 0\n10 10\n0 10\n  4\n1 1\n2 1\n2 2\n1 2\n    Brush (2,16777215,16777215)";
+        assert_eq!(
+            parse(region)?,
+            Some(Geometry::Polygon(polygon!(
+                exterior: [(x: 0.0, y: 0.0), (x: 10.0, y: 0.0), (x: 10.0, y: 10.0), (x: 0.0, y: 10.0)],
+                interiors: [[(x: 1.0, y: 1.0), (x: 2.0, y: 1.0), (x: 2.0, y: 2.0), (x: 1.0, y: 2.0)]],
+            )))
+        );
+
+        // a ring without vertices is skipped
+        assert_eq!(
+            parse("Region 2\n  0\n  3\n0 0\n1 0\n0 1")?,
+            Some(Geometry::Polygon(polygon![
+                (x: 0.0, y: 0.0),
+                (x: 1.0, y: 0.0),
+                (x: 0.0, y: 1.0),
+            ]))
+        );
+        assert_eq!(
+            parse("Region 1\n  0")?,
+            Some(Geometry::MultiPolygon(geo_types::MultiPolygon::new(vec![])))
+        );
+
+        assert_eq!(parse("None")?, None);
+        assert_eq!(parse("Ellipse 0 0 1 1")?, None);
+
+        Ok(())
+    }
+}
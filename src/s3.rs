@@ -0,0 +1,141 @@
+use std::error::Error;
+
+use futures::TryStreamExt;
+use object_store::{aws::AmazonS3Builder, path::Path as ObjectPath, ObjectStore};
+
+const S3_SCHEME: &str = "s3://";
+
+pub(crate) fn is_s3_url(path: &str) -> bool {
+    path.starts_with(S3_SCHEME)
+}
+
+// Split "s3://<bucket>/<key>" into the bucket and the key.
+pub(crate) fn parse_s3_url(url: &str) -> Result<(&str, &str), Box<dyn Error>> {
+    let rest = url
+        .strip_prefix(S3_SCHEME)
+        .ok_or_else(|| format!("Not an S3 URL: {url}"))?;
+    match rest.split_once('/') {
+        Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => Ok((bucket, key)),
+        _ => Err(format!("S3 URL must be 's3://<bucket>/<key>': {url}").into()),
+    }
+}
+
+// The longest directory of the key without any wildcards. The objects under this
+// are listed to match against the pattern.
+fn list_prefix(pattern: &str) -> &str {
+    match pattern.find(['*', '?', '[']) {
+        Some(i) => pattern[..i].rfind('/').map_or("", |j| &pattern[..j]),
+        None => pattern,
+    }
+}
+
+// Expand the S3 URL to the URLs of the objects. If the key contains wildcards, the
+// objects are listed and matched against it in the same way as the local files.
+// Otherwise, the URL is returned as it is without accessing S3.
+pub(crate) fn expand_s3_url(url: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let (bucket, key) = parse_s3_url(url)?;
+    if !key.contains(['*', '?', '[']) {
+        return Ok(vec![url.to_string()]);
+    }
+
+    let pattern = glob::Pattern::new(key)?;
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    let prefix = Some(list_prefix(key))
+        .filter(|p| !p.is_empty())
+        .map(ObjectPath::from);
+
+    let store = build_store(bucket)?;
+    let objects = runtime()?
+        .block_on(store.list(prefix.as_ref()).try_collect::<Vec<_>>())
+        .map_err(|e| describe_error(url, e))?;
+
+    let mut urls: Vec<String> = objects
+        .iter()
+        .map(|o| o.location.as_ref())
+        .filter(|k| pattern.matches_with(k, options))
+        .map(|k| format!("{S3_SCHEME}{bucket}/{k}"))
+        .collect();
+    urls.sort();
+
+    Ok(urls)
+}
+
+// Fetch the whole object into memory.
+pub(crate) fn get_object(url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (bucket, key) = parse_s3_url(url)?;
+    let location = ObjectPath::from(key);
+
+    let store = build_store(bucket)?;
+    let bytes = runtime()?
+        .block_on(async { store.get(&location).await?.bytes().await })
+        .map_err(|e| describe_error(url, e))?;
+
+    Ok(bytes.to_vec())
+}
+
+// The credentials and the region are read from the environment variables (e.g.
+// AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY, AWS_REGION, and AWS_ENDPOINT).
+fn build_store(bucket: &str) -> Result<impl ObjectStore, Box<dyn Error>> {
+    Ok(AmazonS3Builder::from_env()
+        .with_bucket_name(bucket)
+        .build()?)
+}
+
+// The table function is not async, so run a runtime just for each request.
+fn runtime() -> Result<tokio::runtime::Runtime, Box<dyn Error>> {
+    Ok(tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?)
+}
+
+fn describe_error(url: &str, e: object_store::Error) -> Box<dyn Error> {
+    match e {
+        object_store::Error::NotFound { .. } => format!("No such object: {url}").into(),
+        object_store::Error::PermissionDenied { .. } | object_store::Error::Unauthenticated { .. } => {
+            format!(
+                "Access denied to {url}. Please check the credentials (e.g. AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY): {e}"
+            )
+            .into()
+        }
+        e => format!("Failed to read {url}: {e}").into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_parse_s3_url() {
+        assert_eq!(
+            super::parse_s3_url("s3://bucket/data/points.geojson").unwrap(),
+            ("bucket", "data/points.geojson")
+        );
+        assert!(super::parse_s3_url("s3://bucket").is_err());
+        assert!(super::parse_s3_url("s3:///points.geojson").is_err());
+        assert!(super::parse_s3_url("data/points.geojson").is_err());
+    }
+
+    #[test]
+    fn test_list_prefix() {
+        assert_eq!(super::list_prefix("data/2024/*.geojson"), "data/2024");
+        assert_eq!(super::list_prefix("data/**/*.geojson"), "data");
+        assert_eq!(super::list_prefix("*.geojson"), "");
+        assert_eq!(
+            super::list_prefix("data/points.geojson"),
+            "data/points.geojson"
+        );
+    }
+
+    #[test]
+    fn test_expand_s3_url_without_wildcards() -> Result<(), Box<dyn std::error::Error>> {
+        // no request is sent to S3
+        assert_eq!(
+            super::expand_s3_url("s3://bucket/data/points.geojson")?,
+            vec!["s3://bucket/data/points.geojson"]
+        );
+
+        Ok(())
+    }
+}
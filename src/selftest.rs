@@ -0,0 +1,350 @@
+// `st_read_multi_selftest()` writes tiny fixtures of each supported format to a
+// temporary directory, reads them back with ST_Read_Multi, and reports whether
+// the result is as expected. This is for checking a build quickly, so each check
+// covers only the basics of the format.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use duckdb::{
+    core::{DataChunkHandle, LogicalTypeId},
+    types::ValueRef,
+    vtab::{BindInfo, InitInfo, TableFunctionInfo, VTab},
+    Connection,
+};
+
+use crate::writer::WriterConnection;
+
+type CheckResult = Result<(), Box<dyn std::error::Error>>;
+
+// A fixture is written to the directory, and the path and the expected rows are
+// returned. Every value is compared as a string ("NULL" for NULL), so the columns
+// of a check are cast to VARCHAR and the geometries to hex.
+type FixtureResult = Result<(PathBuf, Vec<Vec<String>>), Box<dyn std::error::Error>>;
+type Fixture = fn(&Path) -> FixtureResult;
+
+// name, columns to select, fixture
+const CHECKS: &[(&str, &str, Fixture)] = &[
+    (
+        "geojson_mixed_types",
+        "hex(geometry), i::VARCHAR, f::VARCHAR, s, b::VARCHAR",
+        geojson_mixed_types,
+    ),
+    (
+        "geojson_null_geometry",
+        "hex(geometry), s",
+        geojson_null_geometry,
+    ),
+    ("esrijson", "hex(geometry), s", esrijson),
+    ("gpkg", "hex(geometry), s, f::VARCHAR, \".layer\"", gpkg),
+    ("gpkg_3d", "hex(geometry)", gpkg_3d),
+    ("shapefile", "hex(geometry), s, f::VARCHAR", shapefile),
+    ("csv", "hex(geometry), s", csv),
+    ("mapinfo", "hex(geometry), s", mapinfo),
+];
+
+#[repr(C)]
+pub struct StReadMultiSelftestBindData {
+    conn: WriterConnection,
+}
+
+#[repr(C)]
+pub struct StReadMultiSelftestInitData {
+    done: AtomicBool,
+}
+
+pub(crate) struct StReadMultiSelftestVTab;
+
+impl VTab for StReadMultiSelftestVTab {
+    type InitData = StReadMultiSelftestInitData;
+    type BindData = StReadMultiSelftestBindData;
+
+    fn bind(bind: &BindInfo) -> Result<Self::BindData, Box<dyn std::error::Error>> {
+        let conn = unsafe { &*bind.get_extra_info::<WriterConnection>() }.clone();
+
+        bind.add_result_column("name", LogicalTypeId::Varchar.into());
+        bind.add_result_column("passed", LogicalTypeId::Boolean.into());
+        // the reason of the failure
+        bind.add_result_column("message", LogicalTypeId::Varchar.into());
+
+        Ok(StReadMultiSelftestBindData { conn })
+    }
+
+    fn init(_: &InitInfo) -> Result<Self::InitData, Box<dyn std::error::Error>> {
+        Ok(StReadMultiSelftestInitData {
+            done: AtomicBool::new(false),
+        })
+    }
+
+    fn func(
+        func: &TableFunctionInfo<Self>,
+        output: &mut DataChunkHandle,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let init_data = func.get_init_data();
+        let bind_data = func.get_bind_data();
+
+        if init_data.done.swap(true, Ordering::Relaxed) {
+            output.set_len(0);
+            return Ok(());
+        }
+
+        let dir =
+            std::env::temp_dir().join(format!("st_read_multi_selftest_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let conn = bind_data.conn.lock().map_err(|e| e.to_string())?;
+        let results: Vec<(&str, CheckResult)> = CHECKS
+            .iter()
+            .map(|(name, columns, fixture)| {
+                (*name, run_check(&conn, &dir.join(name), columns, *fixture))
+            })
+            .collect();
+        drop(conn);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let name_vector = output.flat_vector(0);
+        let mut passed_vector = output.flat_vector(1);
+        let mut message_vector = output.flat_vector(2);
+        for (row_idx, (name, result)) in results.iter().enumerate() {
+            name_vector.insert(row_idx, *name);
+            passed_vector.as_mut_slice::<bool>()[row_idx] = result.is_ok();
+            match result {
+                Ok(()) => message_vector.set_null(row_idx),
+                Err(e) => message_vector.insert(row_idx, e.to_string().as_str()),
+            }
+        }
+        output.set_len(results.len());
+
+        Ok(())
+    }
+}
+
+fn run_check(conn: &Connection, dir: &Path, columns: &str, fixture: Fixture) -> CheckResult {
+    std::fs::create_dir_all(dir)?;
+    let (path, mut expected) = fixture(dir)?;
+    // Both are sorted so that the result doesn't depend on the parallel scan
+    let query = format!(
+        "SELECT {columns} FROM st_read_multi('{}') ORDER BY ALL",
+        path.to_string_lossy().replace('\'', "''")
+    );
+    let actual = query_rows(conn, &query)?;
+    expected.sort();
+    if actual != expected {
+        return Err(format!("Expected {expected:?}, but got {actual:?}").into());
+    }
+    Ok(())
+}
+
+fn query_rows(
+    conn: &Connection,
+    query: &str,
+) -> Result<Vec<Vec<String>>, Box<dyn std::error::Error>> {
+    let mut stmt = conn.prepare(query)?;
+    let mut rows = stmt.query([])?;
+    let n_columns = rows
+        .as_ref()
+        .ok_or("Failed to execute the query")?
+        .column_count();
+
+    let mut result = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut values = Vec::with_capacity(n_columns);
+        for i in 0..n_columns {
+            let value = match row.get_ref(i)? {
+                ValueRef::Null => "NULL".to_string(),
+                ValueRef::Text(v) => String::from_utf8_lossy(v).into_owned(),
+                v => return Err(format!("Column {i} is not cast to VARCHAR: {v:?}").into()),
+            };
+            values.push(value);
+        }
+        result.push(values);
+    }
+    Ok(result)
+}
+
+// The WKB of the geometry in uppercase hex, which is what DuckDB's hex() returns
+fn wkb_hex(geometry: geo_types::Geometry) -> String {
+    let mut wkb = Vec::new();
+    wkb::writer::write_geometry(&mut wkb, &geometry, &Default::default()).unwrap();
+    hex(&wkb)
+}
+
+fn hex(b: &[u8]) -> String {
+    b.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+fn point(x: f64, y: f64) -> geo_types::Geometry {
+    geo_types::Point::new(x, y).into()
+}
+
+fn row(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+fn geojson_mixed_types(dir: &Path) -> FixtureResult {
+    let path = dir.join("points.geojson");
+    std::fs::write(
+        &path,
+        r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "properties": {"i": 1, "f": 1.5, "s": "a", "b": true}, "geometry": {"type": "Point", "coordinates": [1, 2]}},
+            {"type": "Feature", "properties": {"i": 2, "f": null, "s": "b", "b": false}, "geometry": {"type": "Point", "coordinates": [3, 4]}}
+        ]}"#,
+    )?;
+    let expected = vec![
+        row(&[&wkb_hex(point(1.0, 2.0)), "1", "1.5", "a", "true"]),
+        row(&[&wkb_hex(point(3.0, 4.0)), "2", "NULL", "b", "false"]),
+    ];
+    Ok((path, expected))
+}
+
+fn geojson_null_geometry(dir: &Path) -> FixtureResult {
+    let path = dir.join("null.geojson");
+    std::fs::write(
+        &path,
+        r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "properties": {"s": "a"}, "geometry": null}
+        ]}"#,
+    )?;
+    Ok((path, vec![row(&["NULL", "a"])]))
+}
+
+fn esrijson(dir: &Path) -> FixtureResult {
+    let path = dir.join("points.json");
+    std::fs::write(
+        &path,
+        r#"{"geometryType": "esriGeometryPoint", "spatialReference": {"wkid": 4326},
+            "fields": [{"name": "s", "type": "esriFieldTypeString"}],
+            "features": [{"attributes": {"s": "a"}, "geometry": {"x": 1, "y": 2}}]}"#,
+    )?;
+    Ok((path, vec![row(&[&wkb_hex(point(1.0, 2.0)), "a"])]))
+}
+
+// Create a GeoPackage with a single point layer. Only the tables that
+// ST_Read_Multi needs are created.
+fn write_gpkg(path: &Path, layer: &str, wkb: &[u8], attributes: bool) -> CheckResult {
+    // a GeoPackage binary header without envelope (little endian, SRS ID 4326)
+    let mut blob = vec![b'G', b'P', 0x00, 0x01];
+    blob.extend_from_slice(&4326i32.to_le_bytes());
+    blob.extend_from_slice(wkb);
+
+    let conn = rusqlite::Connection::open(path)?;
+    conn.execute_batch(&format!(
+        "CREATE TABLE gpkg_contents (table_name TEXT PRIMARY KEY, data_type TEXT, last_change DATETIME, min_x DOUBLE, min_y DOUBLE, max_x DOUBLE, max_y DOUBLE);
+         CREATE TABLE gpkg_geometry_columns (table_name TEXT, column_name TEXT, geometry_type_name TEXT);
+         INSERT INTO gpkg_contents (table_name, data_type) VALUES ('{layer}', 'features');
+         INSERT INTO gpkg_geometry_columns VALUES ('{layer}', 'geom', 'POINT');
+         CREATE TABLE {layer} (fid INTEGER PRIMARY KEY, geom POINT{});",
+        if attributes { ", s TEXT, f DOUBLE" } else { "" }
+    ))?;
+    if attributes {
+        conn.execute(
+            &format!("INSERT INTO {layer} (geom, s, f) VALUES (?1, 'a', 1.5)"),
+            [&blob],
+        )?;
+    } else {
+        conn.execute(&format!("INSERT INTO {layer} (geom) VALUES (?1)"), [&blob])?;
+    }
+    Ok(())
+}
+
+fn gpkg(dir: &Path) -> FixtureResult {
+    let path = dir.join("points.gpkg");
+    let mut wkb = Vec::new();
+    wkb::writer::write_geometry(&mut wkb, &point(1.0, 2.0), &Default::default())?;
+    write_gpkg(&path, "points", &wkb, true)?;
+
+    Ok((path, vec![row(&[&hex(&wkb), "a", "1.5", "points"])]))
+}
+
+fn gpkg_3d(dir: &Path) -> FixtureResult {
+    let path = dir.join("points_z.gpkg");
+    // POINT Z (1 2 3) in ISO WKB
+    let mut wkb = vec![0x01];
+    wkb.extend_from_slice(&1001u32.to_le_bytes());
+    for v in [1.0f64, 2.0, 3.0] {
+        wkb.extend_from_slice(&v.to_le_bytes());
+    }
+    write_gpkg(&path, "points_z", &wkb, false)?;
+
+    Ok((path, vec![row(&[&hex(&wkb)])]))
+}
+
+fn shapefile(dir: &Path) -> FixtureResult {
+    use shapefile::dbase::{FieldName, FieldValue, Record, TableWriterBuilder};
+
+    let path = dir.join("points.shp");
+    let table_builder = TableWriterBuilder::new()
+        .add_character_field(FieldName::try_from("s").map_err(|e| e.to_string())?, 10)
+        .add_numeric_field(FieldName::try_from("f").map_err(|e| e.to_string())?, 10, 2);
+    let mut writer = shapefile::Writer::from_path(&path, table_builder)?;
+    let mut record = Record::default();
+    record.insert(
+        "s".to_string(),
+        FieldValue::Character(Some("a".to_string())),
+    );
+    record.insert("f".to_string(), FieldValue::Numeric(Some(1.5)));
+    writer.write_shape_and_record(&shapefile::Point::new(1.0, 2.0), &record)?;
+    // the headers are written when the writer is dropped
+    drop(writer);
+
+    Ok((path, vec![row(&[&wkb_hex(point(1.0, 2.0)), "a", "1.5"])]))
+}
+
+fn csv(dir: &Path) -> FixtureResult {
+    let path = dir.join("points.csv");
+    std::fs::write(&path, "s,lon,lat\na,1,2\n")?;
+    Ok((path, vec![row(&[&wkb_hex(point(1.0, 2.0)), "a"])]))
+}
+
+fn mapinfo(dir: &Path) -> FixtureResult {
+    let path = dir.join("points.mif");
+    std::fs::write(
+        &path,
+        "Version 300\nCharset \"WindowsLatin1\"\nDelimiter \",\"\nCoordSys Earth Projection 1, 104\nColumns 1\n  s Char(10)\nData\n\nPoint 1 2\n",
+    )?;
+    std::fs::write(path.with_extension("mid"), "\"a\"\n")?;
+    Ok((path, vec![row(&[&wkb_hex(point(1.0, 2.0)), "a"])]))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Format;
+
+    #[test]
+    fn test_fixtures() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join("st_read_multi_test_selftest_fixtures");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // Every fixture is detected as the intended format
+        let expected_formats = [
+            Format::GeoJson,
+            Format::GeoJson,
+            Format::EsriJson,
+            Format::Gpkg,
+            Format::Gpkg,
+            Format::Shapefile,
+            Format::Csv,
+            Format::Mif,
+        ];
+        assert_eq!(super::CHECKS.len(), expected_formats.len());
+        for ((name, _, fixture), format) in super::CHECKS.iter().zip(expected_formats) {
+            let dir = dir.join(name);
+            std::fs::create_dir_all(&dir)?;
+            let (path, expected) = fixture(&dir)?;
+            assert_eq!(crate::utils::detect_format(&[path])?, format, "{name}");
+            assert!(!expected.is_empty());
+        }
+
+        // The GeoPackage is readable without the tables that are not needed
+        let gpkg = crate::gpkg::Gpkg::new(dir.join("gpkg_3d/points_z.gpkg"), None, false)?;
+        let sources = gpkg.list_data_sources(None, false, &Default::default(), None)?;
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].n_rows, 1);
+
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+// A `.shp.zip` file is a zip archive holding a single shapefile, the convention
+// popularized by GDAL. The files are extracted into a temporary directory because
+// the shapefile reader needs the files on disk.
+
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static N_EXTRACTED: AtomicUsize = AtomicUsize::new(0);
+
+// The temporary directory is removed when this is dropped, i.e. when the data
+// source is no longer used.
+pub(crate) struct ExtractedShapefile {
+    dir: PathBuf,
+    pub shp_path: PathBuf,
+}
+
+impl Drop for ExtractedShapefile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+impl ExtractedShapefile {
+    pub(crate) fn extract(zip_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut archive = zip::ZipArchive::new(File::open(zip_path)?)?;
+
+        let dir = std::env::temp_dir().join(format!(
+            "st_read_multi_{}_{}",
+            std::process::id(),
+            N_EXTRACTED.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)?;
+        // create this first so that the directory is removed on error
+        let mut extracted = Self {
+            dir,
+            shp_path: PathBuf::new(),
+        };
+
+        // The files might be in a subdirectory in the archive, but they are put
+        // flat as only one shapefile is expected.
+        let mut shp_paths: Vec<PathBuf> = Vec::new();
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            if entry.is_dir() {
+                continue;
+            }
+            let Some(file_name) = entry
+                .enclosed_name()
+                .and_then(|p| p.file_name().map(PathBuf::from))
+            else {
+                continue;
+            };
+
+            let path = extracted.dir.join(file_name);
+            std::io::copy(&mut entry, &mut File::create(&path)?)?;
+            if path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("shp"))
+            {
+                shp_paths.push(path);
+            }
+        }
+
+        let zip_path = zip_path.to_string_lossy().replace('\\', "/");
+        match shp_paths.len() {
+            1 => {
+                extracted.shp_path = shp_paths.remove(0);
+                Ok(extracted)
+            }
+            0 => Err(format!("No .shp file is found in {zip_path}").into()),
+            _ => Err(format!("{zip_path} contains multiple .shp files").into()),
+        }
+    }
+}
@@ -1,70 +1,480 @@
-use std::path::Path;
+use std::{
+    io::{BufReader, Read, Seek, SeekFrom},
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
-use crate::types::{ColumnSpec, ColumnType};
+use crate::{
+    diagnostics::warn,
+    types::{ColumnSpec, ColumnType},
+    utils::is_shp_zip,
+};
 
-use super::encoding::infer_encoding_from_cpg;
+use super::archive::ExtractedShapefile;
+use super::encoding::{
+    infer_encoding_from_cpg, parse_encoding_label, sniff_dbf_encoding, EncodingOption,
+};
+use super::geometry::shape_to_wkb;
+use super::metadata::Metadata;
 
 #[repr(C)]
 pub struct ShapefileRow {
     pub geometry: Option<Vec<u8>>,
     pub record: ::shapefile::dbase::Record,
+    // true if the record is marked as deleted in the DBF file
+    pub is_deleted: bool,
+    // The bytes of the field before decoding (only when `raw_bytes_for` is
+    // specified)
+    pub raw_bytes: Option<Vec<u8>>,
 }
 
+// The records are not read on bind. Only the deletion flags are kept in memory,
+// and the records are read per chunk by read_rows().
 #[repr(C)]
 pub struct ShapefileDataSource {
-    pub rows: Vec<ShapefileRow>,
+    // The .shp file, or the .dbf file if it's a standalone one
+    path: PathBuf,
+    has_geometry: bool,
+    // true if the record is marked as deleted in the DBF file
+    deletion_flags: Vec<bool>,
+    // The offset of each record in the .shp file, in 16-bit words as in the .shx
+    // file. This is empty for a standalone .dbf file.
+    shp_offsets: Vec<u32>,
+    // The indices of the records to read. None if all the records are read.
+    record_indices: Option<Vec<usize>>,
+    // The offset and the length of the field whose raw bytes are read along with
+    // the rows (cf. set_raw_bytes_field())
+    raw_field: Option<(usize, usize)>,
+    // The files extracted from a `.shp.zip` file. `path` points to the .shp file in
+    // it. This is kept just to remove the files when the data source is dropped.
+    _extracted: Option<ExtractedShapefile>,
     pub filename: String,
     pub column_specs: Vec<ColumnSpec>,
+    // The label of the encoding used to decode the attributes. None if it's
+    // determined by the LDID of the DBF file.
+    pub encoding: Option<String>,
+    // The metadata read from the sidecar file. This is populated only when it's
+    // requested.
+    pub metadata: Option<Metadata>,
+    // The modification time of the .shp (or .dbf) file. Same as above.
+    pub mtime: Option<i64>,
+}
+
+type DbfReader = ::shapefile::dbase::Reader<BufReader<std::fs::File>>;
+
+// Determine the label of the encoding to decode the attributes with. None means
+// it's determined by the LDID of the DBF file.
+fn resolve_encoding(
+    dbf_path: &Path,
+    user_encoding: Option<&EncodingOption>,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let label = match user_encoding {
+        Some(EncodingOption::Specified(label, _)) => Some(label.clone()),
+        Some(EncodingOption::Auto) => Some(sniff_dbf_encoding(dbf_path)?.to_string()),
+        None => infer_encoding_from_cpg(&dbf_path.with_extension("cpg")).map(|(label, _)| label),
+    };
+    Ok(label)
+}
+
+// Open the DBF file with the encoding. The label is resolved again here because
+// the DBF file is reopened for every chunk.
+fn open_dbf(
+    dbf_path: &Path,
+    encoding_label: Option<&str>,
+) -> Result<DbfReader, Box<dyn std::error::Error>> {
+    let encoding = match encoding_label {
+        Some(label) => {
+            Some(parse_encoding_label(label).ok_or_else(|| format!("Unknown encoding: {label}"))?)
+        }
+        None => None,
+    };
+
+    let dbf_reader = match encoding {
+        Some(encoding) => ::shapefile::dbase::Reader::from_path_with_encoding(dbf_path, encoding)?,
+        None => ::shapefile::dbase::Reader::from_path(dbf_path)?,
+    };
+    Ok(dbf_reader)
+}
+
+fn column_specs(dbf_reader: &DbfReader) -> Vec<ColumnSpec> {
+    let mut column_specs: Vec<ColumnSpec> = dbf_reader
+        .fields()
+        .iter()
+        .map(|field| ColumnSpec {
+            name: field.name().to_string(),
+            column_type: field.field_type().into(),
+        })
+        .collect();
+    column_specs.sort_by(|a, b| a.name.cmp(&b.name));
+    column_specs
+}
+
+// Read the number of records, the length of the header, and the length of a record
+// from the DBF header.
+//
+// cf. https://www.clicketyclick.dk/databases/xbase/format/dbf.html
+fn read_dbf_header(
+    reader: &mut BufReader<std::fs::File>,
+    dbf_path: &Path,
+) -> Result<(usize, usize, usize), Box<dyn std::error::Error>> {
+    let mut header = [0u8; 12];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| format!("Invalid DBF header: {}", dbf_path.to_string_lossy()))?;
+    let n_records = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+    let header_len = u16::from_le_bytes(header[8..10].try_into().unwrap()) as usize;
+    let record_len = u16::from_le_bytes(header[10..12].try_into().unwrap()) as usize;
+    Ok((n_records, header_len, record_len))
+}
+
+// Read the deletion flag of each record. The records are still stored in the file
+// after they are deleted; the first byte of a record is '*' if it's deleted, or ' '
+// otherwise.
+pub(crate) fn read_deletion_flags(
+    dbf_path: &Path,
+) -> Result<Vec<bool>, Box<dyn std::error::Error>> {
+    let mut reader = BufReader::new(std::fs::File::open(dbf_path)?);
+    let (n_records, header_len, record_len) = read_dbf_header(&mut reader, dbf_path)?;
+
+    // Read only the first byte of each record instead of the whole file. The
+    // relative seek keeps the buffer as long as the next record is in it.
+    reader.seek(SeekFrom::Start(header_len as u64))?;
+    let mut flags = Vec::with_capacity(n_records);
+    let mut flag = [0u8; 1];
+    for _ in 0..n_records {
+        if reader.read_exact(&mut flag).is_err() {
+            break;
+        }
+        flags.push(flag[0] == b'*');
+        reader.seek_relative(record_len as i64 - 1)?;
+    }
+    Ok(flags)
+}
+
+// Read the offsets of the records in the .shp file. They are read from the .shx
+// file if it exists. Otherwise, the record headers in the .shp file are followed,
+// which reads only 8 bytes per record.
+//
+// cf. https://www.esri.com/content/dam/esrisites/sitecore-archive/Files/Pdfs/library/whitepapers/pdfs/shapefile.pdf
+fn read_shp_offsets(shp_path: &Path) -> Result<Vec<u32>, Box<dyn std::error::Error>> {
+    // Both files start with the same 100-byte header. This fails if the file is
+    // not a shapefile.
+    let mut shp_reader = BufReader::new(std::fs::File::open(shp_path)?);
+    let header = ::shapefile::header::Header::read_from(&mut shp_reader)?;
+
+    // An entry of the .shx file is the offset and the content length of the
+    // record, in 16-bit words.
+    let shx_path = shp_path.with_extension("shx");
+    if shx_path.exists() {
+        let mut shx_reader = BufReader::new(std::fs::File::open(&shx_path)?);
+        let shx_header = ::shapefile::header::Header::read_from(&mut shx_reader)?;
+        let n_records = (shx_header.file_length as usize * 2).saturating_sub(100) / 8;
+        let mut offsets = Vec::with_capacity(n_records);
+        let mut entry = [0u8; 8];
+        for _ in 0..n_records {
+            if shx_reader.read_exact(&mut entry).is_err() {
+                break;
+            }
+            offsets.push(u32::from_be_bytes(entry[0..4].try_into().unwrap()));
+        }
+        return Ok(offsets);
+    }
+
+    // A record of the .shp file starts with the record number and the content
+    // length in 16-bit words.
+    let file_len = header.file_length as u64 * 2;
+    let mut offsets = Vec::new();
+    let mut pos = 100;
+    let mut record_header = [0u8; 8];
+    while pos + 8 <= file_len {
+        if shp_reader.read_exact(&mut record_header).is_err() {
+            break;
+        }
+        let content_len = u32::from_be_bytes(record_header[4..8].try_into().unwrap()) as u64;
+        offsets.push((pos / 2) as u32);
+        shp_reader.seek_relative(content_len as i64 * 2)?;
+        pos += 8 + content_len * 2;
+    }
+    Ok(offsets)
+}
+
+// Read the shape of the record at the offset (in 16-bit words). The reader is
+// moved relatively so that the buffer is kept when the records are read in order.
+fn read_shape_at(
+    reader: &mut BufReader<std::fs::File>,
+    offset: u32,
+) -> Result<::shapefile::Shape, ::shapefile::Error> {
+    use ::shapefile::ReadableShape;
+
+    let pos = reader.stream_position()?;
+    reader.seek_relative(offset as i64 * 2 - pos as i64)?;
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header)?;
+    let content_len = i32::from_be_bytes(header[4..8].try_into().unwrap());
+    ::shapefile::Shape::read_from(reader, content_len * 2)
+}
+
+// Read the bytes of a field directly from the DBF file, without decoding them.
+struct RawFieldReader {
+    reader: BufReader<std::fs::File>,
+    header_len: usize,
+    record_len: usize,
+    offset: usize,
+    len: usize,
+}
+
+impl RawFieldReader {
+    fn open(
+        dbf_path: &Path,
+        (offset, len): (usize, usize),
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut reader = BufReader::new(std::fs::File::open(dbf_path)?);
+        let (_, header_len, record_len) = read_dbf_header(&mut reader, dbf_path)?;
+        Ok(Self {
+            reader,
+            header_len,
+            record_len,
+            offset,
+            len,
+        })
+    }
+
+    // The trailing spaces, which are just the padding, are trimmed.
+    fn read(&mut self, record_idx: usize) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let pos = self.header_len + record_idx * self.record_len + self.offset;
+        let current = self.reader.stream_position()?;
+        self.reader.seek_relative(pos as i64 - current as i64)?;
+        let mut bytes = vec![0u8; self.len];
+        self.reader.read_exact(&mut bytes)?;
+        let len = bytes.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+        bytes.truncate(len);
+        Ok(bytes)
+    }
 }
 
 impl ShapefileDataSource {
     pub(crate) fn new<P: AsRef<Path>>(
         path: P,
-        user_encoding: Option<::shapefile::dbase::encoding::DynEncoding>,
+        user_encoding: Option<&EncodingOption>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let path = path.as_ref();
+        let filename = path.as_ref().to_string_lossy().into_owned();
+        let extracted = if is_shp_zip(&path) {
+            Some(ExtractedShapefile::extract(path.as_ref())?)
+        } else {
+            None
+        };
+        let path = match &extracted {
+            Some(extracted) => extracted.shp_path.as_path(),
+            None => path.as_ref(),
+        };
         let dbf_path = path.with_extension("dbf");
 
-        let encoding = user_encoding.or_else(|| {
-            let cpg_path = path.with_extension("cpg");
-            infer_encoding_from_cpg(&cpg_path)
-        });
+        let encoding_label = resolve_encoding(&dbf_path, user_encoding)?;
+        let dbf_reader = open_dbf(&dbf_path, encoding_label.as_deref())?;
+        let column_specs = column_specs(&dbf_reader);
+        let deletion_flags = read_deletion_flags(&dbf_path)?;
+        // This also fails early if the .shp file is invalid.
+        let shp_offsets = read_shp_offsets(path)?;
 
-        let dbf_reader = match encoding {
-            Some(encoding) => {
-                ::shapefile::dbase::Reader::from_path_with_encoding(&dbf_path, encoding)?
-            }
-            None => ::shapefile::dbase::Reader::from_path(&dbf_path)?,
-        };
+        Ok(ShapefileDataSource {
+            path: path.to_path_buf(),
+            has_geometry: true,
+            deletion_flags,
+            shp_offsets,
+            record_indices: None,
+            raw_field: None,
+            _extracted: extracted,
+            filename,
+            column_specs,
+            encoding: encoding_label,
+            metadata: None,
+            mtime: None,
+        })
+    }
 
-        let mut column_specs: Vec<ColumnSpec> = dbf_reader
-            .fields()
-            .iter()
-            .map(|field| ColumnSpec {
-                name: field.name().to_string(),
-                column_type: field.field_type().into(),
-            })
-            .collect();
-        column_specs.sort_by(|a, b| a.name.cmp(&b.name));
-
-        let shape_reader = ::shapefile::ShapeReader::from_path(path)?;
-        let mut reader = ::shapefile::Reader::new(shape_reader, dbf_reader);
-
-        let mut rows: Vec<ShapefileRow> = Vec::new();
-        for shape_record in reader.iter_shapes_and_records() {
-            let (shape, record) = shape_record?;
-            rows.push(ShapefileRow {
-                geometry: shape_to_wkb(shape)?,
-                record,
-            });
-        }
+    // Read a standalone .dbf file (i.e. an attribute table without geometry).
+    pub(crate) fn from_dbf<P: AsRef<Path>>(
+        path: P,
+        user_encoding: Option<&EncodingOption>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+
+        let encoding_label = resolve_encoding(path, user_encoding)?;
+        let dbf_reader = open_dbf(path, encoding_label.as_deref())?;
+        let column_specs = column_specs(&dbf_reader);
+        let deletion_flags = read_deletion_flags(path)?;
 
         Ok(ShapefileDataSource {
-            rows,
+            path: path.to_path_buf(),
+            has_geometry: false,
+            deletion_flags,
+            shp_offsets: Vec::new(),
+            record_indices: None,
+            raw_field: None,
+            _extracted: None,
             filename: path.to_string_lossy().into_owned(),
             column_specs,
+            encoding: encoding_label,
+            metadata: None,
+            mtime: None,
         })
     }
+
+    // Read the raw bytes of the field along with the rows so that the user can
+    // diagnose the encoding issues (`raw_bytes_for` option). A record starts with
+    // the deletion flag, followed by the fields in the order of the descriptors.
+    pub(crate) fn set_raw_bytes_field(
+        &mut self,
+        name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let dbf_reader = open_dbf(&self.dbf_path(), self.encoding.as_deref())?;
+        let mut offset = 1;
+        for field in dbf_reader.fields() {
+            let len = field.length() as usize;
+            if field.name() == name {
+                self.raw_field = Some((offset, len));
+                return Ok(());
+            }
+            offset += len;
+        }
+        Err(format!(
+            "Column '{name}' in 'raw_bytes_for' doesn't exist in {}",
+            self.filename.replace('\\', "/")
+        )
+        .into())
+    }
+
+    fn dbf_path(&self) -> PathBuf {
+        if self.has_geometry {
+            self.path.with_extension("dbf")
+        } else {
+            self.path.clone()
+        }
+    }
+
+    // The .shp file, which is in the temporary directory for a `.shp.zip` file
+    pub(crate) fn shp_path(&self) -> &Path {
+        &self.path
+    }
+
+    // The files read by read_rows()
+    pub(crate) fn file_paths(&self) -> Vec<PathBuf> {
+        if self.has_geometry {
+            ["shp", "shx", "dbf"]
+                .iter()
+                .map(|ext| self.path.with_extension(ext))
+                .collect()
+        } else {
+            vec![self.path.clone()]
+        }
+    }
+
+    pub(crate) fn n_rows(&self) -> usize {
+        match &self.record_indices {
+            Some(indices) => indices.len(),
+            None => self.deletion_flags.len(),
+        }
+    }
+
+    // Skip the records marked as deleted.
+    pub(crate) fn skip_deleted(&mut self) {
+        let indices = (0..self.deletion_flags.len())
+            .filter(|&i| !self.deletion_flags[i])
+            .collect();
+        self.record_indices = Some(indices);
+    }
+
+    // Read the rows in the range. The range is of the rows, not of the records in
+    // the file, if the deleted records are skipped.
+    pub(crate) fn read_rows(
+        &self,
+        range: Range<usize>,
+    ) -> Result<Vec<ShapefileRow>, Box<dyn std::error::Error>> {
+        if range.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // The records between the first and the last ones are read, and the deleted
+        // ones among them are dropped.
+        let records = match &self.record_indices {
+            Some(indices) => indices[range.start]..(indices[range.end - 1] + 1),
+            None => range,
+        };
+        let skip_deleted = self.record_indices.is_some();
+
+        let dbf_path = self.dbf_path();
+        let mut dbf_reader = open_dbf(&dbf_path, self.encoding.as_deref())?;
+        let mut raw_reader = match self.raw_field {
+            Some(raw_field) => Some(RawFieldReader::open(&dbf_path, raw_field)?),
+            None => None,
+        };
+
+        let mut rows: Vec<ShapefileRow> = Vec::with_capacity(records.len());
+        if self.has_geometry {
+            let mut shp_reader = BufReader::new(std::fs::File::open(&self.path)?);
+            dbf_reader.seek(records.start)?;
+
+            // The offsets are read on bind, so the records before the range are
+            // never read.
+            let dbf_records = dbf_reader.iter_records().take(records.len());
+            for ((i, record), &offset) in records
+                .clone()
+                .zip(dbf_records)
+                .zip(self.shp_offsets.iter().skip(records.start))
+            {
+                let is_deleted = self.deletion_flags.get(i).copied().unwrap_or(false);
+                if skip_deleted && is_deleted {
+                    continue;
+                }
+                let record = record?;
+                let shape = read_shape_at(&mut shp_reader, offset)?;
+                let geometry = shape_to_wkb(shape).unwrap_or_else(|e| {
+                    warn!(
+                        "Failed to read the shape #{} in {}: {e}",
+                        i + 1,
+                        self.path.to_string_lossy().replace('\\', "/")
+                    );
+                    None
+                });
+                rows.push(ShapefileRow {
+                    geometry,
+                    record,
+                    is_deleted,
+                    raw_bytes: raw_reader.as_mut().map(|r| r.read(i)).transpose()?,
+                });
+            }
+        } else {
+            dbf_reader.seek(records.start)?;
+
+            let dbf_records = dbf_reader.iter_records().take(records.len());
+            for (i, record) in records.clone().zip(dbf_records) {
+                let is_deleted = self.deletion_flags.get(i).copied().unwrap_or(false);
+                if skip_deleted && is_deleted {
+                    continue;
+                }
+                rows.push(ShapefileRow {
+                    geometry: None,
+                    record: record?,
+                    is_deleted,
+                    raw_bytes: raw_reader.as_mut().map(|r| r.read(i)).transpose()?,
+                });
+            }
+        }
+
+        Ok(rows)
+    }
+}
+
+// DBF DateTime is a pair of the Julian day number and the milliseconds since
+// midnight. The dbase crate decodes it into the calendar date and the time (the
+// milliseconds are truncated), so convert them to microseconds since Unix epoch.
+// There's no timezone information, so it's read as TIMESTAMP as it is.
+pub(crate) fn datetime_to_unix_micros(v: &::shapefile::dbase::DateTime) -> i64 {
+    let date = v.date();
+    let time = v.time();
+    let days = date.to_unix_days() as i64;
+    let seconds = time.hours() as i64 * 3600 + time.minutes() as i64 * 60 + time.seconds() as i64;
+    (days * 86_400 + seconds) * 1_000_000
 }
 
 impl From<::shapefile::dbase::FieldType> for ColumnType {
@@ -74,24 +484,12 @@ impl From<::shapefile::dbase::FieldType> for ColumnType {
         match value {
             FieldType::Logical => Self::Boolean,
             FieldType::Integer => Self::Integer,
-            FieldType::Numeric | FieldType::Float | FieldType::Currency | FieldType::Double => {
-                Self::Double
-            }
+            // Float is a single-precision field, so it doesn't need DOUBLE.
+            FieldType::Float => Self::Real,
+            FieldType::Numeric | FieldType::Currency | FieldType::Double => Self::Double,
             FieldType::DateTime => Self::Timestamp,
             FieldType::Character | FieldType::Memo => Self::Varchar,
             FieldType::Date => Self::Date,
         }
     }
 }
-
-fn shape_to_wkb(shape: ::shapefile::Shape) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
-    if matches!(shape, ::shapefile::Shape::NullShape) {
-        return Ok(None);
-    }
-
-    let geometry: geo_types::Geometry<f64> = shape.try_into()?;
-    let mut buffer = Vec::new();
-    wkb::writer::write_geometry(&mut buffer, &geometry, &Default::default())
-        .map_err(|e| -> Box<dyn std::error::Error> { Box::new(e) })?;
-    Ok(Some(buffer))
-}
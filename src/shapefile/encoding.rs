@@ -1,8 +1,132 @@
-use std::path::Path;
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
 
 use ::shapefile::dbase::encoding::DynEncoding;
 
-pub(crate) fn infer_encoding_from_cpg(cpg_path: &Path) -> Option<DynEncoding> {
+// The number of records to sample to guess the encoding.
+const SNIFF_SAMPLE_SIZE: usize = 1000;
+
+#[derive(Clone)]
+pub enum EncodingOption {
+    // Guess the encoding from the content of the DBF file.
+    Auto,
+    // Use the specified encoding. The label is kept for reporting.
+    Specified(String, DynEncoding),
+}
+
+impl EncodingOption {
+    pub(crate) fn from_label(label: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        if label.eq_ignore_ascii_case("auto") {
+            return Ok(Self::Auto);
+        }
+
+        let encoding = parse_encoding_label(label)
+            .ok_or_else(|| format!("Unknown encoding label in 'encoding' option: {label}"))?;
+        Ok(Self::Specified(label.to_string(), encoding))
+    }
+}
+
+// Resolve the label of `encoding` option or a CPG file. Some bare code page numbers
+// seen in the wild are not known to DynEncoding::from_name(), so they are mapped to
+// the encodings first.
+pub(crate) fn parse_encoding_label(label: &str) -> Option<DynEncoding> {
+    let label = label.trim();
+    match code_page_encoding(label) {
+        Some(encoding) => DynEncoding::from_name(encoding.name()),
+        None => DynEncoding::from_name(label),
+    }
+}
+
+// Note that encoding_rs follows the WHATWG Encoding Standard, so US-ASCII and
+// ISO-8859-1 are decoded as windows-1252, and ISO-8859-9 as windows-1254, which
+// are their supersets.
+//
+// cf. https://learn.microsoft.com/en-us/windows/win32/intl/code-page-identifiers
+fn code_page_encoding(label: &str) -> Option<&'static encoding_rs::Encoding> {
+    let encoding = match label {
+        "10000" => encoding_rs::MACINTOSH,
+        "20127" => encoding_rs::WINDOWS_1252,
+        "28591" => encoding_rs::WINDOWS_1252,
+        "28592" => encoding_rs::ISO_8859_2,
+        "28593" => encoding_rs::ISO_8859_3,
+        "28594" => encoding_rs::ISO_8859_4,
+        "28595" => encoding_rs::ISO_8859_5,
+        "28596" => encoding_rs::ISO_8859_6,
+        "28597" => encoding_rs::ISO_8859_7,
+        "28598" => encoding_rs::ISO_8859_8,
+        "28599" => encoding_rs::WINDOWS_1254,
+        "28605" => encoding_rs::ISO_8859_15,
+        _ => return None,
+    };
+    Some(encoding)
+}
+
+pub(crate) fn infer_encoding_from_cpg(cpg_path: &Path) -> Option<(String, DynEncoding)> {
     let label = std::fs::read_to_string(cpg_path).ok()?;
-    DynEncoding::from_name(&label)
+    let encoding = parse_encoding_label(&label)?;
+    Some((label.trim().to_string(), encoding))
+}
+
+// Guess the encoding from the raw bytes of the field names and the character fields.
+//
+// cf. https://www.clicketyclick.dk/databases/xbase/format/dbf.html
+pub(crate) fn sniff_dbf_encoding(
+    dbf_path: &Path,
+) -> Result<&'static str, Box<dyn std::error::Error>> {
+    let mut reader = BufReader::new(File::open(dbf_path)?);
+
+    let mut header = [0u8; 32];
+    reader.read_exact(&mut header)?;
+    let n_records = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+    let header_len = u16::from_le_bytes(header[8..10].try_into().unwrap()) as usize;
+    let record_len = u16::from_le_bytes(header[10..12].try_into().unwrap()) as usize;
+
+    if header_len < 32 {
+        return Err(format!("Invalid DBF header: {}", dbf_path.to_string_lossy()).into());
+    }
+
+    let mut field_descriptors = vec![0u8; header_len - 32];
+    reader.read_exact(&mut field_descriptors)?;
+
+    let mut detector = chardetng::EncodingDetector::new();
+
+    // Field descriptors are 32 bytes each, terminated by 0x0D.
+    //   - 0..11: field name
+    //   - 11: field type
+    //   - 16: field length
+    let mut character_fields: Vec<(usize, usize)> = Vec::new(); // (offset, length)
+    let mut offset = 1; // the first byte of a record is the deletion flag
+    for desc in field_descriptors.chunks_exact(32) {
+        if desc[0] == 0x0D {
+            break;
+        }
+
+        let name_len = desc[..11].iter().position(|&b| b == 0).unwrap_or(11);
+        detector.feed(&desc[..name_len], false);
+
+        let field_len = desc[16] as usize;
+        if desc[11] == b'C' {
+            character_fields.push((offset, field_len));
+        }
+        offset += field_len;
+    }
+
+    let mut record = vec![0u8; record_len];
+    for _ in 0..std::cmp::min(n_records, SNIFF_SAMPLE_SIZE) {
+        if reader.read_exact(&mut record).is_err() {
+            break;
+        }
+
+        for &(offset, len) in &character_fields {
+            if let Some(value) = record.get(offset..offset + len) {
+                detector.feed(value, false);
+            }
+        }
+    }
+    detector.feed(&[], true);
+
+    Ok(detector.guess(None, true).name())
 }
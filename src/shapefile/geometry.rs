@@ -0,0 +1,219 @@
+// Convert the shapes to WKB. This writes WKB directly instead of converting via
+// geo_types so that the Z values are preserved. M values are dropped.
+
+use ::shapefile::{Patch, PolygonRing, Shape};
+
+const WKB_POINT: u32 = 1;
+const WKB_LINESTRING: u32 = 2;
+const WKB_POLYGON: u32 = 3;
+const WKB_MULTIPOINT: u32 = 4;
+const WKB_MULTILINESTRING: u32 = 5;
+const WKB_MULTIPOLYGON: u32 = 6;
+
+// (x, y, z). z is ignored for 2D shapes.
+type Coord = [f64; 3];
+
+trait ToCoord {
+    fn to_coord(&self) -> Coord;
+}
+
+impl ToCoord for ::shapefile::Point {
+    fn to_coord(&self) -> Coord {
+        [self.x, self.y, 0.0]
+    }
+}
+
+impl ToCoord for ::shapefile::PointM {
+    fn to_coord(&self) -> Coord {
+        [self.x, self.y, 0.0]
+    }
+}
+
+impl ToCoord for ::shapefile::PointZ {
+    fn to_coord(&self) -> Coord {
+        [self.x, self.y, self.z]
+    }
+}
+
+pub(super) fn shape_to_wkb(shape: Shape) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    let has_z = matches!(
+        shape,
+        Shape::PointZ(_)
+            | Shape::MultipointZ(_)
+            | Shape::PolylineZ(_)
+            | Shape::PolygonZ(_)
+            | Shape::Multipatch(_)
+    );
+    let mut writer = WkbWriter::new(has_z);
+
+    match shape {
+        Shape::NullShape => return Ok(None),
+        Shape::Point(p) => writer.point(&p.to_coord()),
+        Shape::PointM(p) => writer.point(&p.to_coord()),
+        Shape::PointZ(p) => writer.point(&p.to_coord()),
+        Shape::Multipoint(mp) => writer.multipoint(&coords(mp.points())),
+        Shape::MultipointM(mp) => writer.multipoint(&coords(mp.points())),
+        Shape::MultipointZ(mp) => writer.multipoint(&coords(mp.points())),
+        Shape::Polyline(line) => writer.multilinestring(&parts(line.parts())),
+        Shape::PolylineM(line) => writer.multilinestring(&parts(line.parts())),
+        Shape::PolylineZ(line) => writer.multilinestring(&parts(line.parts())),
+        Shape::Polygon(polygon) => writer.multipolygon(&group_rings(polygon.rings())),
+        Shape::PolygonM(polygon) => writer.multipolygon(&group_rings(polygon.rings())),
+        Shape::PolygonZ(polygon) => writer.multipolygon(&group_rings(polygon.rings())),
+        Shape::Multipatch(multipatch) => {
+            writer.multipolygon(&patches_to_polygons(multipatch.patches())?)
+        }
+    }
+
+    Ok(Some(writer.buffer))
+}
+
+fn coords<P: ToCoord>(points: &[P]) -> Vec<Coord> {
+    points.iter().map(|p| p.to_coord()).collect()
+}
+
+fn parts<P: ToCoord>(parts: &[Vec<P>]) -> Vec<Vec<Coord>> {
+    parts.iter().map(|part| coords(part)).collect()
+}
+
+// An outer ring starts a new polygon, and the inner rings that follow are the
+// holes of it.
+fn group_rings<P: ToCoord>(rings: &[PolygonRing<P>]) -> Vec<Vec<Vec<Coord>>> {
+    let mut polygons: Vec<Vec<Vec<Coord>>> = Vec::new();
+    for ring in rings {
+        match (ring, polygons.last_mut()) {
+            (PolygonRing::Inner(points), Some(polygon)) => polygon.push(coords(points)),
+            (PolygonRing::Inner(points), None) | (PolygonRing::Outer(points), _) => {
+                polygons.push(vec![coords(points)])
+            }
+        }
+    }
+    polygons
+}
+
+// A multipatch is read as a MULTIPOLYGON Z. Each triangle of a triangle strip or a
+// triangle fan becomes a polygon.
+fn patches_to_polygons(
+    patches: &[Patch],
+) -> Result<Vec<Vec<Vec<Coord>>>, Box<dyn std::error::Error>> {
+    let mut polygons: Vec<Vec<Vec<Coord>>> = Vec::new();
+    // true if the current polygon is started by a FirstRing, whose following Rings
+    // are its holes
+    let mut after_first_ring = false;
+
+    for patch in patches {
+        match patch {
+            Patch::TriangleStrip(points) => {
+                let points = coords(points);
+                for w in points.windows(3) {
+                    polygons.push(vec![vec![w[0], w[1], w[2], w[0]]]);
+                }
+                after_first_ring = false;
+            }
+            Patch::TriangleFan(points) => {
+                let points = coords(points);
+                if let Some((first, rest)) = points.split_first() {
+                    for w in rest.windows(2) {
+                        polygons.push(vec![vec![*first, w[0], w[1], *first]]);
+                    }
+                }
+                after_first_ring = false;
+            }
+            Patch::OuterRing(points) => {
+                polygons.push(vec![coords(points)]);
+                after_first_ring = false;
+            }
+            Patch::FirstRing(points) => {
+                polygons.push(vec![coords(points)]);
+                after_first_ring = true;
+            }
+            Patch::InnerRing(points) => match polygons.last_mut() {
+                Some(polygon) => polygon.push(coords(points)),
+                None => return Err("An inner ring appears before any outer ring".into()),
+            },
+            Patch::Ring(points) => match polygons.last_mut() {
+                Some(polygon) if after_first_ring => polygon.push(coords(points)),
+                _ => polygons.push(vec![coords(points)]),
+            },
+        }
+    }
+
+    Ok(polygons)
+}
+
+// A minimal little-endian ISO WKB writer
+struct WkbWriter {
+    buffer: Vec<u8>,
+    has_z: bool,
+}
+
+impl WkbWriter {
+    fn new(has_z: bool) -> Self {
+        Self {
+            buffer: Vec::new(),
+            has_z,
+        }
+    }
+
+    fn header(&mut self, geometry_type: u32) {
+        let geometry_type = if self.has_z {
+            geometry_type + 1000
+        } else {
+            geometry_type
+        };
+        self.buffer.push(0x01);
+        self.buffer.extend_from_slice(&geometry_type.to_le_bytes());
+    }
+
+    fn count(&mut self, n: usize) {
+        self.buffer.extend_from_slice(&(n as u32).to_le_bytes());
+    }
+
+    fn coord(&mut self, coord: &Coord) {
+        let n_dims = if self.has_z { 3 } else { 2 };
+        for v in &coord[..n_dims] {
+            self.buffer.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+
+    fn coords(&mut self, coords: &[Coord]) {
+        self.count(coords.len());
+        for coord in coords {
+            self.coord(coord);
+        }
+    }
+
+    fn point(&mut self, coord: &Coord) {
+        self.header(WKB_POINT);
+        self.coord(coord);
+    }
+
+    fn multipoint(&mut self, points: &[Coord]) {
+        self.header(WKB_MULTIPOINT);
+        self.count(points.len());
+        for p in points {
+            self.point(p);
+        }
+    }
+
+    fn multilinestring(&mut self, lines: &[Vec<Coord>]) {
+        self.header(WKB_MULTILINESTRING);
+        self.count(lines.len());
+        for line in lines {
+            self.header(WKB_LINESTRING);
+            self.coords(line);
+        }
+    }
+
+    fn multipolygon(&mut self, polygons: &[Vec<Vec<Coord>>]) {
+        self.header(WKB_MULTIPOLYGON);
+        self.count(polygons.len());
+        for rings in polygons {
+            self.header(WKB_POLYGON);
+            self.count(rings.len());
+            for ring in rings {
+                self.coords(ring);
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::path::{Path, PathBuf};
+
+// The title and the abstract of the dataset, read from the metadata sidecar file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub abstract_text: Option<String>,
+}
+
+impl Metadata {
+    // Read the sidecar file written by ArcGIS (`<name>.shp.xml`) or QGIS
+    // (`<name>.qmd`). Returns None if there's no such file.
+    pub(crate) fn read(path: &Path) -> Option<Self> {
+        sidecar_paths(path)
+            .into_iter()
+            .find_map(|p| std::fs::read_to_string(p).ok())
+            .map(|xml| Self::parse(&xml))
+    }
+
+    // This is not a real XML parser, but these files are simple enough to just
+    // look for the first occurrence of the tags.
+    //
+    // - ArcGIS: <dataIdInfo><idCitation><resTitle> and <dataIdInfo><idAbs>
+    // - FGDC (also written by ArcGIS): <citeinfo><title> and <descript><abstract>
+    // - QGIS: <qgis><title> and <qgis><abstract>
+    pub(crate) fn parse(xml: &str) -> Self {
+        Self {
+            title: find_element(xml, "resTitle").or_else(|| find_element(xml, "title")),
+            abstract_text: find_element(xml, "idAbs").or_else(|| find_element(xml, "abstract")),
+        }
+    }
+}
+
+fn sidecar_paths(path: &Path) -> Vec<PathBuf> {
+    let mut shp_xml = path.as_os_str().to_owned();
+    shp_xml.push(".xml");
+
+    vec![PathBuf::from(shp_xml), path.with_extension("qmd")]
+}
+
+// Returns the text of the first element with the tag name. An empty element is
+// treated as missing.
+fn find_element(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{tag}");
+    let close = format!("</{tag}>");
+
+    let mut rest = xml;
+    loop {
+        let start = rest.find(&open)? + open.len();
+        rest = &rest[start..];
+
+        // Skip the tags that only share the prefix (e.g. <titleText> for <title>)
+        let end_of_tag = rest.find('>')?;
+        let attrs = &rest[..end_of_tag];
+        if !(attrs.is_empty() || attrs.starts_with(char::is_whitespace)) {
+            continue;
+        }
+
+        let content = &rest[(end_of_tag + 1)..];
+        let end = content.find(&close)?;
+        let text = unescape_xml(content[..end].trim());
+
+        return if text.is_empty() { None } else { Some(text) };
+    }
+}
+
+fn unescape_xml(s: &str) -> String {
+    if let Some(cdata) = s
+        .strip_prefix("<![CDATA[")
+        .and_then(|s| s.strip_suffix("]]>"))
+    {
+        return cdata.to_string();
+    }
+
+    // &amp; must be the last so that e.g. "&amp;lt;" becomes "&lt;", not "<".
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
@@ -1,7 +1,12 @@
+mod archive;
 mod datasource;
 mod encoding;
+mod geometry;
+mod metadata;
 
-pub use datasource::ShapefileDataSource;
+pub use datasource::{datetime_to_unix_micros, ShapefileDataSource};
+pub use encoding::EncodingOption;
+pub use metadata::Metadata;
 
 #[cfg(test)]
 mod tests;
@@ -1,4 +1,11 @@
 use crate::types::ColumnType;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::path::PathBuf;
+
+fn read_all_rows(source: &super::ShapefileDataSource) -> Vec<super::datasource::ShapefileRow> {
+    source.read_rows(0..source.n_rows()).unwrap()
+}
 
 fn row_character(
     source: &super::ShapefileDataSource,
@@ -7,7 +14,11 @@ fn row_character(
 ) -> Option<String> {
     use ::shapefile::dbase::FieldValue;
 
-    match source.rows.get(row_index)?.record.get(field_name)? {
+    match read_all_rows(source)
+        .get(row_index)?
+        .record
+        .get(field_name)?
+    {
         FieldValue::Character(Some(value)) => Some(value.clone()),
         _ => None,
     }
@@ -70,3 +81,601 @@ fn test_field_type_mapping_date_and_datetime() {
     assert_eq!(ColumnType::from(FieldType::Date), ColumnType::Date);
     assert_eq!(ColumnType::from(FieldType::DateTime), ColumnType::Timestamp);
 }
+
+#[test]
+fn test_field_type_mapping_float_and_double() {
+    use ::shapefile::dbase::FieldType;
+
+    assert_eq!(ColumnType::from(FieldType::Float), ColumnType::Real);
+    assert_eq!(ColumnType::from(FieldType::Numeric), ColumnType::Double);
+    assert_eq!(ColumnType::from(FieldType::Double), ColumnType::Double);
+    assert_eq!(ColumnType::from(FieldType::Currency), ColumnType::Double);
+}
+
+#[test]
+fn test_from_dbf_float() -> Result<(), Box<dyn std::error::Error>> {
+    use ::shapefile::dbase::FieldValue;
+
+    let source = super::ShapefileDataSource::from_dbf("./test/data/dbf_float/values.dbf", None)?;
+    let specs = &source.column_specs;
+
+    assert_eq!(&specs[0].name, "f32");
+    assert_eq!(specs[0].column_type, ColumnType::Real);
+    assert_eq!(&specs[1].name, "f64");
+    assert_eq!(specs[1].column_type, ColumnType::Double);
+    assert_eq!(
+        read_all_rows(&source)[0].record.get("f32"),
+        Some(&FieldValue::Float(Some(1.5)))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_from_dbf_deleted() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::path::Path::new("./test/data/dbf_deleted/values.dbf");
+    assert_eq!(
+        super::datasource::read_deletion_flags(path)?,
+        vec![false, true, false]
+    );
+
+    let mut source = super::ShapefileDataSource::from_dbf(path, None)?;
+    let is_deleted: Vec<bool> = read_all_rows(&source)
+        .iter()
+        .map(|r| r.is_deleted)
+        .collect();
+    assert_eq!(is_deleted, vec![false, true, false]);
+
+    // The ranges are of the rows after the deleted ones are skipped
+    source.skip_deleted();
+    assert_eq!(source.n_rows(), 2);
+    let is_deleted: Vec<bool> = read_all_rows(&source)
+        .iter()
+        .map(|r| r.is_deleted)
+        .collect();
+    assert_eq!(is_deleted, vec![false, false]);
+    assert_eq!(source.read_rows(1..2)?.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_polyline_m() -> Result<(), Box<dyn std::error::Error>> {
+    use geo_traits::to_geo::ToGeoGeometry;
+    use geo_types::{line_string, Geometry, MultiLineString};
+
+    let source =
+        super::ShapefileDataSource::new("./test/data/shapefile_polyline_m/lines.shp", None)?;
+    assert_eq!(source.n_rows(), 2);
+
+    // M values are dropped
+    let geometries: Vec<Geometry> = read_all_rows(&source)
+        .iter()
+        .map(|r| {
+            let wkb = wkb::reader::read_wkb(r.geometry.as_ref().unwrap()).unwrap();
+            wkb.to_geometry()
+        })
+        .collect();
+    assert_eq!(
+        geometries,
+        vec![
+            Geometry::MultiLineString(MultiLineString(vec![
+                line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 1.0)],
+                line_string![(x: 2.0, y: 2.0), (x: 3.0, y: 3.0)],
+            ])),
+            Geometry::MultiLineString(MultiLineString(vec![line_string![
+                (x: 5.0, y: 5.0),
+                (x: 6.0, y: 7.0),
+                (x: 8.0, y: 9.0)
+            ]])),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_encoding_auto() -> Result<(), Box<dyn std::error::Error>> {
+    // This DBF has neither a .cpg file nor LDID.
+    let source = super::ShapefileDataSource::new(
+        "./test/data/shapefile_cp932_wo_ldid/points.shp",
+        Some(&super::EncodingOption::Auto),
+    )?;
+    let specs = &source.column_specs;
+
+    assert_eq!(source.encoding.as_deref(), Some("Shift_JIS"));
+    assert_eq!(&specs[0].name, "属性1");
+    assert_eq!(&specs[1].name, "属性2");
+    assert_eq!(row_character(&source, 0, "属性2").as_deref(), Some("値a"));
+    assert_eq!(row_character(&source, 1, "属性2").as_deref(), Some("値b"));
+
+    Ok(())
+}
+
+#[test]
+fn test_from_dbf() -> Result<(), Box<dyn std::error::Error>> {
+    let source =
+        super::ShapefileDataSource::from_dbf("./test/data/shapefile_utf8/points.dbf", None)?;
+    let specs = &source.column_specs;
+
+    assert_eq!(specs.len(), 2);
+    assert_eq!(&specs[0].name, "属性1");
+    assert_eq!(&specs[1].name, "属性2");
+    assert_eq!(source.n_rows(), 2);
+    assert!(read_all_rows(&source)
+        .iter()
+        .all(|row| row.geometry.is_none()));
+    assert_eq!(row_character(&source, 0, "属性2").as_deref(), Some("値a"));
+
+    Ok(())
+}
+
+#[test]
+fn test_read_metadata() {
+    let metadata = super::Metadata::read(std::path::Path::new(
+        "./test/data/shapefile_metadata/points.shp",
+    ));
+    assert_eq!(
+        metadata,
+        Some(super::Metadata {
+            title: Some("Sample Points".to_string()),
+            abstract_text: Some("Points for testing & nothing else.".to_string()),
+        })
+    );
+
+    // no sidecar file
+    let metadata = super::Metadata::read(std::path::Path::new(
+        "./test/data/shapefile_utf8/points.shp",
+    ));
+    assert_eq!(metadata, None);
+}
+
+#[test]
+fn test_parse_qgis_metadata() {
+    let metadata = super::Metadata::parse(
+        r#"<qgis version="3.34.0"><identifier>points</identifier><title>QGIS Points</title><abstract><![CDATA[a <b>bold</b> abstract]]></abstract></qgis>"#,
+    );
+    assert_eq!(metadata.title.as_deref(), Some("QGIS Points"));
+    assert_eq!(
+        metadata.abstract_text.as_deref(),
+        Some("a <b>bold</b> abstract")
+    );
+
+    let metadata = super::Metadata::parse("<qgis><title/><abstract></abstract></qgis>");
+    assert_eq!(metadata, super::Metadata::default());
+}
+
+#[test]
+fn test_shape_to_wkb() {
+    use ::shapefile::{
+        Multipatch, Multipoint, MultipointM, MultipointZ, Patch, Point, PointM, PointZ, Polygon,
+        PolygonM, PolygonRing, PolygonZ, Polyline, PolylineM, PolylineZ, Shape,
+    };
+    use geo_traits::{to_geo::ToGeoGeometry, Dimensions, GeometryTrait};
+    use geo_types::Geometry;
+
+    let read = |shape: Shape| {
+        let wkb = super::geometry::shape_to_wkb(shape).unwrap().unwrap();
+        let geometry = wkb::reader::read_wkb(&wkb).unwrap();
+        (geometry.dim(), geometry.to_geometry())
+    };
+
+    assert!(super::geometry::shape_to_wkb(Shape::NullShape)
+        .unwrap()
+        .is_none());
+
+    // points
+    let point = Geometry::Point(geo_types::point!(x: 1.0, y: 2.0));
+    assert_eq!(
+        read(Shape::Point(Point::new(1.0, 2.0))),
+        (Dimensions::Xy, point.clone())
+    );
+    assert_eq!(
+        read(Shape::PointM(PointM::new(1.0, 2.0, 9.0))),
+        (Dimensions::Xy, point.clone())
+    );
+    assert_eq!(
+        read(Shape::PointZ(PointZ::new(1.0, 2.0, 3.0, 9.0))),
+        (Dimensions::Xyz, point)
+    );
+    // the Z value is kept
+    let wkb = super::geometry::shape_to_wkb(Shape::PointZ(PointZ::new(1.0, 2.0, 3.0, 9.0)))
+        .unwrap()
+        .unwrap();
+    assert_eq!(&wkb[..5], &[0x01, 0xE9, 0x03, 0x00, 0x00]);
+    assert_eq!(&wkb[21..], &3.0_f64.to_le_bytes());
+
+    // multipoints
+    let multipoint = |(dim, geometry): (Dimensions, Geometry)| match geometry {
+        Geometry::MultiPoint(mp) => (dim, mp.0.len()),
+        _ => panic!("not a multipoint"),
+    };
+    assert_eq!(
+        multipoint(read(Shape::Multipoint(Multipoint::new(vec![
+            Point::new(0.0, 0.0),
+            Point::new(1.0, 1.0),
+        ])))),
+        (Dimensions::Xy, 2)
+    );
+    assert_eq!(
+        multipoint(read(Shape::MultipointM(MultipointM::new(vec![
+            PointM::new(0.0, 0.0, 9.0),
+            PointM::new(1.0, 1.0, 9.0),
+        ])))),
+        (Dimensions::Xy, 2)
+    );
+    assert_eq!(
+        multipoint(read(Shape::MultipointZ(MultipointZ::new(vec![
+            PointZ::new(0.0, 0.0, 1.0, 9.0),
+            PointZ::new(1.0, 1.0, 2.0, 9.0),
+        ])))),
+        (Dimensions::Xyz, 2)
+    );
+
+    // polylines
+    let multilinestring = |(dim, geometry): (Dimensions, Geometry)| match geometry {
+        Geometry::MultiLineString(mls) => (dim, mls.0.len()),
+        _ => panic!("not a multilinestring"),
+    };
+    assert_eq!(
+        multilinestring(read(Shape::Polyline(Polyline::new(vec![
+            Point::new(0.0, 0.0),
+            Point::new(1.0, 1.0),
+        ])))),
+        (Dimensions::Xy, 1)
+    );
+    assert_eq!(
+        multilinestring(read(Shape::PolylineM(PolylineM::new(vec![
+            PointM::new(0.0, 0.0, 9.0),
+            PointM::new(1.0, 1.0, 9.0),
+        ])))),
+        (Dimensions::Xy, 1)
+    );
+    assert_eq!(
+        multilinestring(read(Shape::PolylineZ(PolylineZ::new(vec![
+            PointZ::new(0.0, 0.0, 1.0, 9.0),
+            PointZ::new(1.0, 1.0, 2.0, 9.0),
+        ])))),
+        (Dimensions::Xyz, 1)
+    );
+
+    // polygons; the number of the polygons and the holes of each polygon
+    let multipolygon = |(dim, geometry): (Dimensions, Geometry)| match geometry {
+        Geometry::MultiPolygon(mp) => (
+            dim,
+            mp.0.iter().map(|p| p.interiors().len()).collect::<Vec<_>>(),
+        ),
+        _ => panic!("not a multipolygon"),
+    };
+    let square = |x0: f64, x1: f64| vec![(x0, x0), (x0, x1), (x1, x1), (x1, x0), (x0, x0)];
+    let outer = square(0.0, 10.0);
+    let inner: Vec<(f64, f64)> = square(2.0, 4.0).into_iter().rev().collect();
+    assert_eq!(
+        multipolygon(read(Shape::Polygon(Polygon::with_rings(vec![
+            PolygonRing::Outer(outer.iter().map(|&(x, y)| Point::new(x, y)).collect()),
+            PolygonRing::Inner(inner.iter().map(|&(x, y)| Point::new(x, y)).collect()),
+        ])))),
+        (Dimensions::Xy, vec![1])
+    );
+    assert_eq!(
+        multipolygon(read(Shape::PolygonM(PolygonM::with_rings(vec![
+            PolygonRing::Outer(outer.iter().map(|&(x, y)| PointM::new(x, y, 9.0)).collect()),
+            PolygonRing::Inner(inner.iter().map(|&(x, y)| PointM::new(x, y, 9.0)).collect()),
+        ])))),
+        (Dimensions::Xy, vec![1])
+    );
+    assert_eq!(
+        multipolygon(read(Shape::PolygonZ(PolygonZ::with_rings(vec![
+            PolygonRing::Outer(
+                outer
+                    .iter()
+                    .map(|&(x, y)| PointZ::new(x, y, 1.0, 9.0))
+                    .collect()
+            ),
+            PolygonRing::Inner(
+                inner
+                    .iter()
+                    .map(|&(x, y)| PointZ::new(x, y, 1.0, 9.0))
+                    .collect()
+            ),
+        ])))),
+        (Dimensions::Xyz, vec![1])
+    );
+
+    // multipatch; a triangle strip and a triangle fan of 4 points are 2 triangles
+    // each, followed by a polygon with a hole
+    let points_z = |points: &[(f64, f64)]| -> Vec<PointZ> {
+        points
+            .iter()
+            .map(|&(x, y)| PointZ::new(x, y, 1.0, 9.0))
+            .collect()
+    };
+    let triangles = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)];
+    assert_eq!(
+        multipolygon(read(Shape::Multipatch(Multipatch::with_parts(vec![
+            Patch::TriangleStrip(points_z(&triangles)),
+            Patch::TriangleFan(points_z(&triangles)),
+            Patch::OuterRing(points_z(&outer)),
+            Patch::InnerRing(points_z(&inner)),
+        ])))),
+        (Dimensions::Xyz, vec![0, 0, 0, 0, 1])
+    );
+}
+
+#[test]
+fn test_from_dbf_datetime() -> Result<(), Box<dyn std::error::Error>> {
+    use ::shapefile::dbase::FieldValue;
+
+    let source = super::ShapefileDataSource::from_dbf("./test/data/dbf_datetime/events.dbf", None)?;
+    assert_eq!(source.column_specs[1].column_type, ColumnType::Timestamp);
+
+    let micros: Vec<i64> = read_all_rows(&source)
+        .iter()
+        .map(|r| match r.record.get("ts") {
+            Some(FieldValue::DateTime(v)) => super::datetime_to_unix_micros(v),
+            v => panic!("unexpected value: {v:?}"),
+        })
+        .collect();
+    assert_eq!(
+        micros,
+        vec![
+            // 2024-01-15 10:30:45
+            1_705_314_645_000_000,
+            // 1970-01-01 00:00:00
+            0,
+            // 1969-12-31 23:59:59
+            -1_000_000,
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_read_rows_range() -> Result<(), Box<dyn std::error::Error>> {
+    use ::shapefile::dbase::FieldValue;
+
+    let source = super::ShapefileDataSource::new("./test/data/many_rows/points_2049.shp", None)?;
+    assert_eq!(source.n_rows(), 2049);
+
+    let all_rows = read_all_rows(&source);
+    let val = |row: &super::datasource::ShapefileRow| match row.record.get("val") {
+        Some(FieldValue::Numeric(v)) => *v,
+        v => panic!("unexpected value: {v:?}"),
+    };
+
+    // Only the rows in the range are read
+    for range in [0..10, 1000..1010, 2040..2049] {
+        let rows = source.read_rows(range.clone())?;
+        assert_eq!(rows.len(), range.len());
+        for (row, expected) in rows.iter().zip(&all_rows[range]) {
+            assert_eq!(row.geometry, expected.geometry);
+            assert_eq!(val(row), val(expected));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_read_rows_wo_shx() -> Result<(), Box<dyn std::error::Error>> {
+    // Without the .shx file, the offsets of the records are read from the .shp
+    // file on bind.
+    let source = super::ShapefileDataSource::new("./test/data/shapefile_wo_shx/points.shp", None)?;
+    let rows = source.read_rows(1..2)?;
+
+    assert_eq!(rows.len(), 1);
+    assert!(rows[0].geometry.is_some());
+    assert_eq!(row_character(&source, 1, "属性2").as_deref(), Some("値b"));
+
+    Ok(())
+}
+
+// Count the bytes allocated by the current thread so that a test can check the
+// peak memory usage of a function, regardless of the other tests running in
+// parallel.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+    static PEAK: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATED.try_with(|allocated| {
+            let current = allocated.get() + layout.size();
+            allocated.set(current);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(current)));
+        });
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // The memory might be allocated by another thread
+        let _ = ALLOCATED
+            .try_with(|allocated| allocated.set(allocated.get().saturating_sub(layout.size())));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+fn peak_allocation<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let base = ALLOCATED.with(|allocated| allocated.get());
+    PEAK.with(|peak| peak.set(base));
+    let result = f();
+    (result, PEAK.with(|peak| peak.get()) - base)
+}
+
+#[test]
+fn test_read_rows_memory_bounded() -> Result<(), Box<dyn std::error::Error>> {
+    use ::shapefile::dbase::{FieldValue, Record, TableWriterBuilder};
+
+    let dir = std::env::temp_dir().join("st_read_multi_test_read_rows_memory_bounded");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("points.shp");
+    let table = TableWriterBuilder::new().add_numeric_field("val".try_into().unwrap(), 10, 0);
+    let mut writer = ::shapefile::Writer::from_path(&path, table)?;
+    for i in 0..100_000 {
+        let mut record = Record::default();
+        record.insert("val".to_string(), FieldValue::Numeric(Some(i as f64)));
+        writer.write_shape_and_record(&::shapefile::Point::new(i as f64, 0.0), &record)?;
+    }
+    drop(writer);
+    let file_size: u64 = ["shp", "dbf"]
+        .iter()
+        .map(|ext| std::fs::metadata(path.with_extension(ext)).map(|m| m.len()))
+        .sum::<Result<u64, _>>()?;
+
+    for with_shx in [true, false] {
+        if !with_shx {
+            std::fs::remove_file(path.with_extension("shx"))?;
+        }
+
+        // Only the deletion flags and the offsets of the records are kept on bind
+        let (source, bind_peak) = peak_allocation(|| super::ShapefileDataSource::new(&path, None));
+        let source = source?;
+        assert_eq!(source.n_rows(), 100_000);
+        assert!(
+            (bind_peak as u64) < file_size / 2,
+            "{bind_peak} bytes are allocated on bind (with_shx: {with_shx})"
+        );
+
+        // Only the rows in the range are read
+        let (rows, read_peak) = peak_allocation(|| source.read_rows(50_000..50_010));
+        let rows = rows?;
+        assert_eq!(rows.len(), 10);
+        assert_eq!(
+            rows[0].record.get("val"),
+            Some(&FieldValue::Numeric(Some(50_000.0)))
+        );
+        assert!(
+            read_peak < 64 * 1024,
+            "{read_peak} bytes are allocated on reading 10 rows (with_shx: {with_shx})"
+        );
+    }
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_file_paths() -> Result<(), Box<dyn std::error::Error>> {
+    let source = super::ShapefileDataSource::new("./test/data/shapefile_utf8/points.shp", None)?;
+    assert_eq!(
+        source.file_paths(),
+        vec![
+            PathBuf::from("./test/data/shapefile_utf8/points.shp"),
+            PathBuf::from("./test/data/shapefile_utf8/points.shx"),
+            PathBuf::from("./test/data/shapefile_utf8/points.dbf"),
+        ]
+    );
+
+    let source = super::ShapefileDataSource::from_dbf("./test/data/dbf_float/values.dbf", None)?;
+    assert_eq!(
+        source.file_paths(),
+        vec![PathBuf::from("./test/data/dbf_float/values.dbf")]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_shp_zip() -> Result<(), Box<dyn std::error::Error>> {
+    let source = super::ShapefileDataSource::new("./test/data/shapefile_zip/points.shp.zip", None)?;
+    assert_eq!(source.filename, "./test/data/shapefile_zip/points.shp.zip");
+
+    // the files are extracted to a temporary directory
+    let shp_path = source.shp_path().to_path_buf();
+    assert!(shp_path.exists());
+    assert!(shp_path.with_extension("cpg").exists());
+
+    let rows = read_all_rows(&source);
+    assert_eq!(rows.len(), 2);
+    assert_eq!(row_character(&source, 1, "属性2").as_deref(), Some("値b"));
+
+    // ...and removed when the data source is dropped
+    drop(source);
+    assert!(!shp_path.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_raw_bytes() -> Result<(), Box<dyn std::error::Error>> {
+    // "値a" and "値b" in CP932
+    let mut source =
+        super::ShapefileDataSource::new("./test/data/shapefile_cp932_wo_cpg/points.shp", None)?;
+    source.set_raw_bytes_field("属性2")?;
+    let raw_bytes: Vec<Option<Vec<u8>>> = read_all_rows(&source)
+        .into_iter()
+        .map(|r| r.raw_bytes)
+        .collect();
+    assert_eq!(
+        raw_bytes,
+        vec![Some(b"\x92la".to_vec()), Some(b"\x92lb".to_vec())]
+    );
+
+    // The field after another field, with the deleted record skipped
+    let mut source =
+        super::ShapefileDataSource::from_dbf("./test/data/dbf_deleted/values.dbf", None)?;
+    source.skip_deleted();
+    source.set_raw_bytes_field("val")?;
+    let raw_bytes: Vec<Option<Vec<u8>>> = read_all_rows(&source)
+        .into_iter()
+        .map(|r| r.raw_bytes)
+        .collect();
+    assert_eq!(
+        raw_bytes,
+        vec![Some(b"    1".to_vec()), Some(b"    3".to_vec())]
+    );
+
+    assert!(source.set_raw_bytes_field("no_such_field").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_empty_shapefile() -> Result<(), Box<dyn std::error::Error>> {
+    // A valid header without any records
+    let source = super::ShapefileDataSource::new("./test/data/shapefile_empty/empty.shp", None)?;
+
+    let specs: Vec<(&str, ColumnType)> = source
+        .column_specs
+        .iter()
+        .map(|s| (s.name.as_str(), s.column_type))
+        .collect();
+    assert_eq!(
+        specs,
+        vec![("name", ColumnType::Varchar), ("val", ColumnType::Double)]
+    );
+    assert_eq!(source.n_rows(), 0);
+    assert!(read_all_rows(&source).is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_encoding_label() {
+    use super::encoding::parse_encoding_label;
+    use ::shapefile::dbase::encoding::DynEncoding;
+
+    let decode = |encoding: DynEncoding, bytes: &[u8]| -> String {
+        use ::shapefile::dbase::encoding::Encoding;
+        encoding.decode(bytes).unwrap().into_owned()
+    };
+
+    // Mac Roman: 0x8E is "é"
+    let encoding = parse_encoding_label("10000").unwrap();
+    assert_eq!(decode(encoding, b"caf\x8E"), "café");
+
+    // ISO-8859-15: 0xA4 is "€" (it's "¤" in ISO-8859-1)
+    let encoding = parse_encoding_label("28605\n").unwrap();
+    assert_eq!(decode(encoding, b"\xA4"), "€");
+
+    assert!(parse_encoding_label("20127").is_some());
+    assert!(parse_encoding_label("28599").is_some());
+    assert!(parse_encoding_label("no such encoding").is_none());
+}
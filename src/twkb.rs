@@ -0,0 +1,355 @@
+// Encode the geometries as TWKB (Tiny Well-Known Binary). The coordinates are
+// scaled by 10^precision, rounded to integers, and written as the zigzag-encoded
+// varint deltas from the previous coordinate. Z and M are dropped.
+//
+// cf. https://github.com/TWKB/Specification/blob/master/twkb.md
+
+use geo_types::{Coord, Geometry, LineString, Polygon};
+
+const TWKB_POINT: u8 = 1;
+const TWKB_LINESTRING: u8 = 2;
+const TWKB_POLYGON: u8 = 3;
+const TWKB_MULTIPOINT: u8 = 4;
+const TWKB_MULTILINESTRING: u8 = 5;
+const TWKB_MULTIPOLYGON: u8 = 6;
+const TWKB_GEOMETRYCOLLECTION: u8 = 7;
+
+// The metadata flag for an empty geometry
+const TWKB_EMPTY: u8 = 0x10;
+
+// The precision is stored as a signed 4-bit integer in the header.
+pub(crate) const TWKB_PRECISION_RANGE: std::ops::RangeInclusive<i64> = -7..=7;
+
+pub(crate) fn wkb_to_twkb(
+    wkb: &[u8],
+    precision: i32,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    use geo_traits::to_geo::ToGeoGeometry;
+
+    let geometry = wkb::reader::read_wkb(wkb).map_err(|e| e.to_string())?;
+    let mut writer = TwkbWriter::new(precision);
+    // geo_types cannot represent an empty point
+    match geometry.try_to_geometry() {
+        Some(geometry) => writer.geometry(&geometry),
+        None => writer.empty(TWKB_POINT),
+    }
+    Ok(writer.buffer)
+}
+
+struct TwkbWriter {
+    buffer: Vec<u8>,
+    precision: i32,
+    scale: f64,
+    // The previous coordinate, from which the next coordinate is the delta
+    last: [i64; 2],
+}
+
+impl TwkbWriter {
+    fn new(precision: i32) -> Self {
+        Self {
+            buffer: Vec::new(),
+            precision,
+            scale: 10f64.powi(precision),
+            last: [0, 0],
+        }
+    }
+
+    // Each geometry (including the members of a collection) has its own header,
+    // and the deltas start from the origin.
+    fn header(&mut self, geometry_type: u8, metadata: u8) {
+        let precision = zigzag(self.precision as i64) as u8;
+        self.buffer.push(geometry_type | (precision << 4));
+        self.buffer.push(metadata);
+        self.last = [0, 0];
+    }
+
+    fn empty(&mut self, geometry_type: u8) {
+        self.header(geometry_type, TWKB_EMPTY);
+    }
+
+    fn varint(&mut self, mut v: u64) {
+        while v >= 0x80 {
+            self.buffer.push((v as u8 & 0x7f) | 0x80);
+            v >>= 7;
+        }
+        self.buffer.push(v as u8);
+    }
+
+    fn coord(&mut self, coord: &Coord) {
+        for (i, v) in [coord.x, coord.y].into_iter().enumerate() {
+            let v = (v * self.scale).round() as i64;
+            self.varint(zigzag(v - self.last[i]));
+            self.last[i] = v;
+        }
+    }
+
+    fn coords(&mut self, line_string: &LineString) {
+        self.varint(line_string.0.len() as u64);
+        for coord in &line_string.0 {
+            self.coord(coord);
+        }
+    }
+
+    fn rings(&mut self, polygon: &Polygon) {
+        if polygon.exterior().0.is_empty() {
+            self.varint(0);
+            return;
+        }
+        self.varint(1 + polygon.interiors().len() as u64);
+        self.coords(polygon.exterior());
+        for interior in polygon.interiors() {
+            self.coords(interior);
+        }
+    }
+
+    fn geometry(&mut self, geometry: &Geometry) {
+        match geometry {
+            Geometry::Point(point) => {
+                self.header(TWKB_POINT, 0);
+                self.coord(&point.0);
+            }
+            Geometry::Line(line) => self.geometry(&Geometry::LineString(LineString(vec![
+                line.start, line.end,
+            ]))),
+            Geometry::LineString(line_string) if line_string.0.is_empty() => {
+                self.empty(TWKB_LINESTRING)
+            }
+            Geometry::LineString(line_string) => {
+                self.header(TWKB_LINESTRING, 0);
+                self.coords(line_string);
+            }
+            Geometry::Polygon(polygon) if polygon.exterior().0.is_empty() => {
+                self.empty(TWKB_POLYGON)
+            }
+            Geometry::Polygon(polygon) => {
+                self.header(TWKB_POLYGON, 0);
+                self.rings(polygon);
+            }
+            Geometry::MultiPoint(multi_point) if multi_point.0.is_empty() => {
+                self.empty(TWKB_MULTIPOINT)
+            }
+            Geometry::MultiPoint(multi_point) => {
+                self.header(TWKB_MULTIPOINT, 0);
+                self.varint(multi_point.0.len() as u64);
+                for point in &multi_point.0 {
+                    self.coord(&point.0);
+                }
+            }
+            Geometry::MultiLineString(multi_line_string) if multi_line_string.0.is_empty() => {
+                self.empty(TWKB_MULTILINESTRING)
+            }
+            Geometry::MultiLineString(multi_line_string) => {
+                self.header(TWKB_MULTILINESTRING, 0);
+                self.varint(multi_line_string.0.len() as u64);
+                for line_string in &multi_line_string.0 {
+                    self.coords(line_string);
+                }
+            }
+            Geometry::MultiPolygon(multi_polygon) if multi_polygon.0.is_empty() => {
+                self.empty(TWKB_MULTIPOLYGON)
+            }
+            Geometry::MultiPolygon(multi_polygon) => {
+                self.header(TWKB_MULTIPOLYGON, 0);
+                self.varint(multi_polygon.0.len() as u64);
+                for polygon in &multi_polygon.0 {
+                    self.rings(polygon);
+                }
+            }
+            Geometry::GeometryCollection(collection) if collection.0.is_empty() => {
+                self.empty(TWKB_GEOMETRYCOLLECTION)
+            }
+            Geometry::GeometryCollection(collection) => {
+                self.header(TWKB_GEOMETRYCOLLECTION, 0);
+                self.varint(collection.0.len() as u64);
+                for g in &collection.0 {
+                    self.geometry(g);
+                }
+            }
+            Geometry::Rect(rect) => self.geometry(&Geometry::Polygon(rect.to_polygon())),
+            Geometry::Triangle(triangle) => {
+                self.geometry(&Geometry::Polygon(triangle.to_polygon()))
+            }
+        }
+    }
+}
+
+fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{
+        line_string, point, polygon, Coord, Geometry, GeometryCollection, LineString, MultiPoint,
+        MultiPolygon, Polygon,
+    };
+
+    // A minimal decoder to check the round trip
+    struct TwkbReader<'a> {
+        buffer: &'a [u8],
+        pos: usize,
+        scale: f64,
+        last: [i64; 2],
+    }
+
+    impl TwkbReader<'_> {
+        fn varint(&mut self) -> u64 {
+            let mut v = 0u64;
+            let mut shift = 0;
+            loop {
+                let b = self.buffer[self.pos];
+                self.pos += 1;
+                v |= ((b & 0x7f) as u64) << shift;
+                if b & 0x80 == 0 {
+                    return v;
+                }
+                shift += 7;
+            }
+        }
+
+        fn coord(&mut self) -> Coord {
+            let mut xy = [0.0; 2];
+            for (i, v) in xy.iter_mut().enumerate() {
+                let zz = self.varint();
+                let delta = ((zz >> 1) as i64) ^ -((zz & 1) as i64);
+                self.last[i] += delta;
+                *v = self.last[i] as f64 / self.scale;
+            }
+            Coord { x: xy[0], y: xy[1] }
+        }
+
+        fn line_string(&mut self) -> LineString {
+            let n = self.varint();
+            LineString((0..n).map(|_| self.coord()).collect())
+        }
+
+        fn polygon(&mut self) -> Polygon {
+            let n = self.varint() as usize;
+            let mut rings: Vec<LineString> = (0..n).map(|_| self.line_string()).collect();
+            if rings.is_empty() {
+                return Polygon::new(LineString(vec![]), vec![]);
+            }
+            let exterior = rings.remove(0);
+            Polygon::new(exterior, rings)
+        }
+
+        fn geometry(&mut self) -> Geometry {
+            let header = self.buffer[self.pos];
+            let metadata = self.buffer[self.pos + 1];
+            self.pos += 2;
+            let zz = (header >> 4) as i64;
+            let precision = (zz >> 1) ^ -(zz & 1);
+            self.scale = 10f64.powi(precision as i32);
+            self.last = [0, 0];
+            assert_eq!(metadata & !super::TWKB_EMPTY, 0);
+            let is_empty = metadata & super::TWKB_EMPTY != 0;
+
+            match header & 0x0f {
+                super::TWKB_POINT => Geometry::Point(self.coord().into()),
+                super::TWKB_LINESTRING if is_empty => Geometry::LineString(LineString(vec![])),
+                super::TWKB_LINESTRING => Geometry::LineString(self.line_string()),
+                super::TWKB_POLYGON => Geometry::Polygon(self.polygon()),
+                super::TWKB_MULTIPOINT => {
+                    let n = self.varint();
+                    Geometry::MultiPoint(MultiPoint((0..n).map(|_| self.coord().into()).collect()))
+                }
+                super::TWKB_MULTIPOLYGON => {
+                    let n = self.varint();
+                    Geometry::MultiPolygon(MultiPolygon((0..n).map(|_| self.polygon()).collect()))
+                }
+                super::TWKB_GEOMETRYCOLLECTION => {
+                    let n = self.varint();
+                    Geometry::GeometryCollection(GeometryCollection(
+                        (0..n).map(|_| self.geometry()).collect(),
+                    ))
+                }
+                t => panic!("unexpected type: {t}"),
+            }
+        }
+    }
+
+    fn round_trip(geometry: &Geometry, precision: i32) -> Geometry {
+        let mut wkb = Vec::new();
+        wkb::writer::write_geometry(&mut wkb, geometry, &Default::default()).unwrap();
+        let twkb = super::wkb_to_twkb(&wkb, precision).unwrap();
+
+        let mut reader = TwkbReader {
+            buffer: &twkb,
+            pos: 0,
+            scale: 1.0,
+            last: [0, 0],
+        };
+        let decoded = reader.geometry();
+        assert_eq!(reader.pos, twkb.len());
+        decoded
+    }
+
+    fn assert_close(actual: &Geometry, expected: &Geometry, tolerance: f64) {
+        use geo::CoordsIter;
+
+        assert_eq!(actual.coords_count(), expected.coords_count());
+        for (a, e) in actual.coords_iter().zip(expected.coords_iter()) {
+            assert!(
+                (a.x - e.x).abs() <= tolerance && (a.y - e.y).abs() <= tolerance,
+                "{a:?} != {e:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_zigzag() {
+        assert_eq!(super::zigzag(0), 0);
+        assert_eq!(super::zigzag(-1), 1);
+        assert_eq!(super::zigzag(1), 2);
+        assert_eq!(super::zigzag(-2), 3);
+    }
+
+    #[test]
+    fn test_wkb_to_twkb() {
+        // POINT (1 2) with precision 0
+        let point: Geometry = point!(x: 1.0, y: 2.0).into();
+        let mut wkb = Vec::new();
+        wkb::writer::write_geometry(&mut wkb, &point, &Default::default()).unwrap();
+        assert_eq!(
+            super::wkb_to_twkb(&wkb, 0).unwrap(),
+            vec![0x01, 0x00, 0x02, 0x04]
+        );
+    }
+
+    #[test]
+    fn test_twkb_round_trip() {
+        let geometries: Vec<Geometry> = vec![
+            point!(x: 139.691706, y: 35.689487).into(),
+            line_string![(x: 0.1, y: 0.2), (x: -1.23456789, y: 5.0)].into(),
+            polygon!(
+                exterior: [(x: 0.0, y: 0.0), (x: 10.0, y: 0.0), (x: 10.0, y: 10.0), (x: 0.0, y: 0.0)],
+                interiors: [[(x: 1.0, y: 1.0), (x: 2.0, y: 1.0), (x: 2.0, y: 2.0), (x: 1.0, y: 1.0)]],
+            )
+            .into(),
+            MultiPoint(vec![point!(x: 1.5, y: 2.5), point!(x: -3.25, y: 4.125)]).into(),
+            Geometry::GeometryCollection(GeometryCollection(vec![
+                point!(x: 1.0, y: 2.0).into(),
+                line_string![(x: 3.0, y: 4.0), (x: 5.0, y: 6.0)].into(),
+            ])),
+        ];
+
+        for precision in [0, 3, 7] {
+            let tolerance = 0.5 / 10f64.powi(precision);
+            for geometry in &geometries {
+                assert_close(&round_trip(geometry, precision), geometry, tolerance);
+            }
+        }
+
+        // A negative precision rounds to tens
+        let point: Geometry = point!(x: 123.0, y: -456.0).into();
+        assert_close(
+            &round_trip(&point, -1),
+            &point!(x: 120.0, y: -460.0).into(),
+            0.0,
+        );
+
+        // An empty geometry has no coordinates
+        let empty: Geometry = LineString(vec![]).into();
+        assert_eq!(round_trip(&empty, 7), empty);
+    }
+}
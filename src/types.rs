@@ -1,11 +1,17 @@
 use duckdb::core::LogicalTypeHandle;
 use duckdb::core::LogicalTypeId;
-use std::sync::Arc;
-use std::sync::Mutex;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, OnceLock};
 
-use crate::geojson::GeoJsonDataSource;
-use crate::gpkg::GpkgDataSource;
+use crate::csv::CsvDataSource;
+use crate::geojson::{GeoJsonDataSource, OnTypeMismatch, PropertyLayout};
+use crate::gpkg::{GeometryColumnEncoding, GpkgConnection, GpkgDataSource};
+use crate::mapinfo::MifDataSource;
 use crate::shapefile::ShapefileDataSource;
+use crate::utils::{geometry_bounds, Prefetcher};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 #[repr(C)]
@@ -13,9 +19,12 @@ pub enum ColumnType {
     Boolean,
     Varchar,
     Double,
+    Real,
     Integer,
+    BigInt,
     Date,
     Timestamp,
+    TimestampTz,
     Blob,
     Geometry,
 }
@@ -25,9 +34,12 @@ impl From<ColumnType> for LogicalTypeHandle {
         match value {
             ColumnType::Boolean => LogicalTypeId::Boolean.into(),
             ColumnType::Double => LogicalTypeId::Double.into(),
+            ColumnType::Real => LogicalTypeId::Float.into(),
             ColumnType::Integer => LogicalTypeId::Integer.into(),
+            ColumnType::BigInt => LogicalTypeId::Bigint.into(),
             ColumnType::Date => LogicalTypeId::Date.into(),
             ColumnType::Timestamp => LogicalTypeId::Timestamp.into(),
+            ColumnType::TimestampTz => LogicalTypeId::TimestampTZ.into(),
             ColumnType::Varchar => LogicalTypeId::Varchar.into(),
             ColumnType::Blob => LogicalTypeId::Blob.into(),
             ColumnType::Geometry => LogicalTypeId::Blob.into(),
@@ -35,6 +47,293 @@ impl From<ColumnType> for LogicalTypeHandle {
     }
 }
 
+// The Arrow types of the columns (experimental, only with `arrow` feature). The
+// result is still emitted as DuckDB vectors; this is for the consumers that want
+// to build RecordBatches of the same layout.
+#[cfg(feature = "arrow")]
+impl From<ColumnType> for duckdb::arrow::datatypes::DataType {
+    fn from(value: ColumnType) -> Self {
+        use duckdb::arrow::datatypes::{DataType, TimeUnit};
+
+        match value {
+            ColumnType::Boolean => DataType::Boolean,
+            ColumnType::Double => DataType::Float64,
+            ColumnType::Real => DataType::Float32,
+            ColumnType::Integer => DataType::Int32,
+            ColumnType::BigInt => DataType::Int64,
+            ColumnType::Date => DataType::Date32,
+            ColumnType::Timestamp => DataType::Timestamp(TimeUnit::Microsecond, None),
+            ColumnType::TimestampTz => {
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+            }
+            ColumnType::Varchar => DataType::Utf8,
+            ColumnType::Blob => DataType::Binary,
+            ColumnType::Geometry => DataType::Binary,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    GeoJson,
+    EsriJson,
+    Gpkg,
+    Shapefile,
+    Dbf,
+    Csv,
+    Mif,
+}
+
+impl std::str::FromStr for Format {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "geojson" => Ok(Self::GeoJson),
+            "esrijson" => Ok(Self::EsriJson),
+            "gpkg" | "geopackage" => Ok(Self::Gpkg),
+            "shapefile" | "shp" => Ok(Self::Shapefile),
+            "dbf" => Ok(Self::Dbf),
+            "csv" => Ok(Self::Csv),
+            "mif" | "mapinfo" => Ok(Self::Mif),
+            _ => Err(format!(
+                "Unknown value in 'format' option: {s} (must be 'geojson', 'esrijson', 'gpkg', 'shapefile', 'dbf', 'csv', or 'mif')"
+            )
+            .into()),
+        }
+    }
+}
+
+// The orientation of the polygon rings (`winding` option). The interior rings are
+// always oriented in the opposite direction to the exterior ring.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Winding {
+    // exterior rings clockwise
+    Cw,
+    // exterior rings counterclockwise. This is also what RFC 7946 requires.
+    Ccw,
+}
+
+impl std::str::FromStr for Winding {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cw" => Ok(Self::Cw),
+            "ccw" | "rfc7946" => Ok(Self::Ccw),
+            _ => Err(format!(
+                "Unknown value in 'winding' option: {s} (must be 'cw', 'ccw', or 'rfc7946')"
+            )
+            .into()),
+        }
+    }
+}
+
+// The axis order of the coordinates in the input (`coordinate_order` option). Some
+// data stores them in latitude/longitude order against the GeoJSON spec.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CoordinateOrder {
+    #[default]
+    LonLat,
+    // the axes are swapped while reading
+    LatLon,
+}
+
+impl std::str::FromStr for CoordinateOrder {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "lonlat" => Ok(Self::LonLat),
+            "latlon" => Ok(Self::LatLon),
+            _ => Err(format!(
+                "Unknown value in 'coordinate_order' option: {s} (must be 'lonlat' or 'latlon')"
+            )
+            .into()),
+        }
+    }
+}
+
+// How to handle GeometryCollections (`collection_mode` option).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CollectionMode {
+    // read as it is
+    #[default]
+    Keep,
+    // read the first member. An empty collection becomes NULL.
+    First,
+    // read as NULL
+    Null,
+}
+
+impl std::str::FromStr for CollectionMode {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "keep" => Ok(Self::Keep),
+            "first" => Ok(Self::First),
+            "null" => Ok(Self::Null),
+            "explode" => Err("'explode' in 'collection_mode' option is not supported yet".into()),
+            _ => Err(format!(
+                "Unknown value in 'collection_mode' option: {s} (must be 'keep', 'first', or 'null')"
+            )
+            .into()),
+        }
+    }
+}
+
+// The geometry type all the geometries are coerced to (`geometry_type` option).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TargetGeometryType {
+    Point,
+    LineString,
+    Polygon,
+    MultiPoint,
+    MultiLineString,
+    MultiPolygon,
+}
+
+impl std::str::FromStr for TargetGeometryType {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "point" => Ok(Self::Point),
+            "linestring" => Ok(Self::LineString),
+            "polygon" => Ok(Self::Polygon),
+            "multipoint" => Ok(Self::MultiPoint),
+            "multilinestring" => Ok(Self::MultiLineString),
+            "multipolygon" => Ok(Self::MultiPolygon),
+            _ => Err(format!(
+                "Unknown value in 'geometry_type' option: {s} (must be 'point', 'linestring', 'polygon', 'multipoint', 'multilinestring', or 'multipolygon')"
+            )
+            .into()),
+        }
+    }
+}
+
+impl std::fmt::Display for TargetGeometryType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Point => "POINT",
+            Self::LineString => "LINESTRING",
+            Self::Polygon => "POLYGON",
+            Self::MultiPoint => "MULTIPOINT",
+            Self::MultiLineString => "MULTILINESTRING",
+            Self::MultiPolygon => "MULTIPOLYGON",
+        };
+        f.write_str(name)
+    }
+}
+
+// The encoding of the geometry column (`geometry_format` option).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GeometryFormat {
+    #[default]
+    Wkb,
+    // TWKB with the number of decimal places to keep
+    Twkb(i32),
+}
+
+// A bounding box to filter the rows (`bbox` option).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bbox {
+    pub xmin: f64,
+    pub ymin: f64,
+    pub xmax: f64,
+    pub ymax: f64,
+}
+
+impl Bbox {
+    pub fn intersects(&self, other: &Bbox) -> bool {
+        self.xmin <= other.xmax
+            && self.xmax >= other.xmin
+            && self.ymin <= other.ymax
+            && self.ymax >= other.ymin
+    }
+}
+
+// A spatial filter by an arbitrary geometry (`filter_wkt` option), e.g. the boundary
+// of a country. The rows whose geometries don't intersect it are skipped. The bbox
+// is checked first because it's much cheaper than the intersection test.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpatialFilter {
+    pub geometry: geo_types::Geometry<f64>,
+    pub bbox: Bbox,
+}
+
+impl SpatialFilter {
+    pub fn intersects(&self, geometry: &geo_types::Geometry<f64>) -> bool {
+        use geo::Intersects;
+
+        geometry_bounds(geometry).is_some_and(|b| b.intersects(&self.bbox))
+            && self.geometry.intersects(geometry)
+    }
+
+    // An empty or invalid WKB never intersects
+    pub fn intersects_wkb(&self, wkb: &[u8]) -> bool {
+        use geo_traits::to_geo::ToGeoGeometry;
+
+        wkb::reader::read_wkb(wkb)
+            .ok()
+            .and_then(|g| g.try_to_geometry())
+            .is_some_and(|g| self.intersects(&g))
+    }
+}
+
+impl std::str::FromStr for SpatialFilter {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use wkt::TryFromWkt;
+
+        let geometry = geo_types::Geometry::<f64>::try_from_wkt_str(s)
+            .map_err(|e| format!("'filter_wkt' must be a WKT geometry: {s} ({e})"))?;
+        let bbox = geometry_bounds(&geometry).ok_or("'filter_wkt' must not be empty")?;
+        Ok(Self { geometry, bbox })
+    }
+}
+
+// The attribute columns to read (`include_columns` and `exclude_columns`).
+#[derive(Clone, Debug, Default)]
+pub struct ColumnFilter {
+    // None means all the columns
+    pub include: Option<Vec<String>>,
+    pub exclude: Vec<String>,
+}
+
+impl ColumnFilter {
+    pub fn keeps(&self, name: &str) -> bool {
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.iter().any(|c| c == name))
+            && !self.exclude.iter().any(|c| c == name)
+    }
+
+    // Drop the values of the filtered columns. This is for the rows that store the
+    // values in the same order as `specs`.
+    pub fn retain_values<T>(&self, specs: &[ColumnSpec], values: &mut Vec<T>) {
+        let mut specs = specs.iter();
+        values.retain(|_| specs.next().is_some_and(|s| self.keeps(&s.name)));
+    }
+
+    // Check if all the included columns exist in the filtered columns.
+    pub fn validate<'a, I>(&self, specs: I) -> Result<(), Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = &'a ColumnSpec>,
+    {
+        let Some(include) = &self.include else {
+            return Ok(());
+        };
+        let names: Vec<&str> = specs.into_iter().map(|s| s.name.as_str()).collect();
+        match include.iter().find(|c| !names.contains(&c.as_str())) {
+            Some(name) => Err(format!("Column '{name}' in 'include_columns' doesn't exist").into()),
+            None => Ok(()),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 #[repr(C)]
 pub struct ColumnSpec {
@@ -42,22 +341,178 @@ pub struct ColumnSpec {
     pub column_type: ColumnType,
 }
 
+// A geometry column is tagged as GeoArrow WKB so that the consumers can tell it
+// from a plain BLOB column.
+//
+// cf. https://geoarrow.org/extension-types.html#extension-names
+#[cfg(feature = "arrow")]
+impl From<&ColumnSpec> for duckdb::arrow::datatypes::Field {
+    fn from(value: &ColumnSpec) -> Self {
+        let field =
+            duckdb::arrow::datatypes::Field::new(&value.name, value.column_type.into(), true);
+        if value.column_type == ColumnType::Geometry {
+            field.with_metadata(
+                [(
+                    "ARROW:extension:name".to_string(),
+                    "geoarrow.wkb".to_string(),
+                )]
+                .into(),
+            )
+        } else {
+            field
+        }
+    }
+}
+
 #[repr(C)]
 pub struct GeoJsonBindData {
     pub sources: Vec<GeoJsonDataSource>,
     pub column_specs: Vec<ColumnSpec>,
+    // The order of the properties of each data source
+    pub property_layouts: Vec<PropertyLayout>,
+    pub foreign_member_specs: Vec<ColumnSpec>,
+    pub coordinate_order: CoordinateOrder,
+    pub coordinate_precision: Option<i32>,
+    pub centroid: bool,
+    pub geometry_format: GeometryFormat,
+    pub winding: Option<Winding>,
+    pub buffer_distance: Option<f64>,
+    pub max_segment_length: Option<f64>,
+    pub collection_mode: CollectionMode,
+    pub geometry_type: Option<TargetGeometryType>,
+    pub on_geometry_type_mismatch: OnTypeMismatch,
+    pub chunk_size: usize,
+    pub distinct: bool,
+    pub strict: bool,
+    pub spatial_filter: Option<SpatialFilter>,
+    pub geometry_only: bool,
+    pub include_mtime: bool,
+    // cf. `filename_relative_to`
+    pub filename_relative_to: Option<PathBuf>,
+    pub geometry_last: bool,
+    pub struct_attributes: bool,
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub include_crs: bool,
+    pub on_type_mismatch: OnTypeMismatch,
 }
 
 #[repr(C)]
 pub struct GpkgBindData {
     pub sources: Vec<GpkgDataSource>,
     pub column_specs: Vec<ColumnSpec>,
+    // The maximum number of the connections open at the same time
+    pub max_open_files: usize,
+    // Open the files with the immutable flag (cf. GpkgConnection::open())
+    pub immutable: bool,
+    // Read the file of the next data source in background (cf. get_prefetch())
+    pub prefetch: bool,
+    // Prefix the layer name with the filename in `.layer` column
+    pub qualify_layer_with_filename: bool,
+    pub geometry_column_encoding: GeometryColumnEncoding,
+    pub coordinate_precision: Option<i32>,
+    pub centroid: bool,
+    pub geometry_format: GeometryFormat,
+    pub winding: Option<Winding>,
+    pub buffer_distance: Option<f64>,
+    pub max_segment_length: Option<f64>,
+    pub collection_mode: CollectionMode,
+    pub chunk_size: usize,
+    pub distinct: bool,
+    pub strict: bool,
+    pub spatial_filter: Option<SpatialFilter>,
+    pub force_2d: bool,
+    pub geometry_only: bool,
+    pub include_layer_metadata: bool,
+    pub include_mtime: bool,
+    // cf. `filename_relative_to`
+    pub filename_relative_to: Option<PathBuf>,
+    pub geometry_last: bool,
+    pub struct_attributes: bool,
+    pub offset: usize,
+    pub limit: Option<usize>,
 }
 
 #[repr(C)]
 pub struct ShapefileBindData {
     pub sources: Vec<ShapefileDataSource>,
     pub column_specs: Vec<ColumnSpec>,
+    pub include_encoding: bool,
+    pub include_metadata: bool,
+    pub include_deleted: bool,
+    // `.raw_bytes` column (cf. `raw_bytes_for`)
+    pub include_raw_bytes: bool,
+    // false if the sources are standalone .dbf files
+    pub has_geometry: bool,
+    // Read the files of the next data source in background (cf. get_prefetch())
+    pub prefetch: bool,
+    pub coordinate_precision: Option<i32>,
+    pub centroid: bool,
+    pub geometry_format: GeometryFormat,
+    pub winding: Option<Winding>,
+    pub buffer_distance: Option<f64>,
+    pub max_segment_length: Option<f64>,
+    pub geometry_type: Option<TargetGeometryType>,
+    pub on_geometry_type_mismatch: OnTypeMismatch,
+    pub chunk_size: usize,
+    pub distinct: bool,
+    pub strict: bool,
+    pub spatial_filter: Option<SpatialFilter>,
+    pub force_2d: bool,
+    pub geometry_only: bool,
+    pub include_mtime: bool,
+    // cf. `filename_relative_to`
+    pub filename_relative_to: Option<PathBuf>,
+    pub geometry_last: bool,
+    pub struct_attributes: bool,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[repr(C)]
+pub struct CsvBindData {
+    pub sources: Vec<CsvDataSource>,
+    pub column_specs: Vec<ColumnSpec>,
+    pub coordinate_order: CoordinateOrder,
+    pub coordinate_precision: Option<i32>,
+    pub centroid: bool,
+    pub geometry_format: GeometryFormat,
+    pub chunk_size: usize,
+    pub distinct: bool,
+    pub strict: bool,
+    pub spatial_filter: Option<SpatialFilter>,
+    pub geometry_only: bool,
+    pub include_mtime: bool,
+    // cf. `filename_relative_to`
+    pub filename_relative_to: Option<PathBuf>,
+    pub geometry_last: bool,
+    pub struct_attributes: bool,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[repr(C)]
+pub struct MifBindData {
+    pub sources: Vec<MifDataSource>,
+    pub column_specs: Vec<ColumnSpec>,
+    pub coordinate_precision: Option<i32>,
+    pub centroid: bool,
+    pub geometry_format: GeometryFormat,
+    pub winding: Option<Winding>,
+    pub buffer_distance: Option<f64>,
+    pub max_segment_length: Option<f64>,
+    pub chunk_size: usize,
+    pub distinct: bool,
+    pub strict: bool,
+    pub spatial_filter: Option<SpatialFilter>,
+    pub geometry_only: bool,
+    pub include_mtime: bool,
+    // cf. `filename_relative_to`
+    pub filename_relative_to: Option<PathBuf>,
+    pub geometry_last: bool,
+    pub struct_attributes: bool,
+    pub offset: usize,
+    pub limit: Option<usize>,
 }
 
 #[repr(C)]
@@ -65,6 +520,8 @@ pub enum StReadMultiBindData {
     GeoJson(GeoJsonBindData),
     Gpkg(GpkgBindData),
     Shapefile(ShapefileBindData),
+    Csv(CsvBindData),
+    Mif(MifBindData),
 }
 
 impl From<GeoJsonBindData> for StReadMultiBindData {
@@ -85,21 +542,848 @@ impl From<ShapefileBindData> for StReadMultiBindData {
     }
 }
 
+impl From<CsvBindData> for StReadMultiBindData {
+    fn from(value: CsvBindData) -> Self {
+        Self::Csv(value)
+    }
+}
+
+impl From<MifBindData> for StReadMultiBindData {
+    fn from(value: MifBindData) -> Self {
+        Self::Mif(value)
+    }
+}
+
+impl StReadMultiBindData {
+    pub fn n_sources(&self) -> usize {
+        match self {
+            Self::GeoJson(bind_data) => bind_data.sources.len(),
+            Self::Gpkg(bind_data) => bind_data.sources.len(),
+            Self::Shapefile(bind_data) => bind_data.sources.len(),
+            Self::Csv(bind_data) => bind_data.sources.len(),
+            Self::Mif(bind_data) => bind_data.sources.len(),
+        }
+    }
+
+    // Drop all the data sources so that the scan emits no rows. The result columns
+    // are already determined at this point, so the schema is still reported.
+    pub fn discard_sources(&mut self) {
+        match self {
+            Self::GeoJson(bind_data) => bind_data.sources.clear(),
+            Self::Gpkg(bind_data) => bind_data.sources.clear(),
+            Self::Shapefile(bind_data) => bind_data.sources.clear(),
+            Self::Csv(bind_data) => bind_data.sources.clear(),
+            Self::Mif(bind_data) => bind_data.sources.clear(),
+        }
+    }
+
+    // The files to read in advance before the data source is scanned. This is empty
+    // unless `prefetch` is specified. A GeoPackage layer with `bbox` is skipped
+    // because only a part of the file might be read via the spatial index.
+    pub fn prefetch_paths(&self, source_idx: usize) -> Vec<PathBuf> {
+        match self {
+            Self::Gpkg(bind_data)
+                if bind_data.prefetch && bind_data.sources[source_idx].bbox.is_none() =>
+            {
+                vec![PathBuf::from(&bind_data.sources[source_idx].path)]
+            }
+            Self::Shapefile(bind_data) if bind_data.prefetch => {
+                bind_data.sources[source_idx].file_paths()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        match self {
+            Self::GeoJson(bind_data) => bind_data.chunk_size,
+            Self::Gpkg(bind_data) => bind_data.chunk_size,
+            Self::Shapefile(bind_data) => bind_data.chunk_size,
+            Self::Csv(bind_data) => bind_data.chunk_size,
+            Self::Mif(bind_data) => bind_data.chunk_size,
+        }
+    }
+
+    pub fn geometry_last(&self) -> bool {
+        match self {
+            Self::GeoJson(bind_data) => bind_data.geometry_last,
+            Self::Gpkg(bind_data) => bind_data.geometry_last,
+            Self::Shapefile(bind_data) => bind_data.geometry_last,
+            Self::Csv(bind_data) => bind_data.geometry_last,
+            Self::Mif(bind_data) => bind_data.geometry_last,
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::GeoJson(bind_data) => bind_data.offset,
+            Self::Gpkg(bind_data) => bind_data.offset,
+            Self::Shapefile(bind_data) => bind_data.offset,
+            Self::Csv(bind_data) => bind_data.offset,
+            Self::Mif(bind_data) => bind_data.offset,
+        }
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        match self {
+            Self::GeoJson(bind_data) => bind_data.limit,
+            Self::Gpkg(bind_data) => bind_data.limit,
+            Self::Shapefile(bind_data) => bind_data.limit,
+            Self::Csv(bind_data) => bind_data.limit,
+            Self::Mif(bind_data) => bind_data.limit,
+        }
+    }
+
+    // true if some rows are skipped while reading them (e.g. by `distinct`). In this
+    // case, `offset` and `limit` need to count the rows after the filters, because
+    // the number of the rows that pass them isn't known until the chunk is read.
+    pub fn filters_rows(&self) -> bool {
+        match self {
+            Self::GeoJson(bind_data) => bind_data.distinct || bind_data.spatial_filter.is_some(),
+            Self::Gpkg(bind_data) => {
+                bind_data.distinct
+                    || bind_data.spatial_filter.is_some()
+                    || bind_data.sources.iter().any(|s| s.bbox.is_some())
+            }
+            Self::Shapefile(bind_data) => bind_data.distinct || bind_data.spatial_filter.is_some(),
+            Self::Csv(bind_data) => bind_data.distinct || bind_data.spatial_filter.is_some(),
+            Self::Mif(bind_data) => bind_data.distinct || bind_data.spatial_filter.is_some(),
+        }
+    }
+
+    pub fn n_rows(&self, source_idx: usize) -> usize {
+        match self {
+            Self::GeoJson(bind_data) => bind_data.sources[source_idx].features.len(),
+            Self::Gpkg(bind_data) => bind_data.sources[source_idx].n_rows,
+            Self::Shapefile(bind_data) => bind_data.sources[source_idx].n_rows(),
+            Self::Csv(bind_data) => bind_data.sources[source_idx].rows.len(),
+            Self::Mif(bind_data) => bind_data.sources[source_idx].rows.len(),
+        }
+    }
+}
+
+// The position of the next chunk to be claimed.
 pub struct Cursor {
     pub source_idx: usize,
     pub offset: usize,
+    // The number of rows skipped so far by `offset` option
+    pub n_skipped: usize,
+    // The number of rows claimed so far, excluding the skipped ones
+    pub n_claimed: usize,
 }
 
+impl Cursor {
+    pub fn new() -> Self {
+        Self {
+            source_idx: 0,
+            offset: 0,
+            n_skipped: 0,
+            n_claimed: 0,
+        }
+    }
+}
+
+// The rows counted by `offset` and `limit` after the filters applied while reading
+// (cf. StReadMultiBindData::filters_rows())
+#[derive(Default)]
+pub struct FilteredPage {
+    pub n_skipped: usize,
+    pub n_emitted: usize,
+}
+
+// A range of rows in a data source, which is read by one func() call.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Chunk {
+    pub source_idx: usize,
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl Chunk {
+    pub fn range(&self) -> Range<usize> {
+        self.offset..(self.offset + self.len)
+    }
+}
+
+// Connections to GeoPackage files.
+pub struct GpkgConnectionPool {
+    // The connections that are not used by any thread
+    pub idle: Vec<GpkgConnection>,
+    // The number of the connections currently open, including the ones in use
+    pub n_open: usize,
+}
+
+// This is shared by all the threads that scan the data. DuckDB calls init() for
+// each scan, so when the same bind data is scanned more than once (e.g. both sides
+// of a self-join), every scan gets its own cursor starting from the first row.
 #[repr(C)]
 pub struct StReadMultiInitData {
-    pub cursor: Arc<Mutex<Cursor>>,
+    pub cursor: Mutex<Cursor>,
+    // A thread takes a connection from the pool while reading a chunk and puts it
+    // back after that.
+    pub gpkg_conns: Mutex<GpkgConnectionPool>,
+    // Notified when a connection is put back to the pool.
+    pub gpkg_conns_released: Condvar,
+    pub max_idle_gpkg_conns: usize,
+    // The hashes of the rows already emitted (only when `distinct` is specified).
+    pub seen_rows: Mutex<HashSet<u64>>,
+    // The rows counted by `offset` and `limit` after the filters. None if they are
+    // counted on claiming the chunks instead (cf. StReadMultiInitData::new_for()).
+    pub filtered_page: Option<Mutex<FilteredPage>>,
+    // The number of the data sources whose files are already prefetched (only when
+    // `prefetch` is specified)
+    pub n_prefetched: AtomicUsize,
+    // The thread that reads the files in background. This is started on the first
+    // request, and stopped when the scan is over.
+    pub prefetcher: OnceLock<Prefetcher>,
 }
 
-impl Cursor {
-    pub fn new() -> Self {
+impl StReadMultiInitData {
+    pub fn new(max_threads: usize) -> Self {
         Self {
-            source_idx: 0,
+            cursor: Mutex::new(Cursor::new()),
+            gpkg_conns: Mutex::new(GpkgConnectionPool {
+                idle: Vec::new(),
+                n_open: 0,
+            }),
+            gpkg_conns_released: Condvar::new(),
+            max_idle_gpkg_conns: max_threads,
+            seen_rows: Mutex::new(HashSet::new()),
+            filtered_page: None,
+            n_prefetched: AtomicUsize::new(0),
+            prefetcher: OnceLock::new(),
+        }
+    }
+
+    // If the rows are filtered while reading, `offset` and `limit` count the rows
+    // that pass the filters. Which rows pass them first depends on the order the
+    // rows are read in, so the scan needs to be single-threaded to be
+    // deterministic. Returns the init data and the maximum number of the threads.
+    pub fn new_for(bind_data: &StReadMultiBindData, max_threads: usize) -> (Self, usize) {
+        let mut init_data = Self::new(max_threads);
+        if bind_data.filters_rows() && (bind_data.offset() > 0 || bind_data.limit().is_some()) {
+            init_data.filtered_page = Some(Mutex::new(FilteredPage::default()));
+            return (init_data, 1);
+        }
+        (init_data, max_threads)
+    }
+
+    // Count a row that passed the filters. Returns false if the row is before
+    // `offset` or after `limit`. This is always true if `offset` and `limit` are
+    // applied on claiming the chunks.
+    pub fn is_in_page(&self, bind_data: &StReadMultiBindData) -> bool {
+        let Some(page) = &self.filtered_page else {
+            return true;
+        };
+        let mut page = page.lock().unwrap_or_else(|e| e.into_inner());
+        if page.n_skipped < bind_data.offset() {
+            page.n_skipped += 1;
+            return false;
+        }
+        if bind_data
+            .limit()
+            .is_some_and(|limit| page.n_emitted >= limit)
+        {
+            return false;
+        }
+        page.n_emitted += 1;
+        true
+    }
+
+    // Record the hash of a row. Returns true if the same hash is already recorded,
+    // i.e. the row is a duplicate of some row emitted before.
+    pub fn is_duplicate(&self, hash: u64) -> bool {
+        let mut seen_rows = self.seen_rows.lock().unwrap_or_else(|e| e.into_inner());
+        !seen_rows.insert(hash)
+    }
+
+    // Start reading the files of the data source next to the one currently being
+    // scanned. This is done only once per data source, by the thread that claims
+    // the first chunk of the current one. With `limit`, the scan might not reach
+    // the next data source, so nothing is prefetched.
+    pub fn prefetch_next_source(&self, bind_data: &StReadMultiBindData, source_idx: usize) {
+        let next_idx = source_idx + 1;
+        if bind_data.limit().is_some()
+            || next_idx >= bind_data.n_sources()
+            || self.n_prefetched.fetch_max(next_idx + 1, Ordering::Relaxed) > next_idx
+        {
+            return;
+        }
+
+        // The layers of the same GeoPackage share the file, which is already read.
+        let current_paths = bind_data.prefetch_paths(source_idx);
+        let paths: Vec<PathBuf> = bind_data
+            .prefetch_paths(next_idx)
+            .into_iter()
+            .filter(|p| !current_paths.contains(p))
+            .collect();
+        if !paths.is_empty() {
+            self.prefetcher.get_or_init(Prefetcher::new).request(paths);
+        }
+    }
+
+    // Fill an output with the chunks of multiple data sources until it's full, so
+    // that many tiny files don't result in many tiny outputs. `fill` writes the rows
+    // of the chunk after the `n_filled` rows and returns the new number of the rows
+    // in the output, which might grow less than the chunk (e.g. by `distinct`).
+    // Returns the number of the rows in the output; 0 means the end of the scan.
+    pub fn fill_output<F>(
+        &self,
+        bind_data: &StReadMultiBindData,
+        mut fill: F,
+    ) -> Result<usize, Box<dyn std::error::Error>>
+    where
+        F: FnMut(Chunk, usize) -> Result<usize, Box<dyn std::error::Error>>,
+    {
+        let capacity = bind_data.chunk_size();
+        let mut n_filled = 0;
+        while n_filled < capacity {
+            let chunk = match self.claim_chunk_up_to(bind_data, capacity - n_filled)? {
+                Some(chunk) => chunk,
+                None => break,
+            };
+            self.prefetch_next_source(bind_data, chunk.source_idx);
+            n_filled = fill(chunk, n_filled)?;
+        }
+        Ok(n_filled)
+    }
+
+    // Claim the next chunk of at most `chunk_size` rows. Returns None if all the
+    // data sources are consumed, or `limit` rows are already claimed.
+    pub fn claim_chunk(
+        &self,
+        bind_data: &StReadMultiBindData,
+    ) -> Result<Option<Chunk>, Box<dyn std::error::Error>> {
+        self.claim_chunk_up_to(bind_data, bind_data.chunk_size())
+    }
+
+    // Same as claim_chunk(), but the chunk is at most `max_len` rows. This is used
+    // to fill the rest of the output with the rows of the next data source.
+    pub fn claim_chunk_up_to(
+        &self,
+        bind_data: &StReadMultiBindData,
+        max_len: usize,
+    ) -> Result<Option<Chunk>, Box<dyn std::error::Error>> {
+        let mut cursor = match self.cursor.lock() {
+            Ok(cursor) => cursor,
+            Err(_) => return Err("Failed to acquire the lock of the cursor".into()),
+        };
+
+        let chunk_size = std::cmp::min(bind_data.chunk_size(), max_len);
+        // If the rows are counted after the filters (cf. is_in_page()), only stop
+        // once `limit` rows are emitted.
+        let (offset, limit) = match &self.filtered_page {
+            Some(page) => {
+                let page = page.lock().unwrap_or_else(|e| e.into_inner());
+                if bind_data
+                    .limit()
+                    .is_some_and(|limit| page.n_emitted >= limit)
+                {
+                    return Ok(None);
+                }
+                (0, None)
+            }
+            None => (bind_data.offset(), bind_data.limit()),
+        };
+
+        while cursor.source_idx < bind_data.n_sources() {
+            let n_rows = bind_data.n_rows(cursor.source_idx);
+
+            // proceed to the next data source
+            if cursor.offset >= n_rows {
+                cursor.source_idx += 1;
+                cursor.offset = 0;
+                continue;
+            }
+
+            // skip the rows before `offset` without reading them
+            if cursor.n_skipped < offset {
+                let n = std::cmp::min(offset - cursor.n_skipped, n_rows - cursor.offset);
+                cursor.offset += n;
+                cursor.n_skipped += n;
+                continue;
+            }
+
+            let mut len = std::cmp::min(chunk_size, n_rows - cursor.offset);
+            if let Some(limit) = limit {
+                if cursor.n_claimed >= limit {
+                    return Ok(None);
+                }
+                len = std::cmp::min(len, limit - cursor.n_claimed);
+            }
+
+            let chunk = Chunk {
+                source_idx: cursor.source_idx,
+                offset: cursor.offset,
+                len,
+            };
+            cursor.offset += chunk.len;
+            cursor.n_claimed += chunk.len;
+
+            return Ok(Some(chunk));
+        }
+
+        Ok(None)
+    }
+
+    // Take an idle connection to the file, or open a new one if there's none. If the
+    // number of open connections reaches `max_open`, close an idle connection to
+    // another file, or wait until some connection is put back.
+    pub fn take_gpkg_connection(
+        &self,
+        path: &str,
+        max_open: usize,
+        immutable: bool,
+    ) -> Result<GpkgConnection, Box<dyn std::error::Error>> {
+        let lock_err = || -> Box<dyn std::error::Error> {
+            "Failed to acquire the lock of the connections".into()
+        };
+
+        let mut pool = self.gpkg_conns.lock().map_err(|_| lock_err())?;
+        loop {
+            if let Some(i) = pool.idle.iter().position(|c| c.path == path) {
+                return Ok(pool.idle.swap_remove(i));
+            }
+
+            if pool.n_open < max_open {
+                pool.n_open += 1;
+                break;
+            }
+
+            if !pool.idle.is_empty() {
+                // close the oldest one
+                pool.idle.remove(0);
+                pool.n_open -= 1;
+                continue;
+            }
+
+            pool = self
+                .gpkg_conns_released
+                .wait(pool)
+                .map_err(|_| lock_err())?;
+        }
+        drop(pool);
+
+        match GpkgConnection::open(path, immutable) {
+            Ok(conn) => Ok(conn),
+            Err(e) => {
+                self.close_gpkg_connection();
+                Err(e.into())
+            }
+        }
+    }
+
+    pub fn put_back_gpkg_connection(&self, conn: GpkgConnection) {
+        if let Ok(mut pool) = self.gpkg_conns.lock() {
+            pool.idle.push(conn);
+            // close the oldest ones so that the connections don't pile up
+            while pool.idle.len() > self.max_idle_gpkg_conns {
+                pool.idle.remove(0);
+                pool.n_open -= 1;
+            }
+        }
+        self.gpkg_conns_released.notify_one();
+    }
+
+    // Close a connection taken from the pool without putting it back.
+    pub fn close_gpkg_connection(&self) {
+        if let Ok(mut pool) = self.gpkg_conns.lock() {
+            pool.n_open -= 1;
+        }
+        self.gpkg_conns_released.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Chunk, CollectionMode, ColumnFilter, ColumnSpec, ColumnType, CoordinateOrder,
+        GeoJsonBindData, GeometryFormat, StReadMultiBindData, StReadMultiInitData,
+    };
+    use crate::geojson::{GeoJsonDataSource, OnTypeMismatch};
+    use crate::gpkg::GpkgConnection;
+
+    fn many_rows_bind_data(
+        chunk_size: usize,
+    ) -> Result<StReadMultiBindData, Box<dyn std::error::Error>> {
+        let mut sources = Vec::new();
+        let mut column_specs = Vec::new();
+        for path in [
+            "./test/data/many_rows/points_2048.geojson",
+            "./test/data/many_rows/points_2049.geojson",
+        ] {
+            let (mut s, c) = GeoJsonDataSource::parse_and_split(path, false, &[], "geometry", "")?;
+            sources.append(&mut s);
+            column_specs = c;
+        }
+        Ok(geojson_bind_data(sources, column_specs, chunk_size))
+    }
+
+    // Many one-feature data sources, like the ones read from many tiny files
+    fn one_feature_bind_data(
+        n_sources: usize,
+    ) -> Result<StReadMultiBindData, Box<dyn std::error::Error>> {
+        let (mut s, column_specs) = GeoJsonDataSource::parse_and_split(
+            "./test/data/points.geojson",
+            false,
+            &[],
+            "geometry",
+            "",
+        )?;
+        let source = s.remove(0);
+        let sources = (0..n_sources)
+            .map(|i| GeoJsonDataSource {
+                features: vec![source.features[i % source.features.len()].clone()],
+                filename: format!("{i}.geojson"),
+                crs: None,
+                mtime: None,
+            })
+            .collect();
+        Ok(geojson_bind_data(sources, column_specs, crate::VECTOR_SIZE))
+    }
+
+    fn geojson_bind_data(
+        sources: Vec<GeoJsonDataSource>,
+        column_specs: Vec<ColumnSpec>,
+        chunk_size: usize,
+    ) -> StReadMultiBindData {
+        GeoJsonBindData {
+            property_layouts: sources
+                .iter()
+                .map(|s| PropertyLayout::new(s, &column_specs))
+                .collect(),
+            sources,
+            column_specs,
+            foreign_member_specs: Vec::new(),
+            coordinate_order: CoordinateOrder::LonLat,
+            coordinate_precision: None,
+            centroid: false,
+            geometry_format: GeometryFormat::Wkb,
+            winding: None,
+            buffer_distance: None,
+            max_segment_length: None,
+            collection_mode: CollectionMode::Keep,
+            geometry_type: None,
+            on_geometry_type_mismatch: OnTypeMismatch::Error,
+            chunk_size,
+            distinct: false,
+            strict: false,
+            spatial_filter: None,
+            geometry_only: false,
+            include_mtime: false,
+            filename_relative_to: None,
+            geometry_last: false,
+            struct_attributes: false,
             offset: 0,
+            limit: None,
+            include_crs: false,
+            on_type_mismatch: OnTypeMismatch::Null,
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_claim_chunk_in_parallel() -> Result<(), Box<dyn std::error::Error>> {
+        let bind_data = many_rows_bind_data(crate::VECTOR_SIZE)?;
+        let init_data = StReadMultiInitData::new(4);
+
+        let mut chunks: Vec<Chunk> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut claimed = Vec::new();
+                        while let Some(chunk) = init_data.claim_chunk(&bind_data).unwrap() {
+                            claimed.push(chunk);
+                        }
+                        claimed
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect()
+        });
+        chunks.sort_by_key(|c| (c.source_idx, c.offset));
+
+        // Every row is claimed exactly once.
+        assert_eq!(
+            chunks,
+            vec![
+                Chunk {
+                    source_idx: 0,
+                    offset: 0,
+                    len: 2048
+                },
+                Chunk {
+                    source_idx: 1,
+                    offset: 0,
+                    len: 2048
+                },
+                Chunk {
+                    source_idx: 1,
+                    offset: 2048,
+                    len: 1
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn test_arrow_schema() {
+        use duckdb::arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+
+        let specs = vec![
+            ColumnSpec {
+                name: "geom".to_string(),
+                column_type: ColumnType::Geometry,
+            },
+            ColumnSpec {
+                name: "val1".to_string(),
+                column_type: ColumnType::Integer,
+            },
+            ColumnSpec {
+                name: "updated".to_string(),
+                column_type: ColumnType::TimestampTz,
+            },
+        ];
+        let schema = Schema::new(specs.iter().map(Field::from).collect::<Vec<_>>());
+
+        let geom = schema.field_with_name("geom").unwrap();
+        assert_eq!(geom.data_type(), &DataType::Binary);
+        assert_eq!(geom.extension_type_name(), Some("geoarrow.wkb"));
+
+        let val1 = schema.field_with_name("val1").unwrap();
+        assert_eq!(val1.data_type(), &DataType::Int32);
+        assert_eq!(val1.extension_type_name(), None);
+
+        assert_eq!(
+            schema.field_with_name("updated").unwrap().data_type(),
+            &DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+        );
+    }
+
+    #[test]
+    fn test_spatial_filter() -> Result<(), Box<dyn std::error::Error>> {
+        let filter: super::SpatialFilter = "POLYGON ((0 0, 20 0, 0 25, 0 0))".parse()?;
+        assert_eq!(
+            filter.bbox,
+            super::Bbox {
+                xmin: 0.0,
+                ymin: 0.0,
+                xmax: 20.0,
+                ymax: 25.0
+            }
+        );
+
+        assert!(filter.intersects(&geo_types::Point::new(1.0, 2.0).into()));
+        // inside the bbox, but outside the polygon
+        assert!(!filter.intersects(&geo_types::Point::new(10.0, 20.0).into()));
+        // outside the bbox
+        assert!(!filter.intersects(&geo_types::Point::new(-1.0, 2.0).into()));
+
+        let mut wkb = Vec::new();
+        let point: geo_types::Geometry<f64> = geo_types::Point::new(1.0, 2.0).into();
+        wkb::writer::write_geometry(&mut wkb, &point, &Default::default())?;
+        assert!(filter.intersects_wkb(&wkb));
+        assert!(!filter.intersects_wkb(&[0x01]));
+
+        assert!("POLYGON ((0 0, 1 1"
+            .parse::<super::SpatialFilter>()
+            .is_err());
+        assert!("POLYGON EMPTY".parse::<super::SpatialFilter>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_claim_chunk_rescan() -> Result<(), Box<dyn std::error::Error>> {
+        let bind_data = many_rows_bind_data(crate::VECTOR_SIZE)?;
+
+        let claim_all = |init_data: &StReadMultiInitData| {
+            let mut chunks = Vec::new();
+            while let Some(chunk) = init_data.claim_chunk(&bind_data).unwrap() {
+                chunks.push((chunk.source_idx, chunk.offset, chunk.len));
+            }
+            chunks
+        };
+
+        // The cursor of one scan doesn't affect the other, even while it's in the
+        // middle of the data.
+        let first = StReadMultiInitData::new(1);
+        assert!(first.claim_chunk(&bind_data)?.is_some());
+        let second = StReadMultiInitData::new(1);
+        let expected = vec![(0, 0, 2048), (1, 0, 2048), (1, 2048, 1)];
+        assert_eq!(claim_all(&second), expected);
+        assert_eq!(claim_all(&first), expected[1..].to_vec());
+        assert_eq!(claim_all(&StReadMultiInitData::new(1)), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_filter() {
+        let specs: Vec<ColumnSpec> = ["a", "b", "c"]
+            .iter()
+            .map(|name| ColumnSpec {
+                name: name.to_string(),
+                column_type: ColumnType::Varchar,
+            })
+            .collect();
+
+        let filter = ColumnFilter {
+            include: Some(vec!["a".to_string(), "c".to_string()]),
+            exclude: vec!["c".to_string()],
+        };
+        assert!(filter.keeps("a"));
+        assert!(!filter.keeps("b"));
+        assert!(!filter.keeps("c"));
+
+        let mut values = vec![1, 2, 3];
+        filter.retain_values(&specs, &mut values);
+        assert_eq!(values, vec![1]);
+
+        assert!(filter.validate(&specs).is_ok());
+        assert!(filter.validate(&specs[1..]).is_err());
+        assert!(ColumnFilter::default().validate(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_is_duplicate() {
+        let init_data = StReadMultiInitData::new(1);
+        assert!(!init_data.is_duplicate(1));
+        assert!(!init_data.is_duplicate(2));
+        assert!(init_data.is_duplicate(1));
+    }
+
+    #[test]
+    fn test_claim_chunk_with_chunk_size() -> Result<(), Box<dyn std::error::Error>> {
+        let bind_data = many_rows_bind_data(512)?;
+        let init_data = StReadMultiInitData::new(1);
+
+        let mut chunks = Vec::new();
+        while let Some(chunk) = init_data.claim_chunk(&bind_data)? {
+            chunks.push((chunk.source_idx, chunk.offset, chunk.len));
+        }
+
+        // A chunk doesn't span multiple data sources.
+        assert_eq!(
+            chunks,
+            vec![
+                (0, 0, 512),
+                (0, 512, 512),
+                (0, 1024, 512),
+                (0, 1536, 512),
+                (1, 0, 512),
+                (1, 512, 512),
+                (1, 1024, 512),
+                (1, 1536, 512),
+                (1, 2048, 1),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_claim_chunk_up_to() -> Result<(), Box<dyn std::error::Error>> {
+        let bind_data = one_feature_bind_data(5000)?;
+        let init_data = StReadMultiInitData::new(1);
+
+        // Fill the outputs as func() does, with all the rows of the chunks
+        let mut outputs = Vec::new();
+        loop {
+            let n_filled =
+                init_data.fill_output(&bind_data, |chunk, n_filled| Ok(n_filled + chunk.len))?;
+            if n_filled == 0 {
+                break;
+            }
+            outputs.push(n_filled);
+        }
+
+        // The outputs are full regardless of the number of features per source
+        assert_eq!(outputs, vec![2048, 2048, 904]);
+
+        // A chunk doesn't exceed `max_len`
+        let bind_data = many_rows_bind_data(crate::VECTOR_SIZE)?;
+        let init_data = StReadMultiInitData::new(1);
+        let chunk = init_data.claim_chunk_up_to(&bind_data, 100)?.unwrap();
+        assert_eq!((chunk.source_idx, chunk.offset, chunk.len), (0, 0, 100));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_claim_chunk_with_offset_limit() -> Result<(), Box<dyn std::error::Error>> {
+        let mut bind_data = many_rows_bind_data(512)?;
+        if let StReadMultiBindData::GeoJson(bind_data) = &mut bind_data {
+            bind_data.offset = 2000;
+            bind_data.limit = Some(600);
+        }
+        let init_data = StReadMultiInitData::new(1);
+
+        let mut chunks = Vec::new();
+        while let Some(chunk) = init_data.claim_chunk(&bind_data)? {
+            chunks.push((chunk.source_idx, chunk.offset, chunk.len));
         }
+
+        // The offset and the limit are counted across the data sources.
+        assert_eq!(chunks, vec![(0, 2000, 48), (1, 0, 512), (1, 512, 40)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_offset_limit_after_filters() -> Result<(), Box<dyn std::error::Error>> {
+        let mut bind_data = many_rows_bind_data(512)?;
+        if let StReadMultiBindData::GeoJson(bind_data) = &mut bind_data {
+            bind_data.distinct = true;
+            bind_data.offset = 2;
+            bind_data.limit = Some(3);
+        }
+        let (init_data, max_threads) = StReadMultiInitData::new_for(&bind_data, 4);
+        assert_eq!(max_threads, 1);
+
+        // The chunks are claimed regardless of the offset
+        let chunk = init_data.claim_chunk(&bind_data)?.unwrap();
+        assert_eq!((chunk.source_idx, chunk.offset, chunk.len), (0, 0, 512));
+
+        // The rows that pass the filters are counted instead
+        let in_page: Vec<bool> = (0..6).map(|_| init_data.is_in_page(&bind_data)).collect();
+        assert_eq!(in_page, vec![false, false, true, true, true, false]);
+
+        // No chunk is claimed after `limit` rows are emitted
+        assert_eq!(init_data.claim_chunk(&bind_data)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gpkg_connection_pool_max_open() -> Result<(), Box<dyn std::error::Error>> {
+        let init_data = StReadMultiInitData::new(4);
+
+        let conn1 = init_data.take_gpkg_connection("./test/data/points.gpkg", 1, false)?;
+        init_data.put_back_gpkg_connection(conn1);
+        assert_eq!(init_data.gpkg_conns.lock().unwrap().n_open, 1);
+
+        // The idle connection to the other file is closed before opening a new one.
+        let conn2 = init_data.take_gpkg_connection("./test/data/points2.gpkg", 1, false)?;
+        {
+            let pool = init_data.gpkg_conns.lock().unwrap();
+            assert_eq!(pool.n_open, 1);
+            assert!(pool.idle.is_empty());
+        }
+        init_data.put_back_gpkg_connection(conn2);
+
+        // The idle connection to the same file is reused.
+        let conn3: GpkgConnection =
+            init_data.take_gpkg_connection("./test/data/points2.gpkg", 1, false)?;
+        assert_eq!(conn3.path, "./test/data/points2.gpkg");
+        assert_eq!(init_data.gpkg_conns.lock().unwrap().n_open, 1);
+
+        Ok(())
     }
 }
@@ -1,5 +1,20 @@
-use crate::types::ColumnSpec;
-use std::path::Path;
+use crate::{
+    esrijson::sniff_esrijson,
+    geojson::sniff_geojson,
+    gpkg::sniff_gpkg,
+    types::{Bbox, CollectionMode, ColumnSpec, Format, TargetGeometryType, Winding},
+};
+use std::{
+    borrow::Cow,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{sync_channel, SyncSender},
+        Arc,
+    },
+    thread::JoinHandle,
+};
 
 // glob() doesn't handle tilda, so I have to.
 pub fn expand_tilde(path: &str) -> String {
@@ -14,13 +29,260 @@ pub fn expand_tilde(path: &str) -> String {
     }
 }
 
+// Expand the glob pattern. The glob crate cannot limit the depth of the recursion
+// by `**`, so, if `max_depth` is specified, walk the directory tree by walkdir
+// instead. `max_depth` is the number of directories `**` can descend into.
+pub fn expand_glob(
+    pattern: &str,
+    max_depth: Option<usize>,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let components: Vec<&str> = pattern.split('/').collect();
+    let recursive_idx = components.iter().position(|c| c.contains("**"));
+
+    let (Some(max_depth), Some(recursive_idx)) = (max_depth, recursive_idx) else {
+        return Ok(glob::glob(pattern)?.collect::<Result<Vec<_>, _>>()?);
+    };
+
+    // Start walking from the longest directory without any wildcards.
+    let wildcard_idx = components
+        .iter()
+        .position(|c| c.contains(['*', '?', '[']))
+        .unwrap_or(recursive_idx);
+    let base = components[..wildcard_idx].join("/");
+    let root = if base.is_empty() { "." } else { base.as_str() };
+
+    // e.g. "data/**/*.geojson" needs one more level for the file name
+    let depth = (recursive_idx - wildcard_idx) + max_depth + (components.len() - recursive_idx - 1);
+
+    let matcher = glob::Pattern::new(pattern)?;
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for entry in walkdir::WalkDir::new(root)
+        .min_depth(1)
+        .max_depth(depth)
+        .sort_by_file_name()
+    {
+        let entry = entry?;
+        let path = if base.is_empty() {
+            entry.path().strip_prefix(".").unwrap_or(entry.path())
+        } else {
+            entry.path()
+        };
+        if matcher.matches_path_with(path, options) {
+            paths.push(path.to_path_buf());
+        }
+    }
+
+    Ok(paths)
+}
+
+// Read the manifest file, which lists one path or glob pattern per line. Empty lines
+// and the lines starting with `#` are ignored. Relative paths are resolved against
+// the current directory, not the directory of the manifest file.
+pub fn read_manifest<P: AsRef<Path>>(path: P) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read the manifest file {}: {e}", path.display()))?;
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(expand_tilde)
+        .collect())
+}
+
+// The filename relative to `base` (cf. `filename_relative_to`), separated by '/'
+// regardless of the platform so that the result is the same across machines. The
+// filename is returned as is if it's not under `base`.
+pub fn relative_filename<'a>(filename: &'a str, base: Option<&Path>) -> Cow<'a, str> {
+    let Some(base) = base else {
+        return Cow::Borrowed(filename);
+    };
+    let path = Path::new(filename);
+    let relative = match path.strip_prefix(base) {
+        Ok(relative) => Some(relative.to_path_buf()),
+        // One of them might be relative to the current directory
+        Err(_) => match (std::path::absolute(path), std::path::absolute(base)) {
+            (Ok(path), Ok(base)) => path.strip_prefix(base).ok().map(Path::to_path_buf),
+            _ => None,
+        },
+    };
+    match relative {
+        Some(relative) => Cow::Owned(
+            relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+        ),
+        None => Cow::Borrowed(filename),
+    }
+}
+
+// The root of the share (e.g. `\\server\share`) if the path is a UNC/network path
+// like `\\server\share\data\*.gpkg`. `//server/share/data/*.gpkg` is also a UNC
+// path on Windows, but it's an ordinary absolute path on the other platforms.
+pub fn network_share_root(path: &str) -> Option<String> {
+    let rest = path
+        .strip_prefix(r"\\")
+        .or_else(|| path.strip_prefix("//").filter(|_| cfg!(windows)))?;
+    let mut components = rest.split(['/', '\\']).filter(|c| !c.is_empty());
+    let server = components.next()?;
+    let share = components.next()?;
+    let sep = &path[..1];
+    Some(format!("{sep}{sep}{server}{sep}{share}"))
+}
+
+// The modification time of the file in microseconds since the Unix epoch. None if
+// it's not available on the filesystem.
+pub fn file_mtime<P: AsRef<Path>>(path: P) -> Option<i64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    match modified.duration_since(std::time::UNIX_EPOCH) {
+        Ok(d) => Some(d.as_micros() as i64),
+        Err(e) => Some(-(e.duration().as_micros() as i64)),
+    }
+}
+
+// Keep only the files modified after the cutoff (in microseconds since the Unix
+// epoch). The files whose modification time is not available (e.g. the objects on
+// S3) are kept because they cannot be judged.
+pub fn filter_modified_after(paths: Vec<PathBuf>, cutoff: i64) -> Vec<PathBuf> {
+    paths
+        .into_iter()
+        .filter(|p| file_mtime(p).is_none_or(|mtime| mtime > cutoff))
+        .collect()
+}
+
+// Read the files in a background thread and discard the content so that they are in
+// the OS page cache when they are actually read. There's only one thread, and it's
+// stopped and joined when this is dropped (i.e. when the scan is over). Errors are
+// ignored here; they are reported when the files are read later.
+pub struct Prefetcher {
+    // None only while dropping
+    sender: Option<SyncSender<Vec<PathBuf>>>,
+    stop: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Prefetcher {
+    pub fn new() -> Self {
+        // At most one request waits while a file is being read. If the thread is
+        // still behind, the later requests are dropped; the scan will catch up
+        // with them anyway.
+        let (sender, receiver) = sync_channel::<Vec<PathBuf>>(1);
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = {
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                for path in receiver.iter().flatten() {
+                    if stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let _ = warm_up_file(&path, &stop);
+                }
+            })
+        };
+
+        Self {
+            sender: Some(sender),
+            stop,
+            worker: Some(worker),
+        }
+    }
+
+    pub fn request(&self, paths: Vec<PathBuf>) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.try_send(paths);
+        }
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Closing the channel ends the loop of the thread
+        self.sender.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+// The file is read by blocks so that the thread can stop in the middle of a large
+// file.
+fn warm_up_file(path: &Path, stop: &AtomicBool) -> std::io::Result<()> {
+    let mut f = std::fs::File::open(path)?;
+    let mut buf = vec![0u8; 1 << 16];
+    while !stop.load(Ordering::Relaxed) {
+        if f.read(&mut buf)? == 0 {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// Parse the string representation of a DuckDB LIST of VARCHAR (e.g. `[a, 'b, c']`).
+// DuckDB quotes an element with single quotes if it contains special characters.
+pub fn parse_list_value(s: &str) -> Vec<String> {
+    let inner = s.trim();
+    let inner = inner.strip_prefix('[').unwrap_or(inner);
+    let inner = inner.strip_suffix(']').unwrap_or(inner);
+
+    let mut elements: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = inner.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' if quoted && chars.peek() == Some(&'\'') => {
+                // escaped single quote
+                current.push('\'');
+                chars.next();
+            }
+            '\'' => quoted = !quoted,
+            ',' if !quoted => {
+                elements.push(current.trim().to_string());
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+
+    if !current.trim().is_empty() || !elements.is_empty() {
+        elements.push(current.trim().to_string());
+    }
+
+    elements
+}
+
 pub fn is_geojson<P: AsRef<Path>>(path: P) -> bool {
-    match path.as_ref().extension() {
+    let path = path.as_ref();
+    // zstd-compressed GeoJSON (`.geojson.zst` or `.json.zst`)
+    if is_zstd(path) {
+        return path
+            .file_stem()
+            .and_then(|stem| Path::new(stem).extension())
+            .is_some_and(|ext| ext == "geojson" || ext == "json");
+    }
+    match path.extension() {
         Some(ext) => ext.to_string_lossy() == "geojson",
         None => false,
     }
 }
 
+pub fn is_zstd<P: AsRef<Path>>(path: P) -> bool {
+    match path.as_ref().extension() {
+        Some(ext) => ext.to_string_lossy() == "zst",
+        None => false,
+    }
+}
+
 pub fn is_gpkg<P: AsRef<Path>>(path: P) -> bool {
     match path.as_ref().extension() {
         Some(ext) => ext.to_string_lossy() == "gpkg",
@@ -35,46 +297,1244 @@ pub fn is_shp<P: AsRef<Path>>(path: P) -> bool {
     }
 }
 
-pub fn validate_schema(
+// A zip archive holding a single shapefile (e.g. `points.shp.zip`)
+pub fn is_shp_zip<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    path.extension().is_some_and(|ext| ext == "zip")
+        && path
+            .file_stem()
+            .and_then(|stem| Path::new(stem).extension())
+            .is_some_and(|ext| ext == "shp")
+}
+
+// Detect the format from the extensions. If the extension is unknown (e.g. `.json`
+// or no extension), the content is sniffed instead, but only GeoJSON, EsriJSON, and
+// GeoPackage can be detected this way. All the files must be the same format.
+pub fn detect_format(paths: &[PathBuf]) -> Result<Format, Box<dyn std::error::Error>> {
+    let mut formats = paths
+        .iter()
+        .map(|p| format_from_extension(p).or_else(|| sniff_format(p)));
+
+    match formats.next().flatten() {
+        Some(format) if formats.all(|f| f == Some(format)) => Ok(format),
+        _ => Err(
+            "All files must have extension '.geojson', '.gpkg', '.shp', '.dbf', '.csv', or '.mif', or be GeoJSON, EsriJSON, or GeoPackage files"
+                .into(),
+        ),
+    }
+}
+
+fn format_from_extension(path: &Path) -> Option<Format> {
+    if is_geojson(path) {
+        Some(Format::GeoJson)
+    } else if is_gpkg(path) {
+        Some(Format::Gpkg)
+    } else if is_shp(path) || is_shp_zip(path) {
+        Some(Format::Shapefile)
+    } else if is_dbf(path) {
+        Some(Format::Dbf)
+    } else if is_csv(path) {
+        Some(Format::Csv)
+    } else if is_mif(path) {
+        Some(Format::Mif)
+    } else {
+        None
+    }
+}
+
+fn sniff_format(path: &Path) -> Option<Format> {
+    if sniff_gpkg(path) {
+        Some(Format::Gpkg)
+    } else if sniff_geojson(path) {
+        Some(Format::GeoJson)
+    } else if sniff_esrijson(path) {
+        Some(Format::EsriJson)
+    } else {
+        None
+    }
+}
+
+pub fn is_dbf<P: AsRef<Path>>(path: P) -> bool {
+    match path.as_ref().extension() {
+        Some(ext) => ext.to_string_lossy() == "dbf",
+        None => false,
+    }
+}
+
+pub fn is_csv<P: AsRef<Path>>(path: P) -> bool {
+    match path.as_ref().extension() {
+        Some(ext) => ext.to_string_lossy() == "csv",
+        None => false,
+    }
+}
+
+pub fn is_mif<P: AsRef<Path>>(path: P) -> bool {
+    match path.as_ref().extension() {
+        Some(ext) => ext.to_string_lossy().eq_ignore_ascii_case("mif"),
+        None => false,
+    }
+}
+
+// Round the coordinates to the specified number of decimal places.
+pub fn round_coordinates(geometry: &mut geo_types::Geometry<f64>, precision: i32) {
+    let factor = 10f64.powi(precision);
+    let round = |c: &mut geo_types::Coord<f64>| {
+        c.x = (c.x * factor).round() / factor;
+        c.y = (c.y * factor).round() / factor;
+    };
+    let round_polygon = |polygon: &mut geo_types::Polygon<f64>| {
+        polygon.exterior_mut(|exterior| exterior.0.iter_mut().for_each(round));
+        polygon.interiors_mut(|interiors| {
+            for interior in interiors {
+                interior.0.iter_mut().for_each(round);
+            }
+        });
+    };
+
+    match geometry {
+        geo_types::Geometry::Point(point) => round(&mut point.0),
+        geo_types::Geometry::Line(line) => {
+            round(&mut line.start);
+            round(&mut line.end);
+        }
+        geo_types::Geometry::LineString(line_string) => line_string.0.iter_mut().for_each(round),
+        geo_types::Geometry::Polygon(polygon) => round_polygon(polygon),
+        geo_types::Geometry::MultiPoint(multi_point) => {
+            multi_point.0.iter_mut().for_each(|p| round(&mut p.0))
+        }
+        geo_types::Geometry::MultiLineString(multi_line_string) => {
+            for line_string in multi_line_string.0.iter_mut() {
+                line_string.0.iter_mut().for_each(round);
+            }
+        }
+        geo_types::Geometry::MultiPolygon(multi_polygon) => {
+            multi_polygon.0.iter_mut().for_each(round_polygon)
+        }
+        geo_types::Geometry::GeometryCollection(collection) => {
+            for g in collection.0.iter_mut() {
+                round_coordinates(g, precision);
+            }
+        }
+        geo_types::Geometry::Rect(rect) => {
+            let (mut min, mut max) = (rect.min(), rect.max());
+            round(&mut min);
+            round(&mut max);
+            *rect = geo_types::Rect::new(min, max);
+        }
+        geo_types::Geometry::Triangle(triangle) => {
+            let (mut v1, mut v2, mut v3) = (triangle.v1(), triangle.v2(), triangle.v3());
+            round(&mut v1);
+            round(&mut v2);
+            round(&mut v3);
+            *triangle = geo_types::Triangle::new(v1, v2, v3);
+        }
+    }
+}
+
+// Handle a GeometryCollection by `mode`. Returns None if the geometry becomes NULL.
+// The other geometries are returned as they are.
+pub fn apply_collection_mode(
+    geometry: geo_types::Geometry<f64>,
+    mode: CollectionMode,
+) -> Option<geo_types::Geometry<f64>> {
+    match (geometry, mode) {
+        (geo_types::Geometry::GeometryCollection(collection), CollectionMode::First) => {
+            collection.0.into_iter().next()
+        }
+        (geo_types::Geometry::GeometryCollection(_), CollectionMode::Null) => None,
+        (geometry, _) => Some(geometry),
+    }
+}
+
+// The same as apply_collection_mode(), but for WKB. Unlike rewrite_wkb(), Z and M
+// are kept.
+pub fn apply_collection_mode_wkb(
+    wkb: &[u8],
+    mode: CollectionMode,
+) -> Result<Option<Cow<'_, [u8]>>, Box<dyn std::error::Error + Send + Sync>> {
+    use geo_traits::{GeometryCollectionTrait, GeometryTrait, GeometryType};
+
+    if mode == CollectionMode::Keep {
+        return Ok(Some(Cow::Borrowed(wkb)));
+    }
+
+    let geometry = wkb::reader::read_wkb(wkb).map_err(|e| e.to_string())?;
+    let GeometryType::GeometryCollection(collection) = geometry.as_type() else {
+        return Ok(Some(Cow::Borrowed(wkb)));
+    };
+    match (mode, collection.geometries().next()) {
+        (CollectionMode::First, Some(first)) => {
+            let mut buffer = Vec::new();
+            wkb::writer::write_geometry(&mut buffer, &first, &Default::default())
+                .map_err(|e| e.to_string())?;
+            Ok(Some(Cow::Owned(buffer)))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Swap the x and y of all the coordinates (cf. `coordinate_order`)
+pub fn swap_xy(geometry: &mut geo_types::Geometry<f64>) {
+    use geo::MapCoordsInPlace;
+
+    geometry.map_coords_in_place(|c| geo_types::coord! { x: c.y, y: c.x });
+}
+
+// Replace the geometry with the area within `distance` (in the unit of the
+// coordinates) from it. A point becomes a polygon approximating a circle. A negative
+// distance shrinks the polygons, and the other geometries become empty. The result
+// is a Polygon if it's a single polygon, or a MultiPolygon otherwise.
+pub fn buffer(geometry: &mut geo_types::Geometry<f64>, distance: f64) {
+    use geo::Buffer;
+
+    let mut buffered = geometry.buffer(distance);
+    *geometry = if buffered.0.len() == 1 {
+        buffered.0.pop().unwrap().into()
+    } else {
+        buffered.into()
+    };
+}
+
+// Insert vertices so that no segment is longer than `max_segment_length` (in the
+// unit of the coordinates). This is done before rounding so that the new vertices
+// are rounded too.
+pub fn densify(geometry: &mut geo_types::Geometry<f64>, max_segment_length: f64) {
+    use geo::{Densify, Euclidean};
+
+    *geometry = geometry.densify(&Euclidean, max_segment_length);
+}
+
+// Reorient the rings of the polygons. The other geometries are left as they are.
+pub fn orient_polygons(geometry: &mut geo_types::Geometry<f64>, winding: Winding) {
+    use geo::Winding as _;
+
+    let orient_polygon = |polygon: &mut geo_types::Polygon<f64>| {
+        polygon.exterior_mut(|exterior| match winding {
+            Winding::Cw => exterior.make_cw_winding(),
+            Winding::Ccw => exterior.make_ccw_winding(),
+        });
+        polygon.interiors_mut(|interiors| {
+            for interior in interiors {
+                match winding {
+                    Winding::Cw => interior.make_ccw_winding(),
+                    Winding::Ccw => interior.make_cw_winding(),
+                }
+            }
+        });
+    };
+
+    match geometry {
+        geo_types::Geometry::Polygon(polygon) => orient_polygon(polygon),
+        geo_types::Geometry::MultiPolygon(multi_polygon) => {
+            multi_polygon.0.iter_mut().for_each(orient_polygon)
+        }
+        geo_types::Geometry::GeometryCollection(collection) => {
+            for g in collection.0.iter_mut() {
+                orient_polygons(g, winding);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Re-encode the WKB as the centroid of the geometry, rounding the coordinates if
+// `precision` is specified. Returns None if the geometry is empty and thus has no
+// centroid.
+pub fn centroid_wkb(
+    wkb: &[u8],
+    precision: Option<i32>,
+) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>> {
+    use geo::Centroid;
+    use geo_traits::to_geo::ToGeoGeometry;
+
+    let geometry = wkb::reader::read_wkb(wkb).map_err(|e| e.to_string())?;
+    // geo_types cannot represent an empty point
+    let Some(centroid) = geometry.try_to_geometry().and_then(|g| g.centroid()) else {
+        return Ok(None);
+    };
+    let mut centroid: geo_types::Geometry<f64> = centroid.into();
+    if let Some(precision) = precision {
+        round_coordinates(&mut centroid, precision);
+    }
+
+    let mut buffer = Vec::new();
+    wkb::writer::write_geometry(&mut buffer, &centroid, &Default::default())
+        .map_err(|e| e.to_string())?;
+    Ok(Some(buffer))
+}
+
+// Returns the bounding box of the geometry, or None if it's empty.
+pub fn geometry_bounds(geometry: &geo_types::Geometry<f64>) -> Option<Bbox> {
+    let mut bounds: Option<Bbox> = None;
+    for_each_coord(geometry, &mut |c| {
+        bounds = Some(match bounds {
+            Some(b) => Bbox {
+                xmin: b.xmin.min(c.x),
+                ymin: b.ymin.min(c.y),
+                xmax: b.xmax.max(c.x),
+                ymax: b.ymax.max(c.y),
+            },
+            None => Bbox {
+                xmin: c.x,
+                ymin: c.y,
+                xmax: c.x,
+                ymax: c.y,
+            },
+        });
+    });
+    bounds
+}
+
+fn for_each_coord<F>(geometry: &geo_types::Geometry<f64>, f: &mut F)
+where
+    F: FnMut(geo_types::Coord<f64>),
+{
+    let mut polygon = |polygon: &geo_types::Polygon<f64>| {
+        for ring in std::iter::once(polygon.exterior()).chain(polygon.interiors()) {
+            ring.0.iter().for_each(|c| f(*c));
+        }
+    };
+
+    match geometry {
+        geo_types::Geometry::Point(point) => f(point.0),
+        geo_types::Geometry::Line(line) => {
+            f(line.start);
+            f(line.end);
+        }
+        geo_types::Geometry::LineString(line_string) => line_string.0.iter().for_each(|c| f(*c)),
+        geo_types::Geometry::Polygon(p) => polygon(p),
+        geo_types::Geometry::MultiPoint(multi_point) => multi_point.0.iter().for_each(|p| f(p.0)),
+        geo_types::Geometry::MultiLineString(multi_line_string) => {
+            for line_string in &multi_line_string.0 {
+                line_string.0.iter().for_each(|c| f(*c));
+            }
+        }
+        geo_types::Geometry::MultiPolygon(multi_polygon) => {
+            multi_polygon.0.iter().for_each(polygon)
+        }
+        geo_types::Geometry::GeometryCollection(collection) => {
+            for g in &collection.0 {
+                for_each_coord(g, f);
+            }
+        }
+        geo_types::Geometry::Rect(rect) => {
+            f(rect.min());
+            f(rect.max());
+        }
+        geo_types::Geometry::Triangle(triangle) => {
+            f(triangle.v1());
+            f(triangle.v2());
+            f(triangle.v3());
+        }
+    }
+}
+
+// Re-encode the WKB via geo_types::Geometry, buffering the geometry if
+// `buffer_distance` is specified, densifying the segments if
+// `max_segment_length` is specified, rounding the coordinates if `precision` is
+// specified, and reorienting the polygon rings if `winding` is specified. Z and M
+// are always dropped because geo_types::Geometry is 2D, so this is also used to
+// force 2D.
+pub fn rewrite_wkb(
+    wkb: &[u8],
+    buffer_distance: Option<f64>,
+    max_segment_length: Option<f64>,
+    precision: Option<i32>,
+    winding: Option<Winding>,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    use geo_traits::to_geo::ToGeoGeometry;
+
+    let geometry = wkb::reader::read_wkb(wkb).map_err(|e| e.to_string())?;
+    // geo_types cannot represent an empty point, but there's nothing to round. It's
+    // written as a 2D empty point (NaN coordinates) instead.
+    let Some(mut geometry) = geometry.try_to_geometry() else {
+        let mut buffer = vec![0x01, 0x01, 0x00, 0x00, 0x00];
+        buffer.extend_from_slice(&f64::NAN.to_le_bytes());
+        buffer.extend_from_slice(&f64::NAN.to_le_bytes());
+        return Ok(buffer);
+    };
+    if let Some(distance) = buffer_distance {
+        buffer(&mut geometry, distance);
+    }
+    if let Some(max_segment_length) = max_segment_length {
+        densify(&mut geometry, max_segment_length);
+    }
+    if let Some(precision) = precision {
+        round_coordinates(&mut geometry, precision);
+    }
+    if let Some(winding) = winding {
+        orient_polygons(&mut geometry, winding);
+    }
+
+    let mut buffer = Vec::new();
+    wkb::writer::write_geometry(&mut buffer, &geometry, &Default::default())
+        .map_err(|e| e.to_string())?;
+    Ok(buffer)
+}
+
+// Check if any coordinate is NaN or infinite (cf. `strict`). An empty point, which
+// is written with NaN coordinates in WKB, doesn't count.
+pub fn has_non_finite_coords(wkb: &[u8]) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    use geo::CoordsIter;
+    use geo_traits::to_geo::ToGeoGeometry;
+
+    let geometry = wkb::reader::read_wkb(wkb).map_err(|e| e.to_string())?;
+    let Some(geometry) = geometry.try_to_geometry() else {
+        return Ok(false);
+    };
+    Ok(geometry
+        .coords_iter()
+        .any(|c| !c.x.is_finite() || !c.y.is_finite()))
+}
+
+// Coerce the geometry to `target` (`geometry_type` option). A single geometry is
+// wrapped into the multi geometry of the same kind, and a multi geometry or a
+// GeometryCollection of exactly one member is unwrapped. Returns None if it cannot
+// be coerced (e.g. a point to a polygon).
+pub fn coerce_geometry_type(
+    geometry: geo_types::Geometry<f64>,
+    target: TargetGeometryType,
+) -> Option<geo_types::Geometry<f64>> {
+    use geo_types::Geometry as G;
+    use TargetGeometryType as T;
+
+    match (geometry, target) {
+        (G::Point(g), T::Point) => Some(g.into()),
+        (G::Point(g), T::MultiPoint) => Some(geo_types::MultiPoint::new(vec![g]).into()),
+        (G::MultiPoint(g), T::MultiPoint) => Some(g.into()),
+        (G::MultiPoint(mut g), T::Point) if g.0.len() == 1 => Some(g.0.remove(0).into()),
+
+        (G::LineString(g), T::LineString) => Some(g.into()),
+        (G::LineString(g), T::MultiLineString) => {
+            Some(geo_types::MultiLineString::new(vec![g]).into())
+        }
+        (G::MultiLineString(g), T::MultiLineString) => Some(g.into()),
+        (G::MultiLineString(mut g), T::LineString) if g.0.len() == 1 => Some(g.0.remove(0).into()),
+        (G::Line(g), T::LineString | T::MultiLineString) => {
+            coerce_geometry_type(geo_types::LineString::from(g).into(), target)
+        }
+
+        (G::Polygon(g), T::Polygon) => Some(g.into()),
+        (G::Polygon(g), T::MultiPolygon) => Some(geo_types::MultiPolygon::new(vec![g]).into()),
+        (G::MultiPolygon(g), T::MultiPolygon) => Some(g.into()),
+        (G::MultiPolygon(mut g), T::Polygon) if g.0.len() == 1 => Some(g.0.remove(0).into()),
+        (G::Rect(g), T::Polygon | T::MultiPolygon) => {
+            coerce_geometry_type(g.to_polygon().into(), target)
+        }
+        (G::Triangle(g), T::Polygon | T::MultiPolygon) => {
+            coerce_geometry_type(g.to_polygon().into(), target)
+        }
+
+        (G::GeometryCollection(mut g), _) if g.0.len() == 1 => {
+            coerce_geometry_type(g.0.remove(0), target)
+        }
+        _ => None,
+    }
+}
+
+// The same as coerce_geometry_type(), but for WKB. The geometry already of the
+// target type is returned as it is; otherwise, Z and M are dropped.
+pub fn coerce_geometry_type_wkb(
+    wkb: &[u8],
+    target: TargetGeometryType,
+) -> Result<Option<Cow<'_, [u8]>>, Box<dyn std::error::Error + Send + Sync>> {
+    use geo_traits::{to_geo::ToGeoGeometry, GeometryTrait, GeometryType};
+    use TargetGeometryType as T;
+
+    let geometry = wkb::reader::read_wkb(wkb).map_err(|e| e.to_string())?;
+    if matches!(
+        (geometry.as_type(), target),
+        (GeometryType::Point(_), T::Point)
+            | (GeometryType::LineString(_), T::LineString)
+            | (GeometryType::Polygon(_), T::Polygon)
+            | (GeometryType::MultiPoint(_), T::MultiPoint)
+            | (GeometryType::MultiLineString(_), T::MultiLineString)
+            | (GeometryType::MultiPolygon(_), T::MultiPolygon)
+    ) {
+        return Ok(Some(Cow::Borrowed(wkb)));
+    }
+
+    // An empty point cannot be converted to geo_types, but it's a point anyway.
+    let Some(geometry) = geometry
+        .try_to_geometry()
+        .and_then(|g| coerce_geometry_type(g, target))
+    else {
+        return Ok(None);
+    };
+    let mut buffer = Vec::new();
+    wkb::writer::write_geometry(&mut buffer, &geometry, &Default::default())
+        .map_err(|e| e.to_string())?;
+    Ok(Some(Cow::Owned(buffer)))
+}
+
+// A copy of the specs sorted by name, for the formats whose columns are not
+// sorted (e.g. GeoPackage).
+pub fn sorted_by_name(specs: &[ColumnSpec]) -> Vec<ColumnSpec> {
+    let mut specs = specs.to_vec();
+    specs.sort_by(|a, b| a.name.cmp(&b.name));
+    specs
+}
+
+// Compare the schema of a file with the existing one. The columns are compared one
+// by one only when the numbers of the columns match.
+fn schema_mismatches(
     existing_specs: &[ColumnSpec],
     new_specs: &[ColumnSpec],
     file_path: &Path,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Vec<String> {
+    let file_path = file_path.to_string_lossy().replace('\\', "/");
+
     // Check if the number of columns matches
     if existing_specs.len() != new_specs.len() {
-        return Err(format!(
-            "Schema mismatch in {}: expected {} columns, found {}",
-            file_path.to_string_lossy().replace('\\', "/"),
+        return vec![format!(
+            "Schema mismatch in {file_path}: expected {} columns, found {}",
             existing_specs.len(),
             new_specs.len()
-        )
-        .into());
+        )];
     }
 
-    // Since both are sorted by name, we can compare directly
+    // The callers pass both sorted by name, so we can compare directly
+    let mut mismatches = Vec::new();
     for (i, (existing, local)) in existing_specs.iter().zip(new_specs.iter()).enumerate() {
         if existing.name != local.name {
-            return Err(format!(
-                "Schema mismatch in {}: column {} has name '{}', expected '{}'",
-                file_path.to_string_lossy().replace('\\', "/"),
-                i,
-                local.name,
-                existing.name
-            )
-            .into());
+            mismatches.push(format!(
+                "Schema mismatch in {file_path}: column {i} has name '{}', expected '{}'",
+                local.name, existing.name
+            ));
+        } else if existing.column_type != local.column_type {
+            mismatches.push(format!(
+                "Schema mismatch in {file_path}: column '{}' has type {:?}, expected {:?}",
+                local.name, local.column_type, existing.column_type
+            ));
         }
+    }
 
-        if existing.column_type != local.column_type {
-            return Err(format!(
-                "Schema mismatch in {}: column '{}' has type {:?}, expected {:?}",
-                file_path.to_string_lossy().replace('\\', "/"),
-                local.name,
-                local.column_type,
-                existing.column_type
-            )
-            .into());
+    mismatches
+}
+
+// Validate the schema of each file against the first one. By default, the first
+// mismatch is returned as an error. If `report_all` is true, the mismatches are
+// collected through all the files and reported at once by finish().
+pub struct SchemaValidator {
+    report_all: bool,
+    mismatches: Vec<String>,
+}
+
+impl SchemaValidator {
+    pub fn new(report_all: bool) -> Self {
+        Self {
+            report_all,
+            mismatches: Vec::new(),
+        }
+    }
+
+    pub fn validate(
+        &mut self,
+        existing_specs: &[ColumnSpec],
+        new_specs: &[ColumnSpec],
+        file_path: &Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut mismatches = schema_mismatches(existing_specs, new_specs, file_path);
+        log::debug!(
+            "Validated the schema of {}: {} mismatches",
+            file_path.to_string_lossy().replace('\\', "/"),
+            mismatches.len()
+        );
+        if self.report_all {
+            self.mismatches.append(&mut mismatches);
+            return Ok(());
+        }
+
+        match mismatches.into_iter().next() {
+            Some(mismatch) => Err(mismatch.into()),
+            None => Ok(()),
+        }
+    }
+
+    pub fn finish(self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.mismatches.is_empty() {
+            return Ok(());
+        }
+
+        Err(format!(
+            "Found {} schema mismatches:\n{}",
+            self.mismatches.len(),
+            self.mismatches.join("\n")
+        )
+        .into())
+    }
+}
+
+// Add the columns that don't exist in `existing_specs` yet. The columns of the same
+// name must have the same type.
+pub fn merge_schema(
+    existing_specs: &mut Vec<ColumnSpec>,
+    new_specs: &[ColumnSpec],
+    file_path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for local in new_specs {
+        match existing_specs.iter().find(|s| s.name == local.name) {
+            Some(existing) if existing.column_type != local.column_type => {
+                return Err(format!(
+                    "Schema mismatch in {}: column '{}' has type {:?}, expected {:?}",
+                    file_path.to_string_lossy().replace('\\', "/"),
+                    local.name,
+                    local.column_type,
+                    existing.column_type
+                )
+                .into());
+            }
+            Some(_) => {}
+            None => existing_specs.push(local.clone()),
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{ColumnSpec, ColumnType};
+
+    #[test]
+    fn test_coerce_geometry_type() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        use crate::types::TargetGeometryType;
+        use geo_types::{
+            Geometry, GeometryCollection, LineString, MultiPolygon, Point, Polygon, Rect,
+        };
+
+        let polygon = Polygon::new(
+            LineString::from(vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 0.0)]),
+            vec![],
+        );
+        let multi = MultiPolygon::new(vec![polygon.clone()]);
+        let coerce = super::coerce_geometry_type;
+
+        // coercible
+        assert_eq!(
+            coerce(polygon.clone().into(), TargetGeometryType::MultiPolygon),
+            Some(multi.clone().into())
+        );
+        assert_eq!(
+            coerce(multi.clone().into(), TargetGeometryType::Polygon),
+            Some(polygon.clone().into())
+        );
+        assert_eq!(
+            coerce(
+                GeometryCollection::new_from(vec![polygon.clone().into()]).into(),
+                TargetGeometryType::Polygon
+            ),
+            Some(polygon.clone().into())
+        );
+        assert!(matches!(
+            coerce(
+                Rect::new((0.0, 0.0), (1.0, 1.0)).into(),
+                TargetGeometryType::Polygon
+            ),
+            Some(Geometry::Polygon(_))
+        ));
+
+        // incoercible
+        assert_eq!(
+            coerce(Point::new(0.0, 0.0).into(), TargetGeometryType::Polygon),
+            None
+        );
+        assert_eq!(
+            coerce(
+                LineString::from(vec![(0.0, 0.0), (1.0, 1.0)]).into(),
+                TargetGeometryType::Polygon
+            ),
+            None
+        );
+        let two_polygons = MultiPolygon::new(vec![polygon.clone(), polygon.clone()]);
+        assert_eq!(
+            coerce(two_polygons.into(), TargetGeometryType::Polygon),
+            None
+        );
+
+        // WKB of the target type is kept as it is
+        let mut wkb = Vec::new();
+        let geometry: Geometry<f64> = polygon.into();
+        wkb::writer::write_geometry(&mut wkb, &geometry, &Default::default())?;
+        assert!(matches!(
+            super::coerce_geometry_type_wkb(&wkb, TargetGeometryType::Polygon)?,
+            Some(std::borrow::Cow::Borrowed(_))
+        ));
+        assert!(super::coerce_geometry_type_wkb(&wkb, TargetGeometryType::MultiPolygon)?.is_some());
+        assert!(super::coerce_geometry_type_wkb(&wkb, TargetGeometryType::Point)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_network_share_root() {
+        assert_eq!(
+            super::network_share_root(r"\\server\share\data\*.gpkg").as_deref(),
+            Some(r"\\server\share")
+        );
+        // `//` is a UNC path only on Windows
+        assert_eq!(
+            super::network_share_root("//server/share/data/*.gpkg").as_deref(),
+            cfg!(windows).then_some("//server/share")
+        );
+        assert_eq!(super::network_share_root("//server"), None);
+        assert_eq!(super::network_share_root("/data/*.gpkg"), None);
+        assert_eq!(super::network_share_root("data/*.gpkg"), None);
+    }
+
+    #[test]
+    fn test_relative_filename() {
+        use std::path::Path;
+
+        let base = Some(Path::new("test/data"));
+        assert_eq!(
+            super::relative_filename("test/data/points.geojson", None),
+            "test/data/points.geojson"
+        );
+        assert_eq!(
+            super::relative_filename("test/data/points.geojson", base),
+            "points.geojson"
+        );
+        assert_eq!(
+            super::relative_filename("test/data/a/b.gpkg", base),
+            "a/b.gpkg"
+        );
+        assert_eq!(
+            super::relative_filename("./test/data/points.geojson", base),
+            "points.geojson"
+        );
+
+        // an absolute path is compared with the absolute base
+        let absolute = std::env::current_dir()
+            .unwrap()
+            .join("test/data/points.geojson");
+        assert_eq!(
+            super::relative_filename(&absolute.to_string_lossy(), base),
+            "points.geojson"
+        );
+
+        // not under the base
+        assert_eq!(
+            super::relative_filename("other/points.geojson", base),
+            "other/points.geojson"
+        );
+    }
+
+    #[test]
+    fn test_file_mtime() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("st_read_multi_test_file_mtime.txt");
+        let file = std::fs::File::create(&path)?;
+        let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        file.set_modified(mtime)?;
+        drop(file);
+
+        assert_eq!(super::file_mtime(&path), Some(1_700_000_000_000_000));
+        assert_eq!(super::file_mtime("./test/data/no_such_file"), None);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefetcher_stops_on_drop() -> Result<(), Box<dyn std::error::Error>> {
+        // A large sparse file, which takes a while to read through
+        let path = std::env::temp_dir().join("st_read_multi_test_prefetcher.bin");
+        std::fs::File::create(&path)?.set_len(4 << 30)?;
+
+        let prefetcher = super::Prefetcher::new();
+        prefetcher.request(vec![path.clone(), "./test/data/no_such_file".into()]);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        // The thread stops in the middle of the file, and is joined
+        let start = std::time::Instant::now();
+        drop(prefetcher);
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_modified_after() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir();
+        let mut paths = Vec::new();
+        for (name, secs) in [("old", 1_600_000_000), ("new", 1_700_000_000)] {
+            let path = dir.join(format!("st_read_multi_test_modified_after_{name}.txt"));
+            let file = std::fs::File::create(&path)?;
+            file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs))?;
+            paths.push(path);
+        }
+        let no_mtime = std::path::PathBuf::from("./test/data/no_such_file");
+
+        let mut candidates = paths.clone();
+        candidates.push(no_mtime.clone());
+        assert_eq!(
+            super::filter_modified_after(candidates, 1_650_000_000_000_000),
+            vec![paths[1].clone(), no_mtime]
+        );
+        // the cutoff itself is not "after"
+        assert_eq!(
+            super::filter_modified_after(paths.clone(), 1_700_000_000_000_000),
+            Vec::<std::path::PathBuf>::new()
+        );
+
+        for path in paths {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_format() {
+        use crate::types::Format;
+
+        let detect = |paths: &[&str]| {
+            let paths: Vec<std::path::PathBuf> = paths.iter().map(|p| p.into()).collect();
+            super::detect_format(&paths).ok()
+        };
+
+        assert_eq!(
+            detect(&["./test/data/points.geojson"]),
+            Some(Format::GeoJson)
+        );
+        // sniffed
+        assert_eq!(
+            detect(&["./test/data/nonstandard_ext/points_geojson.txt"]),
+            Some(Format::GeoJson)
+        );
+        assert_eq!(
+            detect(&["./test/data/nonstandard_ext/points"]),
+            Some(Format::Gpkg)
+        );
+        assert_eq!(
+            detect(&[
+                "./test/data/points.gpkg",
+                "./test/data/nonstandard_ext/points.sqlite"
+            ]),
+            Some(Format::Gpkg)
+        );
+        assert_eq!(detect(&["./test/data/nonstandard_ext/not_geo.json"]), None);
+        // zstd-compressed GeoJSON can be mixed with plain ones
+        assert_eq!(
+            detect(&[
+                "./test/data/zstd/points.geojson",
+                "./test/data/zstd/points2.geojson.zst",
+                "./test/data/zstd/points3.json.zst"
+            ]),
+            Some(Format::GeoJson)
+        );
+        // different formats
+        assert_eq!(
+            detect(&["./test/data/points.geojson", "./test/data/points.gpkg"]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_expand_glob_max_depth() -> Result<(), Box<dyn std::error::Error>> {
+        let expand = |max_depth| -> Result<Vec<String>, Box<dyn std::error::Error>> {
+            Ok(
+                super::expand_glob("test/data/nested/**/*.geojson", max_depth)?
+                    .iter()
+                    .map(|p| p.to_string_lossy().replace('\\', "/"))
+                    .collect(),
+            )
+        };
+
+        assert_eq!(expand(Some(0))?, vec!["test/data/nested/a.geojson"]);
+        assert_eq!(
+            expand(Some(1))?,
+            vec![
+                "test/data/nested/a.geojson",
+                "test/data/nested/sub/b.geojson"
+            ]
+        );
+        assert_eq!(expand(None)?.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_manifest() -> Result<(), Box<dyn std::error::Error>> {
+        let patterns = super::read_manifest("test/data/manifest/files.txt")?;
+        assert_eq!(
+            patterns,
+            vec![
+                "test/data/points*.geojson",
+                "test/data/nested/sub/**/*.geojson"
+            ]
+        );
+
+        assert!(super::read_manifest("test/data/manifest/no_such_file.txt").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema_validator() {
+        let spec = |name: &str, column_type| ColumnSpec {
+            name: name.to_string(),
+            column_type,
+        };
+        let existing = vec![
+            spec("val1", ColumnType::Double),
+            spec("val2", ColumnType::Varchar),
+        ];
+        let files = [
+            ("a.geojson", vec![spec("val1", ColumnType::Double)]),
+            (
+                "b.geojson",
+                vec![
+                    spec("val1", ColumnType::Double),
+                    spec("val3", ColumnType::Varchar),
+                ],
+            ),
+            (
+                "c.geojson",
+                vec![
+                    spec("val1", ColumnType::Varchar),
+                    spec("val2", ColumnType::Varchar),
+                ],
+            ),
+        ];
+
+        // the first mismatch is an error
+        let mut validator = super::SchemaValidator::new(false);
+        let (path, specs) = &files[0];
+        let err = validator
+            .validate(&existing, specs, std::path::Path::new(path))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Schema mismatch in a.geojson: expected 2 columns, found 1"
+        );
+
+        // all the mismatches are reported at once
+        let mut validator = super::SchemaValidator::new(true);
+        validator
+            .validate(&existing, &existing, std::path::Path::new("ok.geojson"))
+            .unwrap();
+        for (path, specs) in &files {
+            validator
+                .validate(&existing, specs, std::path::Path::new(path))
+                .unwrap();
+        }
+        assert_eq!(
+            validator.finish().unwrap_err().to_string(),
+            "Found 3 schema mismatches:
+Schema mismatch in a.geojson: expected 2 columns, found 1
+Schema mismatch in b.geojson: column 1 has name 'val3', expected 'val2'
+Schema mismatch in c.geojson: column 'val1' has type Varchar, expected Double"
+        );
+
+        assert!(super::SchemaValidator::new(true).finish().is_ok());
+    }
+
+    #[test]
+    fn test_merge_schema() {
+        let spec = |name: &str, column_type| ColumnSpec {
+            name: name.to_string(),
+            column_type,
+        };
+        let path = std::path::Path::new("test.gpkg");
+
+        let mut specs = vec![
+            spec("val1", ColumnType::Integer),
+            spec("val2", ColumnType::Varchar),
+        ];
+        super::merge_schema(
+            &mut specs,
+            &[
+                spec("val1", ColumnType::Integer),
+                spec("val3", ColumnType::Double),
+            ],
+            path,
+        )
+        .unwrap();
+
+        let names: Vec<&str> = specs.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["val1", "val2", "val3"]);
+
+        assert!(
+            super::merge_schema(&mut specs, &[spec("val1", ColumnType::Varchar)], path).is_err()
+        );
+    }
+
+    #[test]
+    fn test_round_coordinates() {
+        let mut geometry: geo_types::Geometry<f64> =
+            geo_types::Geometry::MultiPolygon(geo_types::MultiPolygon::new(vec![
+                geo_types::Polygon::new(
+                    geo_types::LineString::from(vec![
+                        (0.123456, 0.987654),
+                        (1.000049, 0.0),
+                        (0.0, -1.55555),
+                        (0.123456, 0.987654),
+                    ]),
+                    vec![],
+                ),
+            ]));
+        super::round_coordinates(&mut geometry, 2);
+
+        let expected: geo_types::Geometry<f64> =
+            geo_types::Geometry::MultiPolygon(geo_types::MultiPolygon::new(vec![
+                geo_types::Polygon::new(
+                    geo_types::LineString::from(vec![
+                        (0.12, 0.99),
+                        (1.0, 0.0),
+                        (0.0, -1.56),
+                        (0.12, 0.99),
+                    ]),
+                    vec![],
+                ),
+            ]));
+        assert_eq!(geometry, expected);
+    }
+
+    #[test]
+    fn test_rewrite_wkb() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let point: geo_types::Geometry<f64> = geo_types::Point::new(1.23456, 9.87654).into();
+        let mut wkb = Vec::new();
+        wkb::writer::write_geometry(&mut wkb, &point, &Default::default())?;
+
+        let rounded: geo_types::Geometry<f64> = geo_types::Point::new(1.235, 9.877).into();
+        let mut expected = Vec::new();
+        wkb::writer::write_geometry(&mut expected, &rounded, &Default::default())?;
+
+        assert_eq!(
+            super::rewrite_wkb(&wkb, None, None, Some(3), None)?,
+            expected
+        );
+        assert_eq!(super::rewrite_wkb(&wkb, None, None, None, None)?, wkb);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_centroid_wkb() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let polygon: geo_types::Geometry<f64> = geo_types::Polygon::new(
+            vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0), (0.0, 0.0)].into(),
+            vec![],
+        )
+        .into();
+        let mut wkb = Vec::new();
+        wkb::writer::write_geometry(&mut wkb, &polygon, &Default::default())?;
+
+        let point: geo_types::Geometry<f64> = geo_types::Point::new(1.0, 1.0).into();
+        let mut expected = Vec::new();
+        wkb::writer::write_geometry(&mut expected, &point, &Default::default())?;
+
+        assert_eq!(super::centroid_wkb(&wkb, None)?, Some(expected));
+
+        // GEOMETRYCOLLECTION EMPTY
+        let empty = [1, 7, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(super::centroid_wkb(&empty, None)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrite_wkb_drops_z() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // POINT Z (1 2 3) in ISO WKB
+        let mut wkb_3d = vec![0x01];
+        wkb_3d.extend_from_slice(&1001u32.to_le_bytes());
+        for v in [1f64, 2.0, 3.0] {
+            wkb_3d.extend_from_slice(&v.to_le_bytes());
+        }
+
+        let point: geo_types::Geometry<f64> = geo_types::Point::new(1.0, 2.0).into();
+        let mut expected = Vec::new();
+        wkb::writer::write_geometry(&mut expected, &point, &Default::default())?;
+
+        assert_eq!(
+            super::rewrite_wkb(&wkb_3d, None, None, None, None)?,
+            expected
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_has_non_finite_coords() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let to_wkb = |geometry: geo_types::Geometry<f64>| {
+            let mut buffer = Vec::new();
+            wkb::writer::write_geometry(&mut buffer, &geometry, &Default::default()).unwrap();
+            buffer
+        };
+
+        let finite = geo_types::line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 1.0)];
+        assert!(!super::has_non_finite_coords(&to_wkb(finite.into()))?);
+        let infinite = geo_types::line_string![(x: 0.0, y: 0.0), (x: f64::INFINITY, y: 1.0)];
+        assert!(super::has_non_finite_coords(&to_wkb(infinite.into()))?);
+
+        // POINT EMPTY
+        let mut empty = vec![0x01, 0x01, 0x00, 0x00, 0x00];
+        empty.extend_from_slice(&f64::NAN.to_le_bytes());
+        empty.extend_from_slice(&f64::NAN.to_le_bytes());
+        assert!(!super::has_non_finite_coords(&empty)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_collection_mode() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        use crate::types::CollectionMode;
+
+        let point: geo_types::Geometry<f64> = geo_types::Point::new(1.0, 2.0).into();
+        let line: geo_types::Geometry<f64> =
+            geo_types::LineString::from(vec![(0.0, 0.0), (1.0, 1.0)]).into();
+        let collection: geo_types::Geometry<f64> =
+            geo_types::GeometryCollection::new_from(vec![point.clone(), line]).into();
+        let empty: geo_types::Geometry<f64> =
+            geo_types::GeometryCollection::<f64>::new_from(vec![]).into();
+
+        let apply =
+            |g: &geo_types::Geometry<f64>, mode| super::apply_collection_mode(g.clone(), mode);
+        assert_eq!(
+            apply(&collection, CollectionMode::Keep),
+            Some(collection.clone())
+        );
+        assert_eq!(
+            apply(&collection, CollectionMode::First),
+            Some(point.clone())
+        );
+        assert_eq!(apply(&collection, CollectionMode::Null), None);
+        assert_eq!(apply(&empty, CollectionMode::First), None);
+        // not a collection
+        assert_eq!(apply(&point, CollectionMode::Null), Some(point.clone()));
+
+        let to_wkb = |g: &geo_types::Geometry<f64>| {
+            let mut buffer = Vec::new();
+            wkb::writer::write_geometry(&mut buffer, g, &Default::default()).unwrap();
+            buffer
+        };
+        let apply_wkb = |g: &geo_types::Geometry<f64>, mode| {
+            super::apply_collection_mode_wkb(&to_wkb(g), mode).map(|wkb| wkb.map(|w| w.to_vec()))
+        };
+        assert_eq!(
+            apply_wkb(&collection, CollectionMode::Keep)?,
+            Some(to_wkb(&collection))
+        );
+        assert_eq!(
+            apply_wkb(&collection, CollectionMode::First)?,
+            Some(to_wkb(&point))
+        );
+        assert_eq!(apply_wkb(&collection, CollectionMode::Null)?, None);
+        assert_eq!(apply_wkb(&empty, CollectionMode::First)?, None);
+        assert_eq!(
+            apply_wkb(&point, CollectionMode::Null)?,
+            Some(to_wkb(&point))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_orient_polygons() {
+        use crate::types::Winding;
+        use geo::Winding as _;
+
+        // a clockwise exterior ring with a clockwise hole
+        let polygon = geo_types::Polygon::new(
+            vec![(0.0, 0.0), (0.0, 4.0), (4.0, 4.0), (4.0, 0.0), (0.0, 0.0)].into(),
+            vec![vec![(1.0, 1.0), (1.0, 2.0), (2.0, 2.0), (2.0, 1.0), (1.0, 1.0)].into()],
+        );
+        let multi_polygon = geo_types::MultiPolygon::new(vec![polygon.clone(), polygon.clone()]);
+
+        let mut geometry: geo_types::Geometry<f64> = polygon.clone().into();
+        super::orient_polygons(&mut geometry, Winding::Ccw);
+        let geo_types::Geometry::Polygon(p) = geometry else {
+            panic!("not a polygon");
+        };
+        assert!(p.exterior().is_ccw());
+        assert!(p.interiors().iter().all(|r| r.is_cw()));
+
+        let mut geometry: geo_types::Geometry<f64> = multi_polygon.into();
+        super::orient_polygons(&mut geometry, Winding::Cw);
+        let geo_types::Geometry::MultiPolygon(mp) = geometry else {
+            panic!("not a multipolygon");
+        };
+        for p in &mp.0 {
+            assert!(p.exterior().is_cw());
+            assert!(p.interiors().iter().all(|r| r.is_ccw()));
+        }
+
+        // the rings are reoriented in the WKB as well
+        let mut wkb = Vec::new();
+        wkb::writer::write_geometry(
+            &mut wkb,
+            &geo_types::Geometry::Polygon(polygon.clone()),
+            &Default::default(),
+        )
+        .unwrap();
+        let mut expected_polygon = polygon;
+        expected_polygon.exterior_mut(|r| r.0.reverse());
+        let mut expected = Vec::new();
+        wkb::writer::write_geometry(
+            &mut expected,
+            &geo_types::Geometry::Polygon(expected_polygon),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            super::rewrite_wkb(&wkb, None, None, None, Some(Winding::Ccw)).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_densify() {
+        use geo_traits::to_geo::ToGeoGeometry;
+
+        let line = geo_types::Geometry::LineString(geo_types::LineString::from(vec![
+            (0.0, 0.0),
+            (10.0, 0.0),
+        ]));
+        let mut wkb = Vec::new();
+        wkb::writer::write_geometry(&mut wkb, &line, &Default::default()).unwrap();
+
+        let densified = super::rewrite_wkb(&wkb, None, Some(3.0), None, None).unwrap();
+        let geometry = wkb::reader::read_wkb(&densified).unwrap().to_geometry();
+        let geo_types::Geometry::LineString(densified) = geometry else {
+            panic!("not a LineString");
+        };
+        assert!(densified.0.len() > 2);
+        assert_eq!(densified.0.first(), Some(&(0.0, 0.0).into()));
+        assert_eq!(densified.0.last(), Some(&(10.0, 0.0).into()));
+        assert!(densified
+            .lines()
+            .all(|l| (l.end.x - l.start.x).abs() <= 3.0));
+    }
+
+    #[test]
+    fn test_buffer() {
+        let mut geometry = geo_types::Geometry::Point(geo_types::Point::new(10.0, 20.0));
+        super::buffer(&mut geometry, 2.0);
+
+        // The point becomes a 32-sided polygon approximating a circle
+        let geo_types::Geometry::Polygon(polygon) = geometry else {
+            panic!("not a Polygon");
+        };
+        let exterior = polygon.exterior();
+        assert_eq!(exterior.0.len(), 33);
+        assert!(exterior.is_closed());
+        for c in exterior.coords() {
+            let distance = ((c.x - 10.0).powi(2) + (c.y - 20.0).powi(2)).sqrt();
+            assert!((distance - 2.0).abs() < 1e-6, "{c:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_list_value() {
+        assert_eq!(super::parse_list_value("[]"), Vec::<String>::new());
+        assert_eq!(super::parse_list_value("[a]"), vec!["a"]);
+        assert_eq!(
+            super::parse_list_value("[data/_temp_*.geojson, b]"),
+            vec!["data/_temp_*.geojson", "b"]
+        );
+        assert_eq!(
+            super::parse_list_value("['a, b', 'it''s']"),
+            vec!["a, b", "it's"]
+        );
+    }
+}
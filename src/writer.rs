@@ -0,0 +1,242 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use duckdb::{
+    core::{DataChunkHandle, LogicalTypeHandle, LogicalTypeId},
+    types::ValueRef,
+    vtab::{BindInfo, InitInfo, TableFunctionInfo, VTab},
+    Connection, Rows,
+};
+
+use crate::{geojson::wkb_to_geojson_geometry, utils::expand_tilde};
+
+const DEFAULT_GEOMETRY_COLUMN: &str = "geometry";
+
+// The query is executed on a separate connection because a table function cannot
+// take a relation as input.
+pub(crate) type WriterConnection = Arc<Mutex<Connection>>;
+
+#[repr(C)]
+pub struct StWriteGeoJsonBindData {
+    conn: WriterConnection,
+    query: String,
+    path: String,
+    geometry_column: String,
+}
+
+#[repr(C)]
+pub struct StWriteGeoJsonInitData {
+    done: AtomicBool,
+}
+
+pub(crate) struct StWriteGeoJsonVTab;
+
+impl VTab for StWriteGeoJsonVTab {
+    type InitData = StWriteGeoJsonInitData;
+    type BindData = StWriteGeoJsonBindData;
+
+    fn bind(bind: &BindInfo) -> Result<Self::BindData, Box<dyn std::error::Error>> {
+        let query = bind.get_parameter(0).to_string();
+        let path = expand_tilde(&bind.get_parameter(1).to_string());
+        let geometry_column = bind
+            .get_named_parameter("geometry_column")
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| DEFAULT_GEOMETRY_COLUMN.to_string());
+
+        let conn = unsafe { &*bind.get_extra_info::<WriterConnection>() }.clone();
+
+        // the number of the features written
+        bind.add_result_column("count", LogicalTypeId::Bigint.into());
+
+        Ok(StWriteGeoJsonBindData {
+            conn,
+            query,
+            path,
+            geometry_column,
+        })
+    }
+
+    fn init(_: &InitInfo) -> Result<Self::InitData, Box<dyn std::error::Error>> {
+        Ok(StWriteGeoJsonInitData {
+            done: AtomicBool::new(false),
+        })
+    }
+
+    fn func(
+        func: &TableFunctionInfo<Self>,
+        output: &mut DataChunkHandle,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let init_data = func.get_init_data();
+        let bind_data = func.get_bind_data();
+
+        if init_data.done.swap(true, Ordering::Relaxed) {
+            output.set_len(0);
+            return Ok(());
+        }
+
+        let conn = bind_data.conn.lock().map_err(|e| e.to_string())?;
+        let n_features = query_to_file(
+            &conn,
+            &bind_data.query,
+            &bind_data.geometry_column,
+            &bind_data.path,
+        )?;
+
+        unsafe {
+            output.flat_vector(0).as_mut_slice::<i64>()[0] = n_features as i64;
+        }
+        output.set_len(1);
+
+        Ok(())
+    }
+
+    fn parameters() -> Option<Vec<LogicalTypeHandle>> {
+        Some(vec![
+            LogicalTypeId::Varchar.into(),
+            LogicalTypeId::Varchar.into(),
+        ])
+    }
+
+    fn named_parameters() -> Option<Vec<(String, LogicalTypeHandle)>> {
+        Some(vec![(
+            "geometry_column".into(),
+            LogicalTypeId::Varchar.into(),
+        )])
+    }
+}
+
+// The features are written one by one as the rows are fetched, so the query result
+// doesn't need to fit in memory. They are written to a temporary file first so that
+// an error in the middle of the query doesn't leave a broken file.
+fn query_to_file(
+    conn: &Connection,
+    query: &str,
+    geometry_column: &str,
+    path: &str,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut stmt = conn.prepare(query)?;
+    let mut rows = stmt.query([])?;
+    let column_names = rows
+        .as_ref()
+        .ok_or("Failed to execute the query")?
+        .column_names();
+
+    let geometry_idx = column_names
+        .iter()
+        .position(|name| name == geometry_column)
+        .ok_or_else(|| format!("The query result doesn't have column '{geometry_column}'"))?;
+
+    let tmp_path = format!("{path}.tmp");
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+    let result = write_feature_collection(
+        &mut rows,
+        &column_names,
+        geometry_idx,
+        geometry_column,
+        &mut writer,
+    );
+    drop(writer);
+
+    match result {
+        Ok(n_features) => {
+            std::fs::rename(&tmp_path, path)?;
+            Ok(n_features)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp_path);
+            Err(e)
+        }
+    }
+}
+
+// This writes the same JSON as serializing a geojson::FeatureCollection without
+// bbox and foreign members.
+fn write_feature_collection<W: Write>(
+    rows: &mut Rows,
+    column_names: &[String],
+    geometry_idx: usize,
+    geometry_column: &str,
+    writer: &mut W,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    writer.write_all(br#"{"type":"FeatureCollection","features":["#)?;
+
+    let mut n_features = 0;
+    while let Some(row) = rows.next()? {
+        let mut properties = geojson::JsonObject::new();
+        let mut geometry: Option<geojson::Geometry> = None;
+
+        for (i, name) in column_names.iter().enumerate() {
+            let val = row.get_ref(i)?;
+
+            if i == geometry_idx {
+                geometry = match val {
+                    ValueRef::Null => None,
+                    ValueRef::Blob(wkb) => Some(wkb_to_geojson_geometry(wkb)?),
+                    _ => {
+                        return Err(
+                            format!("Geometry column '{geometry_column}' must be WKB BLOB").into(),
+                        )
+                    }
+                };
+                continue;
+            }
+
+            properties.insert(name.clone(), value_to_json(name, val)?);
+        }
+
+        let feature = geojson::Feature {
+            bbox: None,
+            geometry,
+            id: None,
+            properties: Some(properties),
+            foreign_members: None,
+        };
+        if n_features > 0 {
+            writer.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut *writer, &feature)?;
+        n_features += 1;
+    }
+
+    writer.write_all(b"]}")?;
+    writer.flush()?;
+    Ok(n_features)
+}
+
+// Only the types that can be read by ST_Read_Multi are supported for now. Other
+// types need to be casted to VARCHAR in the query.
+fn value_to_json(
+    name: &str,
+    val: ValueRef,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let json_val = match val {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Boolean(v) => v.into(),
+        ValueRef::TinyInt(v) => v.into(),
+        ValueRef::SmallInt(v) => v.into(),
+        ValueRef::Int(v) => v.into(),
+        ValueRef::BigInt(v) => v.into(),
+        ValueRef::UTinyInt(v) => v.into(),
+        ValueRef::USmallInt(v) => v.into(),
+        ValueRef::UInt(v) => v.into(),
+        ValueRef::UBigInt(v) => v.into(),
+        // NaN and infinity are converted to null
+        ValueRef::Float(v) => v.into(),
+        ValueRef::Double(v) => v.into(),
+        ValueRef::Text(v) => String::from_utf8_lossy(v).into(),
+        _ => {
+            return Err(format!(
+                "Unsupported type of column '{name}' (consider casting it to VARCHAR)"
+            )
+            .into())
+        }
+    };
+
+    Ok(json_val)
+}
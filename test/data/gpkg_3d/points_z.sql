@@ -0,0 +1,48 @@
+-- Test GeoPackage with 3D (POINT Z) geometries.
+
+CREATE TABLE gpkg_spatial_ref_sys (
+  srs_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL PRIMARY KEY,
+  organization TEXT NOT NULL,
+  organization_coordsys_id INTEGER NOT NULL,
+  definition TEXT NOT NULL,
+  description TEXT
+);
+INSERT INTO gpkg_spatial_ref_sys VALUES
+  ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', ''),
+  ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', ''),
+  ('WGS 84 geographic 2D', 4326, 'EPSG', 4326, 'GEOGCS["WGS 84"]', '');
+
+CREATE TABLE gpkg_contents (
+  table_name TEXT NOT NULL PRIMARY KEY,
+  data_type TEXT NOT NULL,
+  identifier TEXT,
+  description TEXT DEFAULT '',
+  last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+  min_x REAL, min_y REAL, max_x REAL, max_y REAL,
+  srs_id INTEGER REFERENCES gpkg_spatial_ref_sys(srs_id)
+);
+INSERT INTO gpkg_contents VALUES
+  ('points_z', 'features', 'points_z', '', '2025-01-01T00:00:00Z', 1.0, 2.0, 10.0, 20.0, 4326);
+
+CREATE TABLE gpkg_geometry_columns (
+  table_name TEXT NOT NULL,
+  column_name TEXT NOT NULL,
+  geometry_type_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL,
+  z TINYINT NOT NULL,
+  m TINYINT NOT NULL,
+  CONSTRAINT pk_geom_cols PRIMARY KEY (table_name, column_name)
+);
+INSERT INTO gpkg_geometry_columns VALUES
+  ('points_z', 'geom', 'POINT', 4326, 1, 0);
+
+-- POINT Z (1 2 3) and POINT Z (10 20 30) in ISO WKB (type 1001)
+CREATE TABLE "points_z" (
+  "fid" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  "geom" POINT,
+  "val" TEXT
+);
+INSERT INTO "points_z" ("geom", "val") VALUES
+  (X'47500001E610000001E9030000000000000000F03F00000000000000400000000000000840', 'a'),
+  (X'47500001E610000001E9030000000000000000244000000000000034400000000000003E40', 'b');
@@ -0,0 +1,47 @@
+-- Test GeoPackage whose columns are declared in the different order from b.gpkg.
+
+CREATE TABLE gpkg_spatial_ref_sys (
+  srs_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL PRIMARY KEY,
+  organization TEXT NOT NULL,
+  organization_coordsys_id INTEGER NOT NULL,
+  definition TEXT NOT NULL,
+  description TEXT
+);
+INSERT INTO gpkg_spatial_ref_sys VALUES
+  ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', ''),
+  ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', ''),
+  ('WGS 84 geographic 2D', 4326, 'EPSG', 4326, 'GEOGCS["WGS 84"]', '');
+
+CREATE TABLE gpkg_contents (
+  table_name TEXT NOT NULL PRIMARY KEY,
+  data_type TEXT NOT NULL,
+  identifier TEXT,
+  description TEXT DEFAULT '',
+  last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+  min_x REAL, min_y REAL, max_x REAL, max_y REAL,
+  srs_id INTEGER REFERENCES gpkg_spatial_ref_sys(srs_id)
+);
+INSERT INTO gpkg_contents VALUES
+  ('points', 'features', 'points', '', '2025-01-01T00:00:00Z', 1.0, 2.0, 10.0, 20.0, 4326);
+
+CREATE TABLE gpkg_geometry_columns (
+  table_name TEXT NOT NULL,
+  column_name TEXT NOT NULL,
+  geometry_type_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL,
+  z TINYINT NOT NULL,
+  m TINYINT NOT NULL,
+  CONSTRAINT pk_geom_cols PRIMARY KEY (table_name, column_name)
+);
+INSERT INTO gpkg_geometry_columns VALUES
+  ('points', 'geom', 'POINT', 4326, 0, 0);
+
+CREATE TABLE "points" (
+  "fid" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  "geom" POINT,
+  "val1" MEDIUMINT,
+  "val2" TEXT
+);
+INSERT INTO "points" ("geom", "val1", "val2") VALUES
+  (X'47500001E61000000101000000000000000000F03F0000000000000040', 1, 'a');
@@ -0,0 +1,47 @@
+-- Test GeoPackage-like SQLite database with DATETIME columns in various formats.
+
+CREATE TABLE gpkg_spatial_ref_sys (
+  srs_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL PRIMARY KEY,
+  organization TEXT NOT NULL,
+  organization_coordsys_id INTEGER NOT NULL,
+  definition TEXT NOT NULL,
+  description TEXT
+);
+INSERT INTO gpkg_spatial_ref_sys VALUES
+  ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', ''),
+  ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', '');
+
+CREATE TABLE gpkg_contents (
+  table_name TEXT NOT NULL PRIMARY KEY,
+  data_type TEXT NOT NULL,
+  identifier TEXT,
+  description TEXT DEFAULT '',
+  last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+  min_x REAL, min_y REAL, max_x REAL, max_y REAL,
+  srs_id INTEGER REFERENCES gpkg_spatial_ref_sys(srs_id)
+);
+INSERT INTO gpkg_contents VALUES
+  ('datetimes', 'attributes', 'datetimes', '', '2025-01-01T00:00:00Z', NULL, NULL, NULL, NULL, NULL);
+
+CREATE TABLE gpkg_geometry_columns (
+  table_name TEXT NOT NULL,
+  column_name TEXT NOT NULL,
+  geometry_type_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL,
+  z TINYINT NOT NULL,
+  m TINYINT NOT NULL,
+  CONSTRAINT pk_geom_cols PRIMARY KEY (table_name, column_name)
+);
+
+CREATE TABLE "datetimes" (
+  "fid" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  "dt_utc" DATETIME,
+  "dt_offset" DATETIME,
+  "dt_date_only" DATETIME,
+  "dt_invalid" DATETIME
+);
+
+INSERT INTO "datetimes" ("dt_utc", "dt_offset", "dt_date_only", "dt_invalid") VALUES
+  ('2024-01-15T10:30:00Z', '2024-01-15T19:30:00+09:00', '2024-01-15', 'yesterday'),
+  ('2024-06-30T23:59:59.999Z', '2024-06-30 18:59:59.999-05:00', '2024-06-30', NULL);
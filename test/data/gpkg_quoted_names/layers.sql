@@ -0,0 +1,52 @@
+-- Test GeoPackage whose layer and column names contain quotes and spaces.
+
+CREATE TABLE gpkg_spatial_ref_sys (
+  srs_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL PRIMARY KEY,
+  organization TEXT NOT NULL,
+  organization_coordsys_id INTEGER NOT NULL,
+  definition TEXT NOT NULL,
+  description TEXT
+);
+INSERT INTO gpkg_spatial_ref_sys VALUES
+  ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', ''),
+  ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', ''),
+  ('WGS 84 geographic 2D', 4326, 'EPSG', 4326, 'GEOGCS["WGS 84"]', '');
+
+CREATE TABLE gpkg_contents (
+  table_name TEXT NOT NULL PRIMARY KEY,
+  data_type TEXT NOT NULL,
+  identifier TEXT,
+  description TEXT DEFAULT '',
+  last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+  min_x REAL, min_y REAL, max_x REAL, max_y REAL,
+  srs_id INTEGER REFERENCES gpkg_spatial_ref_sys(srs_id)
+);
+INSERT INTO gpkg_contents VALUES
+  ('my layer''s data', 'features', 'my layer''s data', '', '2025-01-01T00:00:00Z', 1.0, 2.0, 10.0, 20.0, 4326);
+
+CREATE TABLE gpkg_geometry_columns (
+  table_name TEXT NOT NULL,
+  column_name TEXT NOT NULL,
+  geometry_type_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL,
+  z TINYINT NOT NULL,
+  m TINYINT NOT NULL,
+  CONSTRAINT pk_geom_cols PRIMARY KEY (table_name, column_name)
+);
+INSERT INTO gpkg_geometry_columns VALUES
+  ('my layer''s data', 'geom', 'POINT', 4326, 0, 0);
+
+CREATE TABLE "my layer's data" (
+  "fid" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  "geom" POINT,
+  "the ""val""" INTEGER
+);
+INSERT INTO "my layer's data" ("geom", "the ""val""") VALUES
+  (X'47500001E61000000101000000000000000000F03F0000000000000040', 1),
+  (X'47500001E6100000010100000000000000000024400000000000003440', 2);
+
+CREATE VIRTUAL TABLE "rtree_my layer's data_geom" USING rtree(id, minx, maxx, miny, maxy);
+INSERT INTO "rtree_my layer's data_geom" VALUES
+  (1, 1.0, 1.0, 2.0, 2.0),
+  (2, 10.0, 10.0, 20.0, 20.0);
@@ -0,0 +1,105 @@
+-- Test GeoPackage whose feature table has related tables (cf. the Related Tables
+-- Extension). "details" is related one-to-one, and "tags" is one-to-many.
+
+CREATE TABLE gpkg_spatial_ref_sys (
+  srs_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL PRIMARY KEY,
+  organization TEXT NOT NULL,
+  organization_coordsys_id INTEGER NOT NULL,
+  definition TEXT NOT NULL,
+  description TEXT
+);
+INSERT INTO gpkg_spatial_ref_sys VALUES
+  ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', ''),
+  ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', ''),
+  ('WGS 84 geographic 2D', 4326, 'EPSG', 4326, 'GEOGCS["WGS 84"]', '');
+
+CREATE TABLE gpkg_contents (
+  table_name TEXT NOT NULL PRIMARY KEY,
+  data_type TEXT NOT NULL,
+  identifier TEXT,
+  description TEXT DEFAULT '',
+  last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+  min_x REAL, min_y REAL, max_x REAL, max_y REAL,
+  srs_id INTEGER REFERENCES gpkg_spatial_ref_sys(srs_id)
+);
+INSERT INTO gpkg_contents VALUES
+  ('points', 'features', 'points', '', '2025-01-01T00:00:00Z', 1.0, 2.0, 3.0, 4.0, 4326),
+  ('details', 'attributes', 'details', '', '2025-01-01T00:00:00Z', NULL, NULL, NULL, NULL, NULL),
+  ('tags', 'attributes', 'tags', '', '2025-01-01T00:00:00Z', NULL, NULL, NULL, NULL, NULL);
+
+CREATE TABLE gpkg_geometry_columns (
+  table_name TEXT NOT NULL,
+  column_name TEXT NOT NULL,
+  geometry_type_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL,
+  z TINYINT NOT NULL,
+  m TINYINT NOT NULL,
+  CONSTRAINT pk_geom_cols PRIMARY KEY (table_name, column_name)
+);
+INSERT INTO gpkg_geometry_columns VALUES
+  ('points', 'geom', 'POINT', 4326, 0, 0);
+CREATE TABLE gpkg_extensions (
+  table_name TEXT,
+  column_name TEXT,
+  extension_name TEXT NOT NULL,
+  definition TEXT NOT NULL,
+  scope TEXT NOT NULL,
+  CONSTRAINT ge_tce UNIQUE (table_name, column_name, extension_name)
+);
+INSERT INTO gpkg_extensions VALUES
+  ('gpkgext_relations', NULL, 'gpkg_related_tables', 'http://docs.opengeospatial.org/is/18-000/18-000.html', 'read-write'),
+  ('points_details', NULL, 'gpkg_related_tables', 'http://docs.opengeospatial.org/is/18-000/18-000.html', 'read-write'),
+  ('points_tags', NULL, 'gpkg_related_tables', 'http://docs.opengeospatial.org/is/18-000/18-000.html', 'read-write');
+
+CREATE TABLE gpkgext_relations (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  base_table_name TEXT NOT NULL,
+  base_primary_column TEXT NOT NULL DEFAULT 'id',
+  related_table_name TEXT NOT NULL,
+  related_primary_column TEXT NOT NULL DEFAULT 'id',
+  relation_name TEXT NOT NULL,
+  mapping_table_name TEXT NOT NULL UNIQUE
+);
+INSERT INTO gpkgext_relations (base_table_name, base_primary_column, related_table_name, related_primary_column, relation_name, mapping_table_name) VALUES
+  ('points', 'fid', 'details', 'id', 'attributes', 'points_details'),
+  ('points', 'fid', 'tags', 'id', 'attributes', 'points_tags');
+
+CREATE TABLE "points" (
+  "fid" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  "geom" POINT,
+  "name" TEXT
+);
+INSERT INTO "points" ("geom", "name") VALUES
+  (X'47500001E61000000101000000000000000000F03F0000000000000040', 'a'),
+  (X'47500001E610000001010000000000000000000040000000000000F03F', 'b'),
+  (X'47500001E6100000010100000000000000000008400000000000001040', 'c');
+
+-- The point "c" has no related row.
+CREATE TABLE "details" (
+  "id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  "population" INTEGER,
+  "note" TEXT
+);
+INSERT INTO "details" ("id", "population", "note") VALUES
+  (10, 100, 'foo'),
+  (20, 200, 'bar');
+
+CREATE TABLE "points_details" (
+  "base_id" INTEGER NOT NULL,
+  "related_id" INTEGER NOT NULL
+);
+INSERT INTO "points_details" VALUES (1, 10), (2, 20);
+
+-- The point "a" has two tags, so this is not read.
+CREATE TABLE "tags" (
+  "id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  "tag" TEXT
+);
+INSERT INTO "tags" ("id", "tag") VALUES (1, 'x'), (2, 'y');
+
+CREATE TABLE "points_tags" (
+  "base_id" INTEGER NOT NULL,
+  "related_id" INTEGER NOT NULL
+);
+INSERT INTO "points_tags" VALUES (1, 1), (1, 2);
@@ -0,0 +1,61 @@
+-- Test GeoPackage with two layers that have different schemas.
+
+CREATE TABLE gpkg_spatial_ref_sys (
+  srs_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL PRIMARY KEY,
+  organization TEXT NOT NULL,
+  organization_coordsys_id INTEGER NOT NULL,
+  definition TEXT NOT NULL,
+  description TEXT
+);
+INSERT INTO gpkg_spatial_ref_sys VALUES
+  ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', ''),
+  ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', ''),
+  ('WGS 84 geographic 2D', 4326, 'EPSG', 4326, 'GEOGCS["WGS 84"]', '');
+
+CREATE TABLE gpkg_contents (
+  table_name TEXT NOT NULL PRIMARY KEY,
+  data_type TEXT NOT NULL,
+  identifier TEXT,
+  description TEXT DEFAULT '',
+  last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+  min_x REAL, min_y REAL, max_x REAL, max_y REAL,
+  srs_id INTEGER REFERENCES gpkg_spatial_ref_sys(srs_id)
+);
+INSERT INTO gpkg_contents VALUES
+  ('layer_a', 'features', 'layer_a', '', '2025-01-01T00:00:00Z', 1.0, 2.0, 10.0, 20.0, 4326),
+  ('layer_b', 'features', 'layer_b', '', '2025-01-01T00:00:00Z', 100.0, 200.0, 100.0, 200.0, 4326);
+
+CREATE TABLE gpkg_geometry_columns (
+  table_name TEXT NOT NULL,
+  column_name TEXT NOT NULL,
+  geometry_type_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL,
+  z TINYINT NOT NULL,
+  m TINYINT NOT NULL,
+  CONSTRAINT pk_geom_cols PRIMARY KEY (table_name, column_name)
+);
+INSERT INTO gpkg_geometry_columns VALUES
+  ('layer_a', 'geom', 'POINT', 4326, 0, 0),
+  ('layer_b', 'geom', 'POINT', 4326, 0, 0);
+
+-- layer_a has val1 and val2
+CREATE TABLE "layer_a" (
+  "fid" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  "geom" POINT,
+  "val1" MEDIUMINT,
+  "val2" TEXT
+);
+INSERT INTO "layer_a" ("geom", "val1", "val2") VALUES
+  (X'47500001E61000000101000000000000000000F03F0000000000000040', 1, 'a'),
+  (X'47500001E6100000010100000000000000000024400000000000003440', 2, 'b');
+
+-- layer_b has val3 instead of val2
+CREATE TABLE "layer_b" (
+  "fid" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  "geom" POINT,
+  "val1" MEDIUMINT,
+  "val3" DOUBLE
+);
+INSERT INTO "layer_b" ("geom", "val1", "val3") VALUES
+  (X'47500001E6100000010100000000000000000059400000000000006940', 5, 0.5);